@@ -0,0 +1,145 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::command_runner::CommandRunner;
+
+// Built everywhere under test, so the capture pipeline can run on Linux
+// against a fake `screencapture`.
+#[cfg(any(target_os = "macos", test))]
+mod screencapture;
+#[cfg(target_os = "linux")]
+mod portal;
+#[cfg(target_os = "linux")]
+mod x11;
+
+// Capture backends write a PNG to the given output path.
+// Optimization, naming and the rename popup are handled by the caller.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CaptureBackendKind {
+    // screencapture on macOS, Wayland portal or X11 on Linux
+    #[default]
+    Auto,
+    Screencapture,
+    X11,
+    WaylandPortal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureTarget {
+    Fullscreen,
+    InteractiveArea,
+    // 1-based, 1 is the main display (same numbering as `screencapture -D`)
+    Display(u32),
+    // CGWindowID on macOS, X11 window id on Linux
+    Window(u64),
+    // Fixed rectangle in global screen coordinates (points on macOS, pixels on X11)
+    Region(CaptureRegion),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureRegion {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    Cancelled,
+    Unsupported(String),
+    Failed(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::Cancelled => write!(f, "Screenshot cancelled"),
+            CaptureError::Unsupported(e) => write!(f, "Not supported: {}", e),
+            CaptureError::Failed(e) => write!(f, "Screenshot failed: {}", e),
+            CaptureError::Io(e) => write!(f, "Screenshot failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl From<std::io::Error> for CaptureError {
+    fn from(e: std::io::Error) -> Self {
+        CaptureError::Io(e)
+    }
+}
+
+pub trait CaptureBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn capture_fullscreen(&self, output: &Path) -> Result<(), CaptureError>;
+    // Returns the selected region when the backend can report it
+    fn capture_area(&self, output: &Path) -> Result<Option<CaptureRegion>, CaptureError>;
+    fn capture_display(&self, display: u32, output: &Path) -> Result<(), CaptureError>;
+    fn capture_window(&self, window: u64, output: &Path) -> Result<(), CaptureError>;
+    fn capture_region(&self, region: CaptureRegion, output: &Path) -> Result<(), CaptureError>;
+
+    // Returns the region that was captured, if the user selected one and the backend knows it.
+    fn capture(&self, target: CaptureTarget, output: &Path) -> Result<Option<CaptureRegion>, CaptureError> {
+        let region = match target {
+            CaptureTarget::Fullscreen => self.capture_fullscreen(output).map(|_| None),
+            CaptureTarget::InteractiveArea => self.capture_area(output),
+            CaptureTarget::Display(display) => self.capture_display(display, output).map(|_| None),
+            CaptureTarget::Window(window) => self.capture_window(window, output).map(|_| None),
+            CaptureTarget::Region(region) => self.capture_region(region, output).map(|_| None),
+        }?;
+        // Backends that exit cleanly without writing a file were cancelled by the user
+        if output.exists() {
+            Ok(region)
+        } else {
+            Err(CaptureError::Cancelled)
+        }
+    }
+}
+
+pub fn backend_for(
+    kind: CaptureBackendKind,
+    runner: Arc<dyn CommandRunner>,
+) -> Result<Box<dyn CaptureBackend>, CaptureError> {
+    match kind {
+        CaptureBackendKind::Auto => default_backend(runner),
+        #[cfg(any(target_os = "macos", test))]
+        CaptureBackendKind::Screencapture => Ok(Box::new(screencapture::ScreencaptureBackend::new(runner))),
+        #[cfg(target_os = "linux")]
+        CaptureBackendKind::X11 => Ok(Box::new(x11::X11Backend)),
+        #[cfg(target_os = "linux")]
+        CaptureBackendKind::WaylandPortal => Ok(Box::new(portal::PortalBackend)),
+        #[allow(unreachable_patterns)]
+        other => Err(CaptureError::Unsupported(format!(
+            "{:?} capture backend is not available on this platform",
+            other
+        ))),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn default_backend(runner: Arc<dyn CommandRunner>) -> Result<Box<dyn CaptureBackend>, CaptureError> {
+    Ok(Box::new(screencapture::ScreencaptureBackend::new(runner)))
+}
+
+#[cfg(target_os = "linux")]
+fn default_backend(_runner: Arc<dyn CommandRunner>) -> Result<Box<dyn CaptureBackend>, CaptureError> {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var("XDG_SESSION_TYPE").map(|t| t == "wayland").unwrap_or(false);
+    if wayland {
+        Ok(Box::new(portal::PortalBackend))
+    } else {
+        Ok(Box::new(x11::X11Backend))
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn default_backend(_runner: Arc<dyn CommandRunner>) -> Result<Box<dyn CaptureBackend>, CaptureError> {
+    Err(CaptureError::Unsupported("no capture backend for this platform".to_string()))
+}
@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use ashpd::desktop::screenshot::Screenshot;
+
+use super::{CaptureBackend, CaptureError, CaptureRegion};
+
+// xdg-desktop-portal Screenshot interface for Wayland sessions.
+// The compositor owns the selection UI, so only fullscreen and interactive modes exist.
+pub struct PortalBackend;
+
+impl PortalBackend {
+    fn request(&self, interactive: bool, output: &Path) -> Result<(), CaptureError> {
+        let response = tauri::async_runtime::block_on(async {
+            Screenshot::request()
+                .interactive(interactive)
+                .modal(true)
+                .send()
+                .await?
+                .response()
+        });
+
+        let screenshot = match response {
+            Ok(screenshot) => screenshot,
+            Err(ashpd::Error::Response(ashpd::desktop::ResponseError::Cancelled)) => {
+                return Err(CaptureError::Cancelled);
+            }
+            Err(e) => return Err(CaptureError::Failed(format!("Screenshot portal: {}", e))),
+        };
+
+        let source = screenshot
+            .uri()
+            .to_file_path()
+            .map_err(|_| CaptureError::Failed(format!("Unexpected portal URI: {}", screenshot.uri())))?;
+        // The portal saves into the user's Pictures folder; move it to our temp path
+        if std::fs::rename(&source, output).is_err() {
+            std::fs::copy(&source, output)?;
+            let _ = std::fs::remove_file(&source);
+        }
+        Ok(())
+    }
+}
+
+impl CaptureBackend for PortalBackend {
+    fn name(&self) -> &'static str {
+        "wayland-portal"
+    }
+
+    fn capture_fullscreen(&self, output: &Path) -> Result<(), CaptureError> {
+        self.request(false, output)
+    }
+
+    fn capture_area(&self, output: &Path) -> Result<Option<CaptureRegion>, CaptureError> {
+        self.request(true, output).map(|_| None)
+    }
+
+    fn capture_display(&self, _display: u32, _output: &Path) -> Result<(), CaptureError> {
+        Err(CaptureError::Unsupported(
+            "the screenshot portal cannot target a specific display".to_string(),
+        ))
+    }
+
+    fn capture_window(&self, _window: u64, _output: &Path) -> Result<(), CaptureError> {
+        Err(CaptureError::Unsupported(
+            "the screenshot portal cannot target a specific window".to_string(),
+        ))
+    }
+
+    fn capture_region(&self, _region: CaptureRegion, _output: &Path) -> Result<(), CaptureError> {
+        Err(CaptureError::Unsupported(
+            "the screenshot portal cannot capture a fixed region".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{CaptureBackend, CaptureError, CaptureRegion};
+use crate::command_runner::CommandRunner;
+
+// macOS `screencapture`. `-x` silences the shutter sound.
+pub struct ScreencaptureBackend {
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl ScreencaptureBackend {
+    pub fn new(runner: Arc<dyn CommandRunner>) -> Self {
+        Self { runner }
+    }
+
+    fn run(&self, args: &[&str], output: &Path) -> Result<(), CaptureError> {
+        let output_path = output.to_string_lossy();
+        let mut args = args.to_vec();
+        args.push(&output_path);
+        let result = self
+            .runner
+            .output("screencapture", &args)
+            .map_err(|e| CaptureError::Failed(format!("Failed to run screencapture: {}", e)))?;
+
+        if result.success {
+            Ok(())
+        } else if !output.exists() {
+            // Escape during interactive selection exits non-zero without a file
+            Err(CaptureError::Cancelled)
+        } else {
+            Err(CaptureError::Failed(result.stderr.trim().to_string()))
+        }
+    }
+}
+
+impl CaptureBackend for ScreencaptureBackend {
+    fn name(&self) -> &'static str {
+        "screencapture"
+    }
+
+    fn capture_fullscreen(&self, output: &Path) -> Result<(), CaptureError> {
+        self.run(&["-x"], output)
+    }
+
+    // screencapture does not report where the selection was
+    fn capture_area(&self, output: &Path) -> Result<Option<CaptureRegion>, CaptureError> {
+        self.run(&["-i", "-x"], output).map(|_| None)
+    }
+
+    fn capture_display(&self, display: u32, output: &Path) -> Result<(), CaptureError> {
+        self.run(&["-x", "-D", &display.to_string()], output)
+    }
+
+    fn capture_window(&self, window: u64, output: &Path) -> Result<(), CaptureError> {
+        // -o drops the window shadow
+        self.run(&["-x", "-o", "-l", &window.to_string()], output)
+    }
+
+    fn capture_region(&self, region: CaptureRegion, output: &Path) -> Result<(), CaptureError> {
+        let rect = format!("{},{},{},{}", region.x, region.y, region.width, region.height);
+        self.run(&["-x", "-R", &rect], output)
+    }
+}
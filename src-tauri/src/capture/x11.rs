@@ -0,0 +1,587 @@
+use std::path::Path;
+
+use x11rb::connection::Connection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::protocol::randr::ConnectionExt as _;
+use x11rb::protocol::shm::ConnectionExt as _;
+use x11rb::protocol::xproto::{
+    ConnectionExt as _, CreateGCAux, EventMask, GrabMode, GrabStatus, ImageFormat, ImageOrder,
+    Rectangle, SubwindowMode, Window, GX,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::CURRENT_TIME;
+
+use super::{CaptureBackend, CaptureError, CaptureRegion};
+use crate::image_pipeline::{self, RgbaImage};
+
+// X11 capture via XShm (falls back to plain GetImage for remote displays).
+// Works under Xvfb, which has no compositor or portal.
+pub struct X11Backend;
+
+const XK_ESCAPE: u32 = 0xff1b;
+
+fn x11_error(e: impl std::fmt::Display) -> CaptureError {
+    CaptureError::Failed(format!("X11: {}", e))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Region {
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+}
+
+// Clamp a region to the root window; empty regions mean nothing to capture.
+fn clip(region: Region, root_width: u16, root_height: u16) -> Result<Region, CaptureError> {
+    let x0 = (region.x as i32).max(0);
+    let y0 = (region.y as i32).max(0);
+    let x1 = (region.x as i32 + region.width as i32).min(root_width as i32);
+    let y1 = (region.y as i32 + region.height as i32).min(root_height as i32);
+    if x1 <= x0 || y1 <= y0 {
+        return Err(CaptureError::Failed("Region is off screen".to_string()));
+    }
+    Ok(Region {
+        x: x0 as i16,
+        y: y0 as i16,
+        width: (x1 - x0) as u16,
+        height: (y1 - y0) as u16,
+    })
+}
+
+// Coordinates outside the X11 range are off screen anyway
+fn region_from(region: CaptureRegion) -> Region {
+    Region {
+        x: region.x.clamp(i16::MIN.into(), i16::MAX.into()) as i16,
+        y: region.y.clamp(i16::MIN.into(), i16::MAX.into()) as i16,
+        width: region.width.min(u16::MAX.into()) as u16,
+        height: region.height.min(u16::MAX.into()) as u16,
+    }
+}
+
+// `display` is 1-based with the primary monitor first, so that display 1
+// matches macOS "main display". Monitors are `(primary, region)`.
+fn nth_monitor(mut monitors: Vec<(bool, Region)>, display: u32) -> Result<Region, CaptureError> {
+    monitors.sort_by_key(|(primary, _)| !primary);
+    display
+        .checked_sub(1)
+        .and_then(|i| monitors.get(i as usize))
+        .map(|(_, region)| *region)
+        .ok_or_else(|| CaptureError::Failed(format!("Display {} not found ({} connected)", display, monitors.len())))
+}
+
+// Rectangle spanned by a drag from `a` to `b`, in either direction.
+fn drag_rect(a: (i16, i16), b: (i16, i16)) -> Rectangle {
+    Rectangle {
+        x: a.0.min(b.0),
+        y: a.1.min(b.1),
+        // The span of two i16 always fits in a u16
+        width: (a.0 as i32 - b.0 as i32).unsigned_abs() as u16,
+        height: (a.1 as i32 - b.1 as i32).unsigned_abs() as u16,
+    }
+}
+
+// Red, green and blue masks of a TrueColor visual.
+#[derive(Debug, Clone, Copy)]
+struct ChannelMasks {
+    red: u32,
+    green: u32,
+    blue: u32,
+}
+
+// Convert 32 bpp ZPixmap data to RGBA using the visual's channel masks.
+fn zpixmap_to_rgba(width: u16, height: u16, data: &[u8], masks: ChannelMasks, big_endian: bool) -> RgbaImage {
+    let channel = |pixel: u32, mask: u32| ((pixel & mask) >> mask.trailing_zeros()) as u8;
+    let mut image = RgbaImage::new(width as u32, height as u32);
+    for (src, dst) in data.chunks_exact(4).zip(image.pixels.chunks_exact_mut(4)) {
+        let bytes = [src[0], src[1], src[2], src[3]];
+        let pixel = if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        };
+        dst[0] = channel(pixel, masks.red);
+        dst[1] = channel(pixel, masks.green);
+        dst[2] = channel(pixel, masks.blue);
+        dst[3] = 255;
+    }
+    image
+}
+
+struct Session {
+    conn: RustConnection,
+    root: Window,
+    root_width: u16,
+    root_height: u16,
+}
+
+impl Session {
+    fn connect() -> Result<Self, CaptureError> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(x11_error)?;
+        let screen = &conn.setup().roots[screen_num];
+        let (root, root_width, root_height) =
+            (screen.root, screen.width_in_pixels, screen.height_in_pixels);
+        Ok(Self {
+            conn,
+            root,
+            root_width,
+            root_height,
+        })
+    }
+
+    fn full_region(&self) -> Region {
+        Region {
+            x: 0,
+            y: 0,
+            width: self.root_width,
+            height: self.root_height,
+        }
+    }
+
+    fn grab(&self, region: Region) -> Result<RgbaImage, CaptureError> {
+        let (depth, visual, data) = match self.grab_shm(region) {
+            Ok(grabbed) => grabbed,
+            Err(e) => {
+                println!("[capture] XShm unavailable ({}), using GetImage", e);
+                let reply = self
+                    .conn
+                    .get_image(
+                        ImageFormat::Z_PIXMAP,
+                        self.root,
+                        region.x,
+                        region.y,
+                        region.width,
+                        region.height,
+                        !0,
+                    )
+                    .map_err(x11_error)?
+                    .reply()
+                    .map_err(x11_error)?;
+                (reply.depth, reply.visual, reply.data)
+            }
+        };
+        self.to_rgba(region, depth, visual, &data)
+    }
+
+    fn grab_shm(&self, region: Region) -> Result<(u8, u32, Vec<u8>), CaptureError> {
+        self.conn
+            .shm_query_version()
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+
+        let size = region.width as usize * region.height as usize * 4;
+        let shmid = unsafe { libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600) };
+        if shmid < 0 {
+            return Err(CaptureError::Io(std::io::Error::last_os_error()));
+        }
+        let addr = unsafe { libc::shmat(shmid, std::ptr::null(), 0) };
+        if addr as isize == -1 {
+            let err = std::io::Error::last_os_error();
+            unsafe { libc::shmctl(shmid, libc::IPC_RMID, std::ptr::null_mut()) };
+            return Err(CaptureError::Io(err));
+        }
+
+        let result = (|| {
+            let seg = self.conn.generate_id().map_err(x11_error)?;
+            self.conn
+                .shm_attach(seg, shmid as u32, false)
+                .map_err(x11_error)?
+                .check()
+                .map_err(x11_error)?;
+            let reply = self
+                .conn
+                .shm_get_image(
+                    self.root,
+                    region.x,
+                    region.y,
+                    region.width,
+                    region.height,
+                    !0,
+                    ImageFormat::Z_PIXMAP.into(),
+                    seg,
+                    0,
+                )
+                .map_err(x11_error)?
+                .reply();
+            let _ = self.conn.shm_detach(seg);
+            let reply = reply.map_err(x11_error)?;
+            let len = (reply.size as usize).min(size);
+            let data = unsafe { std::slice::from_raw_parts(addr as *const u8, len) }.to_vec();
+            Ok((reply.depth, reply.visual, data))
+        })();
+
+        unsafe {
+            libc::shmdt(addr);
+            libc::shmctl(shmid, libc::IPC_RMID, std::ptr::null_mut());
+        }
+        result
+    }
+
+    // Convert a ZPixmap (24/32-bit TrueColor) to RGBA.
+    fn to_rgba(&self, region: Region, depth: u8, visual: u32, data: &[u8]) -> Result<RgbaImage, CaptureError> {
+        let setup = self.conn.setup();
+        let bits_per_pixel = setup
+            .pixmap_formats
+            .iter()
+            .find(|f| f.depth == depth)
+            .map(|f| f.bits_per_pixel)
+            .unwrap_or(0);
+        if bits_per_pixel != 32 {
+            return Err(CaptureError::Unsupported(format!(
+                "{}-bit X11 visuals ({} bpp)",
+                depth, bits_per_pixel
+            )));
+        }
+
+        let visual_type = setup
+            .roots
+            .iter()
+            .flat_map(|s| s.allowed_depths.iter())
+            .flat_map(|d| d.visuals.iter())
+            .find(|v| v.visual_id == visual)
+            .ok_or_else(|| x11_error(format!("unknown visual {}", visual)))?;
+        let masks = ChannelMasks {
+            red: visual_type.red_mask,
+            green: visual_type.green_mask,
+            blue: visual_type.blue_mask,
+        };
+        let big_endian = setup.image_byte_order == ImageOrder::MSB_FIRST;
+        Ok(zpixmap_to_rgba(region.width, region.height, data, masks, big_endian))
+    }
+
+    fn monitor_region(&self, display: u32) -> Result<Region, CaptureError> {
+        let reply = self
+            .conn
+            .randr_get_monitors(self.root, true)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        let monitors = reply
+            .monitors
+            .iter()
+            .map(|m| {
+                let region = Region {
+                    x: m.x,
+                    y: m.y,
+                    width: m.width,
+                    height: m.height,
+                };
+                (m.primary, region)
+            })
+            .collect();
+        nth_monitor(monitors, display)
+    }
+
+    fn window_region(&self, window: Window) -> Result<Region, CaptureError> {
+        let geometry = self
+            .conn
+            .get_geometry(window)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(|_| CaptureError::Failed(format!("Window {:#x} not found", window)))?;
+        let origin = self
+            .conn
+            .translate_coordinates(window, self.root, 0, 0)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        self.clip(Region {
+            x: origin.dst_x,
+            y: origin.dst_y,
+            width: geometry.width,
+            height: geometry.height,
+        })
+    }
+
+    fn clip(&self, region: Region) -> Result<Region, CaptureError> {
+        clip(region, self.root_width, self.root_height)
+    }
+
+    fn escape_keycodes(&self) -> Result<Vec<u8>, CaptureError> {
+        let setup = self.conn.setup();
+        let count = setup.max_keycode - setup.min_keycode + 1;
+        let mapping = self
+            .conn
+            .get_keyboard_mapping(setup.min_keycode, count)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        let per_keycode = mapping.keysyms_per_keycode.max(1) as usize;
+        Ok(mapping
+            .keysyms
+            .chunks(per_keycode)
+            .enumerate()
+            .filter(|(_, syms)| syms.contains(&XK_ESCAPE))
+            .map(|(i, _)| setup.min_keycode + i as u8)
+            .collect())
+    }
+
+    // Rubber-band selection drawn with an XOR GC on the root window.
+    // Left drag selects, Escape or right click cancels.
+    fn select_region(&self) -> Result<Region, CaptureError> {
+        let conn = &self.conn;
+        let screen_num = conn
+            .setup()
+            .roots
+            .iter()
+            .position(|s| s.root == self.root)
+            .unwrap_or(0);
+        let resources = x11rb::resource_manager::new_from_default(conn).map_err(x11_error)?;
+        let cursor = x11rb::cursor::Handle::new(conn, screen_num, &resources)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?
+            .load_cursor(conn, "crosshair")
+            .map_err(x11_error)?;
+        let escape = self.escape_keycodes()?;
+
+        let gc = conn.generate_id().map_err(x11_error)?;
+        conn.create_gc(
+            gc,
+            self.root,
+            &CreateGCAux::new()
+                .function(GX::XOR)
+                .foreground(0xffffff)
+                .subwindow_mode(SubwindowMode::INCLUDE_INFERIORS)
+                .line_width(1),
+        )
+        .map_err(x11_error)?;
+
+        let mask = EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::POINTER_MOTION;
+        let grab = conn
+            .grab_pointer(false, self.root, mask, GrabMode::ASYNC, GrabMode::ASYNC, self.root, cursor, CURRENT_TIME)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?;
+        if grab.status != GrabStatus::SUCCESS {
+            let _ = conn.free_gc(gc);
+            return Err(CaptureError::Failed("Could not grab the pointer".to_string()));
+        }
+        let _ = conn.grab_keyboard(false, self.root, CURRENT_TIME, GrabMode::ASYNC, GrabMode::ASYNC);
+        conn.flush().map_err(x11_error)?;
+
+        let mut anchor: Option<(i16, i16)> = None;
+        let mut drawn: Option<Rectangle> = None;
+        let result = loop {
+            let event = match conn.wait_for_event() {
+                Ok(event) => event,
+                Err(e) => break Err(x11_error(e)),
+            };
+            match event {
+                Event::ButtonPress(e) if e.detail == 1 => anchor = Some((e.root_x, e.root_y)),
+                Event::ButtonPress(_) => break Err(CaptureError::Cancelled),
+                Event::KeyPress(e) if escape.contains(&e.detail) => break Err(CaptureError::Cancelled),
+                Event::MotionNotify(e) => {
+                    if let Some(start) = anchor {
+                        let rect = drag_rect(start, (e.root_x, e.root_y));
+                        // Drawing the same rectangle twice with XOR erases it
+                        let mut rects = drawn.take().into_iter().collect::<Vec<_>>();
+                        rects.push(rect);
+                        let _ = conn.poly_rectangle(self.root, gc, &rects);
+                        let _ = conn.flush();
+                        drawn = Some(rect);
+                    }
+                }
+                Event::ButtonRelease(e) if e.detail == 1 => {
+                    if let Some(start) = anchor {
+                        break Ok(drag_rect(start, (e.root_x, e.root_y)));
+                    }
+                }
+                _ => {}
+            }
+        };
+
+        if let Some(rect) = drawn {
+            let _ = conn.poly_rectangle(self.root, gc, &[rect]);
+        }
+        let _ = conn.ungrab_keyboard(CURRENT_TIME);
+        let _ = conn.ungrab_pointer(CURRENT_TIME);
+        let _ = conn.free_gc(gc);
+        let _ = conn.free_cursor(cursor);
+        // Make sure the outline is gone before pixels are read back
+        conn.sync().map_err(x11_error)?;
+
+        let rect = result?;
+        if rect.width < 2 || rect.height < 2 {
+            return Err(CaptureError::Cancelled);
+        }
+        self.clip(Region {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+        })
+    }
+
+    fn capture_to(&self, region: Region, output: &Path) -> Result<(), CaptureError> {
+        let image = self.grab(region)?;
+        let bytes = image_pipeline::encode_png(&image).map_err(|e| CaptureError::Failed(e.to_string()))?;
+        std::fs::write(output, bytes)?;
+        Ok(())
+    }
+}
+
+impl CaptureBackend for X11Backend {
+    fn name(&self) -> &'static str {
+        "x11"
+    }
+
+    fn capture_fullscreen(&self, output: &Path) -> Result<(), CaptureError> {
+        let session = Session::connect()?;
+        session.capture_to(session.full_region(), output)
+    }
+
+    fn capture_area(&self, output: &Path) -> Result<Option<CaptureRegion>, CaptureError> {
+        let session = Session::connect()?;
+        let region = session.select_region()?;
+        session.capture_to(region, output)?;
+        Ok(Some(CaptureRegion {
+            x: region.x.into(),
+            y: region.y.into(),
+            width: region.width.into(),
+            height: region.height.into(),
+        }))
+    }
+
+    fn capture_display(&self, display: u32, output: &Path) -> Result<(), CaptureError> {
+        let session = Session::connect()?;
+        let region = session.monitor_region(display)?;
+        session.capture_to(region, output)
+    }
+
+    fn capture_window(&self, window: u64, output: &Path) -> Result<(), CaptureError> {
+        let window = u32::try_from(window)
+            .map_err(|_| CaptureError::Failed(format!("Invalid X11 window id {}", window)))?;
+        let session = Session::connect()?;
+        let region = session.window_region(window)?;
+        session.capture_to(region, output)
+    }
+
+    fn capture_region(&self, region: CaptureRegion, output: &Path) -> Result<(), CaptureError> {
+        let session = Session::connect()?;
+        let region = session.clip(region_from(region))?;
+        session.capture_to(region, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(x: i16, y: i16, width: u16, height: u16) -> Region {
+        Region { x, y, width, height }
+    }
+
+    #[test]
+    fn clip_keeps_regions_inside_the_root() {
+        assert_eq!(clip(region(10, 20, 30, 40), 1920, 1080).unwrap(), region(10, 20, 30, 40));
+        assert_eq!(clip(region(-10, -5, 30, 40), 1920, 1080).unwrap(), region(0, 0, 20, 35));
+        assert_eq!(clip(region(1900, 1070, 100, 100), 1920, 1080).unwrap(), region(1900, 1070, 20, 10));
+        assert!(clip(region(1920, 0, 10, 10), 1920, 1080).is_err());
+        assert!(clip(region(-50, 0, 50, 10), 1920, 1080).is_err());
+        assert!(clip(region(0, 0, 0, 10), 1920, 1080).is_err());
+    }
+
+    #[test]
+    fn capture_regions_are_clamped_to_the_x11_range() {
+        let huge = CaptureRegion {
+            x: -100_000,
+            y: 100_000,
+            width: 70_000,
+            height: 5,
+        };
+        assert_eq!(region_from(huge), region(i16::MIN, i16::MAX, u16::MAX, 5));
+    }
+
+    #[test]
+    fn display_one_is_the_primary_monitor() {
+        let left = region(0, 0, 1280, 1024);
+        let right = region(1280, 0, 1920, 1080);
+        let monitors = vec![(false, left), (true, right)];
+
+        assert_eq!(nth_monitor(monitors.clone(), 1).unwrap(), right);
+        assert_eq!(nth_monitor(monitors.clone(), 2).unwrap(), left);
+        assert!(nth_monitor(monitors.clone(), 0).is_err());
+        assert!(nth_monitor(monitors, 3).is_err());
+    }
+
+    #[test]
+    fn drag_rect_works_in_any_direction() {
+        let rect = drag_rect((300, 200), (100, 50));
+        assert_eq!((rect.x, rect.y, rect.width, rect.height), (100, 50, 200, 150));
+        let rect = drag_rect((i16::MIN, 0), (i16::MAX, 0));
+        assert_eq!((rect.x, rect.width), (i16::MIN, u16::MAX));
+    }
+
+    #[test]
+    fn zpixmap_uses_the_visual_masks_and_byte_order() {
+        let masks = ChannelMasks {
+            red: 0x00FF_0000,
+            green: 0x0000_FF00,
+            blue: 0x0000_00FF,
+        };
+        // 0x00112233 as BGRX (LSB first) and XRGB (MSB first)
+        let little = zpixmap_to_rgba(1, 1, &[0x33, 0x22, 0x11, 0x00], masks, false);
+        let big = zpixmap_to_rgba(1, 1, &[0x00, 0x11, 0x22, 0x33], masks, true);
+        assert_eq!(little.pixels, [0x11, 0x22, 0x33, 255]);
+        assert_eq!(big.pixels, [0x11, 0x22, 0x33, 255]);
+
+        let bgr = ChannelMasks {
+            red: 0x0000_00FF,
+            green: 0x0000_FF00,
+            blue: 0x00FF_0000,
+        };
+        let swapped = zpixmap_to_rgba(1, 1, &[0x33, 0x22, 0x11, 0x00], bgr, false);
+        assert_eq!(swapped.pixels, [0x33, 0x22, 0x11, 255]);
+    }
+
+    // These need an X server: `xvfb-run -a cargo test -- --ignored x11`
+    fn scratch_png(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("screenshotapp-x11-{}-{}.png", name, std::process::id()))
+    }
+
+    #[test]
+    #[ignore = "needs an X server (run under xvfb-run)"]
+    fn xvfb_fullscreen_matches_the_root_window() {
+        let session = Session::connect().unwrap();
+        let output = scratch_png("fullscreen");
+
+        X11Backend.capture_fullscreen(&output).unwrap();
+
+        let (width, height) = crate::image_probe::probe_dimensions(&output).unwrap();
+        assert_eq!((width, height), (session.root_width.into(), session.root_height.into()));
+        let _ = std::fs::remove_file(&output);
+    }
+
+    #[test]
+    #[ignore = "needs an X server (run under xvfb-run)"]
+    fn xvfb_region_is_clipped_to_the_screen() {
+        let session = Session::connect().unwrap();
+        let output = scratch_png("region");
+        let region = CaptureRegion {
+            x: session.root_width as i32 - 50,
+            y: -10,
+            width: 200,
+            height: 60,
+        };
+
+        X11Backend.capture_region(region, &output).unwrap();
+
+        assert_eq!(crate::image_probe::probe_dimensions(&output).unwrap(), (50, 50));
+        let _ = std::fs::remove_file(&output);
+    }
+
+    #[test]
+    #[ignore = "needs an X server (run under xvfb-run)"]
+    fn xvfb_display_and_window_lookups() {
+        let session = Session::connect().unwrap();
+
+        let primary = session.monitor_region(1).unwrap();
+        assert!(primary.width > 0 && primary.height > 0);
+        assert!(session.monitor_region(99).is_err());
+        assert_eq!(session.window_region(session.root).unwrap(), session.full_region());
+        assert!(session.window_region(0x1234_5678).is_err());
+    }
+}
@@ -0,0 +1,602 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+
+use crate::capture::{CaptureRegion, CaptureTarget};
+use crate::command_runner::CommandRunner;
+use crate::error::AppError;
+use crate::filename_template::NameContext;
+use crate::frontmost::{FrontmostLookup, SystemFrontmost};
+use crate::history::{self, HistoryEntry};
+use crate::image_pipeline;
+use crate::output::{self, OutputDestination};
+use crate::permissions::{self, PermissionProbe, SystemProbe};
+use crate::stitch_session;
+use crate::stitch_sources;
+use crate::{
+    embed_screenshot_metadata, generate_screenshot_path, generate_temp_screenshot_path, get_image_dimensions, open_rename_popup,
+    optimize_screenshot, run_capture, AppState, DataPaths, Settings,
+};
+
+// One pipeline for every capture mode:
+// guard -> capture (or clipboard import) to temp -> optimize -> probe -> name -> move into place.
+// Each stage is reported to the frontend as an event carrying the job id.
+
+static CAPTURE_JOB_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum CaptureMode {
+    Area,
+    Fullscreen,
+    Display { display: u32 },
+    Window { window: u64 },
+    // Frontmost window at the time of the capture
+    ActiveWindow,
+    // Same rectangle as the last area selection
+    LastRegion,
+    // Image already on the clipboard, imported as if it had been captured
+    Clipboard,
+}
+
+impl CaptureMode {
+    pub fn target(self) -> Option<CaptureTarget> {
+        match self {
+            CaptureMode::Area => Some(CaptureTarget::InteractiveArea),
+            CaptureMode::Fullscreen => Some(CaptureTarget::Fullscreen),
+            CaptureMode::Display { display } => Some(CaptureTarget::Display(display)),
+            CaptureMode::Window { window } => Some(CaptureTarget::Window(window)),
+            // Resolved when the job runs
+            CaptureMode::ActiveWindow | CaptureMode::LastRegion | CaptureMode::Clipboard => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CaptureMode::Area => "area",
+            CaptureMode::Fullscreen => "fullscreen",
+            CaptureMode::Display { .. } => "display",
+            CaptureMode::Window { .. } | CaptureMode::ActiveWindow => "window",
+            CaptureMode::LastRegion => "area",
+            CaptureMode::Clipboard => "clipboard",
+        }
+    }
+
+    pub fn destination(self) -> OutputDestination {
+        match self {
+            CaptureMode::Area
+            | CaptureMode::Window { .. }
+            | CaptureMode::ActiveWindow
+            | CaptureMode::LastRegion
+            | CaptureMode::Clipboard => OutputDestination::Area,
+            CaptureMode::Fullscreen | CaptureMode::Display { .. } => OutputDestination::Fullscreen,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureStartedEvent {
+    pub job_id: u64,
+    pub mode: CaptureMode,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureOptimizedEvent {
+    pub job_id: u64,
+    pub mode: CaptureMode,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Emitted for every failure except a cancelled capture.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureErrorEvent {
+    pub job_id: u64,
+    pub mode: CaptureMode,
+    pub error: AppError,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureSavedEvent {
+    pub job_id: u64,
+    pub mode: CaptureMode,
+    pub path: String,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub enum CaptureEvent {
+    Started(CaptureStartedEvent),
+    Optimized(CaptureOptimizedEvent),
+    Saved(CaptureSavedEvent),
+}
+
+impl CaptureEvent {
+    fn emit(self, app: &tauri::AppHandle) {
+        let _ = match self {
+            CaptureEvent::Started(event) => app.emit("capture-started", event),
+            CaptureEvent::Optimized(event) => app.emit("capture-optimized", event),
+            CaptureEvent::Saved(event) => app.emit("capture-saved", event),
+        };
+    }
+}
+
+// What a job needs from the app. `run` builds it from `AppState`; tests build
+// it around a `FakeRunner` and a scratch directory.
+pub struct CaptureEnv<'a> {
+    pub runner: Arc<dyn CommandRunner>,
+    pub probe: &'a dyn PermissionProbe,
+    pub frontmost: &'a dyn FrontmostLookup,
+    pub last_region: &'a Mutex<Option<CaptureRegion>>,
+    pub data: DataPaths,
+}
+
+pub struct CaptureJob {
+    pub id: u64,
+    pub mode: CaptureMode,
+    settings: Settings,
+}
+
+fn file_size(path: &str) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+// Copied image files (e.g. from Finder) win over image data: for those the
+// pasteboard image is usually just the file icon.
+fn import_clipboard_image(dest: &str) -> Result<(), AppError> {
+    let contents = stitch_sources::read_clipboard().map_err(AppError::Failed)?;
+    if let Some(path) = contents.paths.first() {
+        std::fs::copy(path, dest).map_err(|e| AppError::io("Failed to copy clipboard file", e))?;
+        return Ok(());
+    }
+    let image = contents
+        .image
+        .ok_or_else(|| AppError::NotFound("The clipboard does not contain an image".to_string()))?;
+    let png = image_pipeline::encode_png(&image).map_err(|e| AppError::image("Failed to encode clipboard image", e))?;
+    std::fs::write(dest, png).map_err(|e| AppError::io("Failed to write clipboard image", e))
+}
+
+impl CaptureJob {
+    pub fn new(mode: CaptureMode, settings: Settings) -> Self {
+        Self {
+            id: CAPTURE_JOB_COUNTER.fetch_add(1, Ordering::Relaxed),
+            mode,
+            settings,
+        }
+    }
+
+    pub fn run(&self, app: &tauri::AppHandle) -> Result<String, AppError> {
+        if app.get_webview_window("rename").is_some() {
+            return Err(AppError::WindowBusy(
+                "Please finish renaming the current screenshot first".to_string(),
+            ));
+        }
+
+        let state = app.state::<AppState>();
+        let probe = SystemProbe::new(state.runner.as_ref());
+        let frontmost = SystemFrontmost::new(state.runner.as_ref());
+        let env = CaptureEnv {
+            runner: state.runner.clone(),
+            probe: &probe,
+            frontmost: &frontmost,
+            last_region: &state.last_region,
+            data: state.data.clone(),
+        };
+        self.execute(&env, |event| event.emit(app))
+    }
+
+    // The pipeline itself, without the window check. Events go to `emit`.
+    pub fn execute(&self, env: &CaptureEnv, emit: impl Fn(CaptureEvent)) -> Result<String, AppError> {
+        emit(CaptureEvent::Started(CaptureStartedEvent {
+            job_id: self.id,
+            mode: self.mode,
+        }));
+
+        // Read the frontmost window before the capture UI takes focus.
+        // Used by the {app}/{title} tokens and stored in the file's metadata.
+        let frontmost = env.frontmost.window();
+
+        let filepath = generate_temp_screenshot_path(&env.data, "png")?;
+        let target = match self.mode {
+            CaptureMode::Clipboard => None,
+            CaptureMode::ActiveWindow => {
+                let window = env
+                    .frontmost
+                    .window_id()
+                    .ok_or_else(|| AppError::NotFound("No active window to capture".to_string()))?;
+                Some(CaptureTarget::Window(window))
+            }
+            CaptureMode::LastRegion => {
+                let region = *env.last_region.lock().unwrap();
+                let region = region.ok_or_else(|| {
+                    AppError::NotFound(
+                        "No area selection to repeat yet (the capture backend may not report selections)".to_string(),
+                    )
+                })?;
+                Some(CaptureTarget::Region(region))
+            }
+            mode => mode.target(),
+        };
+        match target {
+            Some(target) => {
+                permissions::require_screen_recording(env.probe)?;
+                if let Some(region) = run_capture(&env.runner, &self.settings, target, &filepath)? {
+                    *env.last_region.lock().unwrap() = Some(region);
+                }
+            }
+            None => import_clipboard_image(&filepath)?,
+        }
+        let bytes_before = file_size(&filepath);
+
+        let optimized_path = optimize_screenshot(&filepath, &self.settings)?;
+        let bytes_after = file_size(&optimized_path);
+        let (width, height) = get_image_dimensions(&optimized_path)?;
+        emit(CaptureEvent::Optimized(CaptureOptimizedEvent {
+            job_id: self.id,
+            mode: self.mode,
+            bytes_before,
+            bytes_after,
+            width,
+            height,
+        }));
+
+        let extension = Path::new(&optimized_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("png");
+        let context = NameContext {
+            now: Local::now(),
+            mode: self.mode.label().to_string(),
+            quality: self.settings.quality,
+            width,
+            height,
+            app: frontmost.app.clone(),
+            title: frontmost.title,
+        };
+        let final_path =
+            generate_screenshot_path(&env.data, extension, &self.settings, self.mode.destination(), &context)?;
+        if let Err(e) = output::move_file(Path::new(&optimized_path), Path::new(&final_path)) {
+            let _ = std::fs::remove_file(&final_path);
+            return Err(AppError::io("Failed to move screenshot into place", e));
+        }
+        let mut entry = HistoryEntry::new(&final_path, self.mode.label(), width, height, self.settings.quality);
+        entry.app = frontmost.app;
+        if let Err(e) = history::record(&env.data.history, entry) {
+            println!("[history] Failed to record capture: {}", e);
+        }
+        embed_screenshot_metadata(&env.data, &final_path);
+
+        emit(CaptureEvent::Saved(CaptureSavedEvent {
+            job_id: self.id,
+            mode: self.mode,
+            path: final_path.clone(),
+            bytes_before,
+            bytes_after,
+            width,
+            height,
+        }));
+        Ok(final_path)
+    }
+}
+
+pub fn run_capture_job(app: &tauri::AppHandle, mode: CaptureMode) -> Result<String, AppError> {
+    let settings = app.state::<AppState>().settings.lock().unwrap().clone();
+    CaptureJob::new(mode, settings).run(app)
+}
+
+// Shortcut and tray entry point: capture off the main thread, then open the rename
+// popup, or hand the file to a running stitch session. Failures other than a
+// cancelled capture are reported as `capture-error`.
+pub fn spawn_capture_job(app: &tauri::AppHandle, mode: CaptureMode) {
+    let app_clone = app.clone();
+    std::thread::spawn(move || {
+        let settings = app_clone.state::<AppState>().settings.lock().unwrap().clone();
+        let job = CaptureJob::new(mode, settings);
+        match job.run(&app_clone) {
+            Ok(path) => {
+                if !stitch_session::append_capture(&app_clone, &path) {
+                    let _ = open_rename_popup(app_clone, path);
+                }
+            }
+            Err(e) if e.is_cancelled() => {}
+            Err(e) => {
+                println!("[capture] job {} failed: {}", job.id, e);
+                // The main window reports it, for every mode
+                let _ = app_clone.emit(
+                    "capture-error",
+                    CaptureErrorEvent {
+                        job_id: job.id,
+                        mode,
+                        error: e,
+                    },
+                );
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::capture::CaptureBackendKind;
+    use crate::command_runner::{CommandOutput, FakeRunner};
+    use crate::frontmost::FrontmostWindow;
+    use crate::image_pipeline::RgbaImage;
+    use crate::permissions::PermissionState;
+
+    // Each test gets its own directory for output, counter, history and temp files.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("screenshotapp-capture-{}", std::process::id()))
+            .join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    struct FixedProbe(PermissionState);
+
+    impl PermissionProbe for FixedProbe {
+        fn screen_recording(&self) -> PermissionState {
+            self.0
+        }
+
+        fn has_automation(&self) -> bool {
+            false
+        }
+
+        fn automation(&self, _target: &str) -> PermissionState {
+            PermissionState::NotApplicable
+        }
+    }
+
+    // Stands in for the focused window, which tests can't rely on.
+    #[derive(Default)]
+    struct FixedFrontmost {
+        app: Option<String>,
+        window_id: Option<u64>,
+    }
+
+    impl FrontmostLookup for FixedFrontmost {
+        fn window(&self) -> FrontmostWindow {
+            FrontmostWindow {
+                app: self.app.clone(),
+                title: None,
+            }
+        }
+
+        fn window_id(&self) -> Option<u64> {
+            self.window_id
+        }
+    }
+
+    fn settings(output_dir: &Path) -> Settings {
+        let mut settings = Settings {
+            capture_backend: CaptureBackendKind::Screencapture,
+            max_width: 1024,
+            ..Settings::default()
+        };
+        settings.output.directory = output_dir.to_string_lossy().to_string();
+        settings
+    }
+
+    fn output_dir(scratch: &Path) -> PathBuf {
+        scratch.join("output")
+    }
+
+    // Writes a PNG to the last argument, like screencapture does.
+    fn writes_png(width: u32, height: u32) -> impl FnOnce(&[String]) -> std::io::Result<CommandOutput> {
+        move |args| {
+            let png = image_pipeline::encode_png(&RgbaImage::new(width, height)).unwrap();
+            std::fs::write(args.last().unwrap(), png)?;
+            Ok(CommandOutput::ok(""))
+        }
+    }
+
+    fn execute(
+        job: &CaptureJob,
+        scratch: &Path,
+        runner: &Arc<FakeRunner>,
+        probe: &dyn PermissionProbe,
+        last_region: &Mutex<Option<CaptureRegion>>,
+    ) -> (Result<String, AppError>, Vec<CaptureEvent>) {
+        execute_with(job, scratch, runner, probe, &FixedFrontmost::default(), last_region)
+    }
+
+    fn execute_with(
+        job: &CaptureJob,
+        scratch: &Path,
+        runner: &Arc<FakeRunner>,
+        probe: &dyn PermissionProbe,
+        frontmost: &dyn FrontmostLookup,
+        last_region: &Mutex<Option<CaptureRegion>>,
+    ) -> (Result<String, AppError>, Vec<CaptureEvent>) {
+        let env = CaptureEnv {
+            runner: runner.clone(),
+            probe,
+            frontmost,
+            last_region,
+            data: DataPaths::under(scratch),
+        };
+        let events = Mutex::new(Vec::new());
+        let result = job.execute(&env, |event| events.lock().unwrap().push(event));
+        (result, events.into_inner().unwrap())
+    }
+
+    #[test]
+    fn area_capture_is_optimized_named_and_renamed() {
+        let scratch = scratch_dir("area");
+        let dir = output_dir(&scratch);
+        let runner = Arc::new(FakeRunner::default());
+        runner.on("screencapture", writes_png(2048, 100));
+
+        let job = CaptureJob::new(CaptureMode::Area, settings(&dir));
+        let (result, events) = execute(&job, &scratch, &runner, &FixedProbe(PermissionState::Granted), &Mutex::new(None));
+        let path = result.unwrap();
+
+        let calls = runner.calls("screencapture");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0][..2], ["-i", "-x"]);
+        let temp_png = PathBuf::from(&calls[0][2]);
+        let data = DataPaths::under(&scratch);
+        assert!(temp_png.starts_with(&data.temp_captures));
+        assert!(!temp_png.exists());
+
+        assert!(path.ends_with(".jpg"));
+        assert_eq!(Path::new(&path).parent(), Some(dir.as_path()));
+        assert_eq!(get_image_dimensions(&path).unwrap(), (1024, 50));
+        assert!(matches!(
+            events[..],
+            [CaptureEvent::Started(_), CaptureEvent::Optimized(_), CaptureEvent::Saved(_)]
+        ));
+        let CaptureEvent::Saved(saved) = &events[2] else {
+            unreachable!()
+        };
+        assert_eq!(saved.path, path);
+        assert_eq!((saved.width, saved.height), (1024, 50));
+        assert!(history::find_by_path(&data.history, &path).is_some());
+
+        let renamed = crate::rename_screenshot_file(&data, path.clone(), "bug: report/1".to_string()).unwrap();
+        assert_eq!(PathBuf::from(&renamed), dir.join("bug report1.jpg"));
+        assert!(!Path::new(&path).exists());
+        assert!(history::find_by_path(&data.history, &renamed).is_some());
+    }
+
+    #[test]
+    fn escape_during_selection_is_cancelled() {
+        let scratch = scratch_dir("cancelled");
+        let dir = output_dir(&scratch);
+        let runner = Arc::new(FakeRunner::default());
+        runner.on("screencapture", |_| Ok(CommandOutput::failed("")));
+
+        let job = CaptureJob::new(CaptureMode::Area, settings(&dir));
+        let (result, events) = execute(&job, &scratch, &runner, &FixedProbe(PermissionState::Granted), &Mutex::new(None));
+
+        assert!(result.unwrap_err().is_cancelled());
+        assert!(matches!(events[..], [CaptureEvent::Started(_)]));
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn screencapture_error_is_reported() {
+        let scratch = scratch_dir("failed");
+        let dir = output_dir(&scratch);
+        let runner = Arc::new(FakeRunner::default());
+        runner.on("screencapture", |args| {
+            std::fs::write(args.last().unwrap(), b"")?;
+            Ok(CommandOutput::failed("screencapture: cannot write file\n"))
+        });
+
+        let job = CaptureJob::new(CaptureMode::Fullscreen, settings(&dir));
+        let (result, _) = execute(&job, &scratch, &runner, &FixedProbe(PermissionState::Granted), &Mutex::new(None));
+
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), "failed");
+        assert_eq!(error.to_string(), "Screenshot failed: screencapture: cannot write file");
+    }
+
+    #[test]
+    fn denied_screen_recording_skips_screencapture() {
+        let scratch = scratch_dir("denied");
+        let dir = output_dir(&scratch);
+        let runner = Arc::new(FakeRunner::default());
+
+        let job = CaptureJob::new(CaptureMode::Fullscreen, settings(&dir));
+        let (result, _) = execute(&job, &scratch, &runner, &FixedProbe(PermissionState::Denied), &Mutex::new(None));
+
+        assert_eq!(result.unwrap_err().kind(), "permissionDenied");
+        assert!(runner.calls("screencapture").is_empty());
+    }
+
+    #[test]
+    fn last_region_repeats_the_stored_rectangle() {
+        let scratch = scratch_dir("last-region");
+        let dir = output_dir(&scratch);
+        let runner = Arc::new(FakeRunner::default());
+        runner.on("screencapture", writes_png(300, 200));
+        let last_region = Mutex::new(Some(CaptureRegion {
+            x: 10,
+            y: 20,
+            width: 300,
+            height: 200,
+        }));
+
+        let job = CaptureJob::new(CaptureMode::LastRegion, settings(&dir));
+        let (result, _) = execute(&job, &scratch, &runner, &FixedProbe(PermissionState::Granted), &last_region);
+
+        let path = result.unwrap();
+        assert_eq!(runner.calls("screencapture")[0][..3], ["-x", "-R", "10,20,300,200"]);
+        assert_eq!(get_image_dimensions(&path).unwrap(), (300, 200));
+    }
+
+    #[test]
+    fn last_region_without_a_selection_is_not_found() {
+        let scratch = scratch_dir("no-region");
+        let dir = output_dir(&scratch);
+        let runner = Arc::new(FakeRunner::default());
+
+        let job = CaptureJob::new(CaptureMode::LastRegion, settings(&dir));
+        let (result, _) = execute(&job, &scratch, &runner, &FixedProbe(PermissionState::Granted), &Mutex::new(None));
+
+        assert_eq!(result.unwrap_err().kind(), "notFound");
+        assert!(runner.calls("screencapture").is_empty());
+    }
+
+    #[test]
+    fn active_window_capture_targets_the_frontmost_window() {
+        let scratch = scratch_dir("active-window");
+        let dir = output_dir(&scratch);
+        let runner = Arc::new(FakeRunner::default());
+        runner.on("screencapture", writes_png(400, 300));
+        let frontmost = FixedFrontmost {
+            app: Some("Terminal".to_string()),
+            window_id: Some(42),
+        };
+        let mut settings = settings(&dir);
+        settings.filename_template.pattern = "{app}-{mode}".to_string();
+
+        let job = CaptureJob::new(CaptureMode::ActiveWindow, settings);
+        let (result, _) = execute_with(
+            &job,
+            &scratch,
+            &runner,
+            &FixedProbe(PermissionState::Granted),
+            &frontmost,
+            &Mutex::new(None),
+        );
+
+        let path = result.unwrap();
+        assert_eq!(runner.calls("screencapture")[0][..4], ["-x", "-o", "-l", "42"]);
+        assert_eq!(PathBuf::from(&path), dir.join("Terminal-window.jpg"));
+        let data = DataPaths::under(&scratch);
+        assert_eq!(history::find_by_path(&data.history, &path).unwrap().app.as_deref(), Some("Terminal"));
+    }
+
+    #[test]
+    fn active_window_without_a_focused_window_is_not_found() {
+        let scratch = scratch_dir("no-active-window");
+        let dir = output_dir(&scratch);
+        let runner = Arc::new(FakeRunner::default());
+
+        let job = CaptureJob::new(CaptureMode::ActiveWindow, settings(&dir));
+        let (result, _) = execute(&job, &scratch, &runner, &FixedProbe(PermissionState::Granted), &Mutex::new(None));
+
+        assert_eq!(result.unwrap_err().kind(), "notFound");
+        assert!(runner.calls("screencapture").is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+
+use crate::image_probe::{detect_format, ImageFormat};
+use crate::metadata;
+
+#[cfg(target_os = "macos")]
+mod pasteboard;
+#[cfg(target_os = "linux")]
+mod x11;
+
+// Clipboard writer. One copy offers several representations at once so each
+// target app can pick what it understands: the compressed image bytes as they
+// are on disk (never re-encoded to raw pixels), a file URL, and the note as text.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardImageFormat {
+    Png,
+    Jpeg,
+}
+
+impl ClipboardImageFormat {
+    pub fn uti(self) -> &'static str {
+        match self {
+            ClipboardImageFormat::Png => "public.png",
+            ClipboardImageFormat::Jpeg => "public.jpeg",
+        }
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            ClipboardImageFormat::Png => "image/png",
+            ClipboardImageFormat::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClipboardImage {
+    pub format: ClipboardImageFormat,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ClipboardPayload {
+    pub image: Option<ClipboardImage>,
+    pub file: Option<PathBuf>,
+    pub text: Option<String>,
+}
+
+impl ClipboardPayload {
+    // Image bytes and note come from `source`; the file URL points at `url_path`
+    // (the same file, or a cached copy that outlives renames and deletes).
+    pub fn from_file(source: &Path, url_path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(source).map_err(|e| format!("Failed to read image for clipboard: {}", e))?;
+        let format = match detect_format(&bytes) {
+            Some(ImageFormat::Png) => ClipboardImageFormat::Png,
+            Some(ImageFormat::Jpeg) => ClipboardImageFormat::Jpeg,
+            _ => return Err("Only PNG and JPEG images can be copied to the clipboard".to_string()),
+        };
+        let text = metadata::read_metadata(source)
+            .ok()
+            .flatten()
+            .and_then(|m| m.note)
+            .filter(|note| !note.trim().is_empty());
+        Ok(Self {
+            image: Some(ClipboardImage { format, bytes }),
+            file: Some(url_path.to_path_buf()),
+            text,
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn write(payload: ClipboardPayload) -> Result<(), String> {
+    pasteboard::write(&payload)
+}
+
+// Native X11 selection owner; Wayland sessions are reached through XWayland.
+#[cfg(target_os = "linux")]
+pub fn write(payload: ClipboardPayload) -> Result<(), String> {
+    if std::env::var_os("DISPLAY").is_none() {
+        return Err("Clipboard copy needs an X11 display (DISPLAY is not set)".to_string());
+    }
+    x11::write(payload)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub fn write(_payload: ClipboardPayload) -> Result<(), String> {
+    Err("Clipboard copy is not supported on this platform".to_string())
+}
@@ -0,0 +1,50 @@
+use objc2::rc::{autoreleasepool, Retained};
+use objc2::runtime::ProtocolObject;
+use objc2_app_kit::{NSPasteboard, NSPasteboardItem, NSPasteboardWriting};
+use objc2_foundation::{NSArray, NSData, NSString, NSURL};
+
+use super::ClipboardPayload;
+
+// NSPasteboard writer: a single pasteboard item carrying every representation,
+// image data first so apps that accept images prefer it over the URL or text.
+
+const FILE_URL_UTI: &str = "public.file-url";
+const PLAIN_TEXT_UTI: &str = "public.utf8-plain-text";
+
+pub fn write(payload: &ClipboardPayload) -> Result<(), String> {
+    autoreleasepool(|_| {
+        let item = unsafe { NSPasteboardItem::new() };
+
+        if let Some(image) = &payload.image {
+            let data = NSData::with_bytes(&image.bytes);
+            let kind = NSString::from_str(image.format.uti());
+            if !unsafe { item.setData_forType(&data, &kind) } {
+                return Err(format!("Failed to put {} on the clipboard", image.format.uti()));
+            }
+        }
+
+        if let Some(file) = &payload.file {
+            let path = NSString::from_str(&file.to_string_lossy());
+            let url = unsafe { NSURL::fileURLWithPath(&path) };
+            if let Some(url_string) = unsafe { url.absoluteString() } {
+                unsafe { item.setString_forType(&url_string, &NSString::from_str(FILE_URL_UTI)) };
+            }
+        }
+
+        if let Some(text) = &payload.text {
+            unsafe { item.setString_forType(&NSString::from_str(text), &NSString::from_str(PLAIN_TEXT_UTI)) };
+        }
+
+        let object: Retained<ProtocolObject<dyn NSPasteboardWriting>> = ProtocolObject::from_retained(item);
+        let objects = NSArray::from_vec(vec![object]);
+        let pasteboard = unsafe { NSPasteboard::generalPasteboard() };
+        unsafe {
+            pasteboard.clearContents();
+        }
+        if unsafe { pasteboard.writeObjects(&objects) } {
+            Ok(())
+        } else {
+            Err("Failed to write to the clipboard".to_string())
+        }
+    })
+}
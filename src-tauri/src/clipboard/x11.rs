@@ -0,0 +1,449 @@
+use std::path::Path;
+
+use x11rb::connection::{Connection, RequestConnection as _};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, Property,
+    SelectionNotifyEvent, SelectionRequestEvent, Window, WindowClass, SELECTION_NOTIFY_EVENT,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::{COPY_DEPTH_FROM_PARENT, CURRENT_TIME};
+
+use super::{ClipboardImageFormat, ClipboardPayload};
+use crate::image_pipeline;
+
+// X11 CLIPBOARD owner. A background thread answers selection requests until
+// another client takes the clipboard. Large payloads go out with the INCR
+// protocol. Works under Xvfb, so it can be checked headless with
+// `xclip -selection clipboard -t TARGETS -o`.
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        CLIPBOARD,
+        TARGETS,
+        MULTIPLE,
+        INCR,
+        UTF8_STRING,
+        TEXT,
+        TEXT_PLAIN_UTF8: b"text/plain;charset=utf-8",
+        TEXT_URI_LIST: b"text/uri-list",
+        GNOME_COPIED_FILES: b"x-special/gnome-copied-files",
+        IMAGE_PNG: b"image/png",
+        IMAGE_JPEG: b"image/jpeg",
+    }
+}
+
+// Bytes per property write; INCR is used above this.
+const MAX_CHUNK: usize = 256 * 1024;
+
+fn x11_error(e: impl std::fmt::Display) -> String {
+    format!("X11 clipboard: {}", e)
+}
+
+fn file_url(path: &Path) -> String {
+    let encoded: Vec<String> = path
+        .to_string_lossy()
+        .split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect();
+    format!("file://{}", encoded.join("/"))
+}
+
+// The targets offered for `payload`, in order, and the JPEG bytes to convert if
+// a PNG is requested.
+fn targets_for(payload: ClipboardPayload, atoms: &Atoms) -> (Vec<Target>, Option<Vec<u8>>) {
+    let mut targets = Vec::new();
+    let mut jpeg = None;
+    // Image first: chat tools take the first image type they understand
+    if let Some(image) = payload.image {
+        match image.format {
+            ClipboardImageFormat::Png => targets.push(Target {
+                atom: atoms.IMAGE_PNG,
+                data: TargetData::Bytes(image.bytes),
+            }),
+            ClipboardImageFormat::Jpeg => {
+                targets.push(Target {
+                    atom: atoms.IMAGE_JPEG,
+                    data: TargetData::Bytes(image.bytes.clone()),
+                });
+                targets.push(Target {
+                    atom: atoms.IMAGE_PNG,
+                    data: TargetData::PngFromJpeg,
+                });
+                jpeg = Some(image.bytes);
+            }
+        }
+    }
+    if let Some(file) = &payload.file {
+        let url = file_url(file);
+        targets.push(Target {
+            atom: atoms.TEXT_URI_LIST,
+            data: TargetData::Bytes(format!("{}\r\n", url).into_bytes()),
+        });
+        targets.push(Target {
+            atom: atoms.GNOME_COPIED_FILES,
+            data: TargetData::Bytes(format!("copy\n{}", url).into_bytes()),
+        });
+    }
+    if let Some(text) = &payload.text {
+        for atom in [atoms.UTF8_STRING, atoms.TEXT_PLAIN_UTF8, atoms.TEXT] {
+            targets.push(Target {
+                atom,
+                data: TargetData::Bytes(text.as_bytes().to_vec()),
+            });
+        }
+    }
+    (targets, jpeg)
+}
+
+enum TargetData {
+    Bytes(Vec<u8>),
+    // Converted on first request: many apps only read image/png
+    PngFromJpeg,
+}
+
+struct Target {
+    atom: Atom,
+    data: TargetData,
+}
+
+// An INCR transfer waiting for the requestor to delete the property.
+struct Transfer {
+    requestor: Window,
+    property: Atom,
+    target: Atom,
+    data: Vec<u8>,
+    offset: usize,
+}
+
+struct Owner {
+    conn: RustConnection,
+    window: Window,
+    atoms: Atoms,
+    targets: Vec<Target>,
+    jpeg: Option<Vec<u8>>,
+    transfers: Vec<Transfer>,
+    chunk: usize,
+}
+
+pub fn write(payload: ClipboardPayload) -> Result<(), String> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(x11_error)?;
+    let root = conn.setup().roots[screen_num].root;
+    let atoms = Atoms::new(&conn).map_err(x11_error)?.reply().map_err(x11_error)?;
+
+    let window = conn.generate_id().map_err(x11_error)?;
+    conn.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        window,
+        root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_ONLY,
+        0,
+        &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+    )
+    .map_err(x11_error)?;
+
+    let (targets, jpeg) = targets_for(payload, &atoms);
+    if targets.is_empty() {
+        return Err("Nothing to copy".to_string());
+    }
+
+    conn.set_selection_owner(window, atoms.CLIPBOARD, CURRENT_TIME).map_err(x11_error)?;
+    let owner = conn
+        .get_selection_owner(atoms.CLIPBOARD)
+        .map_err(x11_error)?
+        .reply()
+        .map_err(x11_error)?
+        .owner;
+    if owner != window {
+        return Err("Could not take ownership of the X11 clipboard".to_string());
+    }
+
+    let chunk = MAX_CHUNK.min(conn.maximum_request_bytes().saturating_sub(64));
+    let mut owner = Owner {
+        conn,
+        window,
+        atoms,
+        targets,
+        jpeg,
+        transfers: Vec::new(),
+        chunk,
+    };
+    std::thread::spawn(move || {
+        if let Err(e) = owner.serve() {
+            println!("[clipboard] X11 selection owner stopped: {}", e);
+        }
+    });
+    Ok(())
+}
+
+impl Owner {
+    fn serve(&mut self) -> Result<(), String> {
+        loop {
+            match self.conn.wait_for_event().map_err(x11_error)? {
+                Event::SelectionClear(event) if event.selection == self.atoms.CLIPBOARD => {
+                    println!("[clipboard] X11 clipboard taken by another client");
+                    return Ok(());
+                }
+                Event::SelectionRequest(request) => self.answer(request)?,
+                Event::PropertyNotify(event) if event.state == Property::DELETE => {
+                    self.continue_transfer(event.window, event.atom)?
+                }
+                _ => {}
+            }
+            self.conn.flush().map_err(x11_error)?;
+        }
+    }
+
+    fn data_for(&mut self, target: Atom) -> Option<Vec<u8>> {
+        let index = self.targets.iter().position(|t| t.atom == target)?;
+        if let TargetData::PngFromJpeg = self.targets[index].data {
+            let jpeg = self.jpeg.as_ref()?;
+            let png = image_pipeline::decode_bytes(jpeg)
+                .and_then(|image| image_pipeline::encode_png(&image))
+                .map_err(|e| println!("[clipboard] Failed to convert clipboard image to PNG: {}", e))
+                .ok()?;
+            self.targets[index].data = TargetData::Bytes(png);
+        }
+        match &self.targets[index].data {
+            TargetData::Bytes(bytes) => Some(bytes.clone()),
+            TargetData::PngFromJpeg => None,
+        }
+    }
+
+    fn answer(&mut self, request: SelectionRequestEvent) -> Result<(), String> {
+        // Obsolete clients pass no property and expect the target to be used
+        let property = if request.property == u32::from(AtomEnum::NONE) {
+            request.target
+        } else {
+            request.property
+        };
+
+        let stored = if request.selection != self.atoms.CLIPBOARD || request.target == self.atoms.MULTIPLE {
+            false
+        } else if request.target == self.atoms.TARGETS {
+            let mut atoms = vec![self.atoms.TARGETS];
+            atoms.extend(self.targets.iter().map(|t| t.atom));
+            self.conn
+                .change_property32(PropMode::REPLACE, request.requestor, property, AtomEnum::ATOM, &atoms)
+                .map_err(x11_error)?;
+            true
+        } else if let Some(data) = self.data_for(request.target) {
+            if data.len() <= self.chunk {
+                self.conn
+                    .change_property8(PropMode::REPLACE, request.requestor, property, request.target, &data)
+                    .map_err(x11_error)?;
+            } else {
+                self.start_transfer(request.requestor, property, request.target, data)?;
+            }
+            true
+        } else {
+            false
+        };
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if stored { property } else { AtomEnum::NONE.into() },
+        };
+        self.conn
+            .send_event(false, request.requestor, EventMask::NO_EVENT, notify)
+            .map_err(x11_error)?;
+        Ok(())
+    }
+
+    fn start_transfer(&mut self, requestor: Window, property: Atom, target: Atom, data: Vec<u8>) -> Result<(), String> {
+        // Watch the requestor's properties so we see it delete each chunk
+        self.conn
+            .change_window_attributes(requestor, &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE))
+            .map_err(x11_error)?;
+        self.conn
+            .change_property32(PropMode::REPLACE, requestor, property, self.atoms.INCR, &[data.len() as u32])
+            .map_err(x11_error)?;
+        self.transfers.retain(|t| !(t.requestor == requestor && t.property == property));
+        self.transfers.push(Transfer {
+            requestor,
+            property,
+            target,
+            data,
+            offset: 0,
+        });
+        Ok(())
+    }
+
+    fn continue_transfer(&mut self, requestor: Window, property: Atom) -> Result<(), String> {
+        let Some(index) = self
+            .transfers
+            .iter()
+            .position(|t| t.requestor == requestor && t.property == property)
+        else {
+            return Ok(());
+        };
+        let transfer = &mut self.transfers[index];
+        let end = (transfer.offset + self.chunk).min(transfer.data.len());
+        // A zero-length write marks the end of the transfer
+        self.conn
+            .change_property8(
+                PropMode::REPLACE,
+                transfer.requestor,
+                transfer.property,
+                transfer.target,
+                &transfer.data[transfer.offset..end],
+            )
+            .map_err(x11_error)?;
+        if transfer.offset == end {
+            self.transfers.remove(index);
+            if !self.transfers.iter().any(|t| t.requestor == requestor) && requestor != self.window {
+                let _ = self
+                    .conn
+                    .change_window_attributes(requestor, &ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT));
+            }
+        } else {
+            transfer.offset = end;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::clipboard::ClipboardImage;
+
+    // Distinct placeholder atoms; the real ones come from the X server
+    fn atoms() -> Atoms {
+        Atoms {
+            CLIPBOARD: 1,
+            TARGETS: 2,
+            MULTIPLE: 3,
+            INCR: 4,
+            UTF8_STRING: 5,
+            TEXT: 6,
+            TEXT_PLAIN_UTF8: 7,
+            TEXT_URI_LIST: 8,
+            GNOME_COPIED_FILES: 9,
+            IMAGE_PNG: 10,
+            IMAGE_JPEG: 11,
+        }
+    }
+
+    fn offered(payload: ClipboardPayload) -> Vec<(Atom, Option<Vec<u8>>)> {
+        let (targets, _) = targets_for(payload, &atoms());
+        targets
+            .into_iter()
+            .map(|target| match target.data {
+                TargetData::Bytes(bytes) => (target.atom, Some(bytes)),
+                TargetData::PngFromJpeg => (target.atom, None),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn file_urls_percent_encode_each_segment() {
+        assert_eq!(file_url(Path::new("/tmp/shot.png")), "file:///tmp/shot.png");
+        assert_eq!(
+            file_url(Path::new("/home/me/My Shots/a#1 (copy)%.jpg")),
+            "file:///home/me/My%20Shots/a%231%20%28copy%29%25.jpg"
+        );
+        assert_eq!(file_url(Path::new("/tmp/été.png")), "file:///tmp/%C3%A9t%C3%A9.png");
+    }
+
+    #[test]
+    fn png_payload_offers_the_bytes_as_is() {
+        let payload = ClipboardPayload {
+            image: Some(ClipboardImage {
+                format: ClipboardImageFormat::Png,
+                bytes: vec![1, 2, 3],
+            }),
+            ..ClipboardPayload::default()
+        };
+        let (_, jpeg) = targets_for(payload.clone(), &atoms());
+
+        assert_eq!(offered(payload), [(10, Some(vec![1, 2, 3]))]);
+        assert!(jpeg.is_none());
+    }
+
+    #[test]
+    fn jpeg_payload_also_offers_a_converted_png() {
+        let payload = ClipboardPayload {
+            image: Some(ClipboardImage {
+                format: ClipboardImageFormat::Jpeg,
+                bytes: vec![0xFF, 0xD8],
+            }),
+            ..ClipboardPayload::default()
+        };
+        let (_, jpeg) = targets_for(payload.clone(), &atoms());
+
+        assert_eq!(offered(payload), [(11, Some(vec![0xFF, 0xD8])), (10, None)]);
+        assert_eq!(jpeg, Some(vec![0xFF, 0xD8]));
+    }
+
+    #[test]
+    fn file_and_text_follow_the_image() {
+        let payload = ClipboardPayload {
+            image: Some(ClipboardImage {
+                format: ClipboardImageFormat::Png,
+                bytes: vec![9],
+            }),
+            file: Some(PathBuf::from("/tmp/a b.png")),
+            text: Some("note".to_string()),
+        };
+
+        let offered = offered(payload);
+
+        let atoms: Vec<Atom> = offered.iter().map(|(atom, _)| *atom).collect();
+        assert_eq!(atoms, [10, 8, 9, 5, 7, 6]);
+        assert_eq!(offered[1].1.as_deref(), Some(&b"file:///tmp/a%20b.png\r\n"[..]));
+        assert_eq!(offered[2].1.as_deref(), Some(&b"copy\nfile:///tmp/a%20b.png"[..]));
+        assert!(offered[3..].iter().all(|(_, bytes)| bytes.as_deref() == Some(&b"note"[..])));
+    }
+
+    #[test]
+    fn empty_payload_offers_nothing() {
+        assert!(offered(ClipboardPayload::default()).is_empty());
+    }
+
+    // Needs an X server and xclip: `xvfb-run -a cargo test -- --ignored x11`
+    fn xclip(target: &str) -> Vec<u8> {
+        let output = std::process::Command::new("xclip")
+            .args(["-selection", "clipboard", "-t", target, "-o"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "xclip -t {} failed", target);
+        output.stdout
+    }
+
+    #[test]
+    #[ignore = "needs an X server and xclip (run under xvfb-run)"]
+    fn xvfb_owner_answers_xclip() {
+        let png = image_pipeline::encode_png(&image_pipeline::RgbaImage::new(2, 2)).unwrap();
+        write(ClipboardPayload {
+            image: Some(ClipboardImage {
+                format: ClipboardImageFormat::Png,
+                bytes: png.clone(),
+            }),
+            file: Some(PathBuf::from("/tmp/shot.png")),
+            text: Some("hello".to_string()),
+        })
+        .unwrap();
+
+        let targets = String::from_utf8(xclip("TARGETS")).unwrap();
+        for target in ["image/png", "text/uri-list", "UTF8_STRING"] {
+            assert!(targets.contains(target), "{} missing from {}", target, targets);
+        }
+        assert_eq!(xclip("image/png"), png);
+        assert_eq!(xclip("UTF8_STRING"), b"hello");
+        assert_eq!(xclip("text/uri-list"), b"file:///tmp/shot.png\r\n");
+    }
+}
@@ -0,0 +1,295 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use tauri::Manager;
+
+use crate::output;
+use crate::AppState;
+
+// Cache of files handed to the clipboard. The clipboard only holds a file URL,
+// so the file has to outlive renames and deletes of the original. Files are
+// evicted oldest first once the cache is over its size cap or past its age
+// limit; the file currently on the clipboard is never evicted. Which file that
+// is gets saved beside the cache so the eviction at startup skips it too.
+
+const MAX_CACHE_BYTES: u64 = 256 * 1024 * 1024;
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+const EVICTION_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+static CLIPBOARD_CACHE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub files: usize,
+    pub bytes: u64,
+    pub max_bytes: u64,
+}
+
+struct CachedFile {
+    path: PathBuf,
+    bytes: u64,
+    modified: SystemTime,
+}
+
+pub struct ClipboardCache {
+    dir: PathBuf,
+    // Last cached file put on the clipboard
+    current: Mutex<Option<PathBuf>>,
+}
+
+// Kept outside the cache folder so it is never listed or evicted itself.
+fn current_marker(dir: &Path) -> PathBuf {
+    dir.with_extension("current")
+}
+
+fn new_cache_path(cache_dir: &Path, extension: &str) -> Result<PathBuf, String> {
+    let sanitized_extension = extension.trim_start_matches('.');
+    if sanitized_extension.is_empty() {
+        return Err("Missing clipboard cache file extension".to_string());
+    }
+
+    std::fs::create_dir_all(cache_dir).map_err(|e| format!("Failed to create clipboard cache dir: {}", e))?;
+
+    let millis = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_err(|e| format!("Failed to read system time: {}", e))?
+        .as_millis();
+    let counter = CLIPBOARD_CACHE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    Ok(cache_dir.join(format!("clipboard-{}-{}.{}", millis, counter, sanitized_extension)))
+}
+
+fn list_files(dir: &Path) -> Vec<CachedFile> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            if !meta.is_file() {
+                return None;
+            }
+            Some(CachedFile {
+                path: entry.path(),
+                bytes: meta.len(),
+                modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            })
+        })
+        .collect()
+}
+
+// Drop files past the age limit, then the oldest until under the size cap.
+// `remove` deletes one file and reports whether it is gone. Returns how many
+// files were removed and the bytes left.
+fn evict_files(
+    mut files: Vec<CachedFile>,
+    now: SystemTime,
+    current: Option<&Path>,
+    mut remove: impl FnMut(&Path) -> bool,
+) -> (usize, u64) {
+    files.sort_by_key(|file| file.modified);
+    let mut total: u64 = files.iter().map(|file| file.bytes).sum();
+    let mut removed = 0;
+    for file in files {
+        let expired = now.duration_since(file.modified).map(|age| age > MAX_AGE).unwrap_or(false);
+        if !expired && total <= MAX_CACHE_BYTES {
+            continue;
+        }
+        if current == Some(file.path.as_path()) {
+            continue;
+        }
+        if remove(&file.path) {
+            total = total.saturating_sub(file.bytes);
+            removed += 1;
+        }
+    }
+    (removed, total)
+}
+
+impl ClipboardCache {
+    pub fn new(dir: PathBuf) -> Self {
+        let current = std::fs::read_to_string(current_marker(&dir))
+            .ok()
+            .map(|path| PathBuf::from(path.trim()))
+            .filter(|path| path.is_file());
+        Self {
+            dir,
+            current: Mutex::new(current),
+        }
+    }
+
+    pub fn system() -> Self {
+        Self::new(output::cache_dir().join("clipboard"))
+    }
+
+    pub fn write_bytes(&self, bytes: &[u8], extension: &str) -> Result<String, String> {
+        let cached_path = new_cache_path(&self.dir, extension)?;
+        std::fs::write(&cached_path, bytes).map_err(|e| format!("Failed to write clipboard cache file: {}", e))?;
+        Ok(cached_path.to_string_lossy().to_string())
+    }
+
+    pub fn copy_file(&self, source: &str, extension: &str) -> Result<String, String> {
+        let cached_path = new_cache_path(&self.dir, extension)?;
+        std::fs::copy(source, &cached_path).map_err(|e| format!("Failed to cache file for clipboard: {}", e))?;
+        Ok(cached_path.to_string_lossy().to_string())
+    }
+
+    // Record the file now on the clipboard and evict around it.
+    pub fn set_current(&self, path: &str) {
+        *self.current.lock().unwrap() = Some(PathBuf::from(path));
+        if let Err(e) = std::fs::write(current_marker(&self.dir), path) {
+            println!("[clipboard] Failed to save the current clipboard file: {}", e);
+        }
+        self.evict();
+    }
+
+    fn is_current(&self, path: &Path) -> bool {
+        self.current.lock().unwrap().as_deref() == Some(path)
+    }
+
+    pub fn evict(&self) {
+        let current = self.current.lock().unwrap().clone();
+        let (removed, total) = evict_files(list_files(&self.dir), SystemTime::now(), current.as_deref(), |path| {
+            std::fs::remove_file(path).is_ok()
+        });
+        if removed > 0 {
+            println!("[clipboard] evicted {} cached file(s), {} bytes left", removed, total);
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let files = list_files(&self.dir);
+        CacheStats {
+            files: files.len(),
+            bytes: files.iter().map(|file| file.bytes).sum(),
+            max_bytes: MAX_CACHE_BYTES,
+        }
+    }
+
+    // Remove everything except the file still on the clipboard.
+    pub fn clear(&self) -> CacheStats {
+        for file in list_files(&self.dir) {
+            if !self.is_current(&file.path) {
+                let _ = std::fs::remove_file(&file.path);
+            }
+        }
+        self.stats()
+    }
+}
+
+pub fn spawn_eviction(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        app.state::<AppState>().clipboard_cache.evict();
+        std::thread::sleep(EVICTION_INTERVAL);
+    });
+}
+
+#[tauri::command]
+pub fn get_cache_stats(state: tauri::State<AppState>) -> CacheStats {
+    state.clipboard_cache.stats()
+}
+
+#[tauri::command]
+pub fn clear_cache(state: tauri::State<AppState>) -> CacheStats {
+    state.clipboard_cache.clear()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn file(name: &str, megabytes: u64, age_minutes: u64, now: SystemTime) -> CachedFile {
+        CachedFile {
+            path: PathBuf::from(format!("/cache/{}", name)),
+            bytes: megabytes * MIB,
+            modified: now - Duration::from_secs(age_minutes * 60),
+        }
+    }
+
+    fn evicted(files: Vec<CachedFile>, now: SystemTime, current: Option<&str>) -> Vec<String> {
+        let current = current.map(|name| PathBuf::from(format!("/cache/{}", name)));
+        let mut removed = Vec::new();
+        evict_files(files, now, current.as_deref(), |path| {
+            removed.push(path.file_name().unwrap().to_string_lossy().to_string());
+            true
+        });
+        removed
+    }
+
+    #[test]
+    fn small_fresh_cache_is_left_alone() {
+        let now = SystemTime::now();
+        let files = vec![file("a", 10, 5, now), file("b", 10, 1, now)];
+
+        assert!(evicted(files, now, None).is_empty());
+    }
+
+    #[test]
+    fn expired_files_go_even_under_the_size_cap() {
+        let now = SystemTime::now();
+        let files = vec![file("new", 1, 10, now), file("old", 1, 25 * 60, now), file("older", 1, 48 * 60, now)];
+
+        assert_eq!(evicted(files, now, None), ["older", "old"]);
+    }
+
+    #[test]
+    fn oldest_files_go_until_under_the_size_cap() {
+        let now = SystemTime::now();
+        let files = vec![
+            file("c", 100, 1, now),
+            file("a", 100, 30, now),
+            file("b", 100, 20, now),
+            file("d", 10, 0, now),
+        ];
+
+        // 310 MiB: dropping the oldest leaves 210 MiB, under the 256 MiB cap
+        assert_eq!(evicted(files, now, None), ["a"]);
+    }
+
+    #[test]
+    fn age_limit_is_applied_before_the_size_cap() {
+        let now = SystemTime::now();
+        let files = vec![
+            file("expired", 1, 25 * 60, now),
+            file("big-old", 200, 60, now),
+            file("big-new", 200, 5, now),
+        ];
+
+        assert_eq!(evicted(files, now, None), ["expired", "big-old"]);
+    }
+
+    #[test]
+    fn current_file_is_never_evicted() {
+        let now = SystemTime::now();
+        let files = vec![
+            file("current", 100, 48 * 60, now),
+            file("b", 100, 30, now),
+            file("c", 100, 10, now),
+        ];
+
+        assert_eq!(evicted(files, now, Some("current")), ["b"]);
+    }
+
+    #[test]
+    fn current_file_is_remembered_across_restarts() {
+        let base = std::env::temp_dir().join(format!("screenshotapp-clipboard-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let dir = base.join("clipboard");
+
+        let cache = ClipboardCache::new(dir.clone());
+        let path = cache.write_bytes(b"image", "png").unwrap();
+        cache.set_current(&path);
+
+        let restarted = ClipboardCache::new(dir);
+        assert!(restarted.is_current(Path::new(&path)));
+        restarted.clear();
+        assert!(Path::new(&path).exists());
+    }
+}
@@ -0,0 +1,125 @@
+use std::io;
+use std::process::Command;
+
+#[cfg(test)]
+use std::collections::{HashMap, VecDeque};
+#[cfg(test)]
+use std::sync::Mutex;
+
+// External programs (screencapture, osascript, open) are started through
+// `CommandRunner`, held in `AppState`. The code around them then runs against
+// `FakeRunner` in tests, with scripted outputs instead of macOS tools.
+
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+pub trait CommandRunner: Send + Sync {
+    // Runs to completion and captures stdout and stderr.
+    fn output(&self, program: &str, args: &[&str]) -> io::Result<CommandOutput>;
+    // Starts without waiting, for programs that hand off to another app.
+    fn spawn(&self, program: &str, args: &[&str]) -> io::Result<()>;
+}
+
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn output(&self, program: &str, args: &[&str]) -> io::Result<CommandOutput> {
+        let output = Command::new(program).args(args).output()?;
+        Ok(CommandOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+
+    fn spawn(&self, program: &str, args: &[&str]) -> io::Result<()> {
+        Command::new(program).args(args).spawn().map(|_| ())
+    }
+}
+
+#[cfg(test)]
+impl CommandOutput {
+    pub fn ok(stdout: &str) -> Self {
+        Self {
+            success: true,
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+        }
+    }
+
+    pub fn failed(stderr: &str) -> Self {
+        Self {
+            success: false,
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+type Response = Box<dyn FnOnce(&[String]) -> io::Result<CommandOutput> + Send>;
+
+// Records every call and answers from a per-program queue of scripted responses.
+// A program with nothing queued fails like a missing binary.
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeRunner {
+    responses: Mutex<HashMap<String, VecDeque<Response>>>,
+    calls: Mutex<Vec<(String, Vec<String>)>>,
+}
+
+#[cfg(test)]
+impl FakeRunner {
+    // Queues the next response for `program`. It sees the arguments, so it can
+    // write the file the real program would (e.g. screencapture's output path).
+    pub fn on(
+        &self,
+        program: &str,
+        respond: impl FnOnce(&[String]) -> io::Result<CommandOutput> + Send + 'static,
+    ) -> &Self {
+        self.responses
+            .lock()
+            .unwrap()
+            .entry(program.to_string())
+            .or_default()
+            .push_back(Box::new(respond));
+        self
+    }
+
+    pub fn calls(&self, program: &str) -> Vec<Vec<String>> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(called, _)| called == program)
+            .map(|(_, args)| args.clone())
+            .collect()
+    }
+
+    fn respond(&self, program: &str, args: &[&str]) -> Option<io::Result<CommandOutput>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        self.calls.lock().unwrap().push((program.to_string(), args.clone()));
+        let response = self.responses.lock().unwrap().get_mut(program)?.pop_front()?;
+        Some(response(&args))
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for FakeRunner {
+    fn output(&self, program: &str, args: &[&str]) -> io::Result<CommandOutput> {
+        self.respond(program, args).unwrap_or_else(|| {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no scripted output for {}", program),
+            ))
+        })
+    }
+
+    fn spawn(&self, program: &str, args: &[&str]) -> io::Result<()> {
+        self.respond(program, args).unwrap_or(Ok(CommandOutput::default())).map(|_| ())
+    }
+}
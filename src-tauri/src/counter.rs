@@ -0,0 +1,149 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::settings_file;
+
+// Persistent {counter} value, stored in its own file next to the settings so that
+// saving settings from the UI never rewinds it.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CounterReset {
+    // Never resets; keeps counting across days and restarts
+    #[default]
+    Global,
+    // Starts again from the configured start value each day
+    Daily,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CounterState {
+    next: u32,
+    // Day of the last capture, "%Y-%m-%d"
+    day: Option<String>,
+}
+
+// Serializes read-modify-write between capture threads.
+static COUNTER_LOCK: Mutex<()> = Mutex::new(());
+
+// A missing file starts the counter fresh. A broken one does too, but is moved
+// aside first so the old value can still be recovered by hand.
+fn read_state(path: &Path) -> CounterState {
+    let problem = match std::fs::read_to_string(path) {
+        Ok(content) => match serde_json::from_str(&content) {
+            Ok(state) => return state,
+            Err(e) => format!("Counter file is not valid: {}", e),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return CounterState::default(),
+        Err(e) => format!("Failed to read counter file: {}", e),
+    };
+    let backup = settings_file::unused_backup_path(path);
+    match std::fs::rename(path, &backup) {
+        Ok(()) => println!("[counter] {}; moved it to {} and started again", problem, backup.display()),
+        Err(e) => println!("[counter] {}; failed to move it aside: {}", problem, e),
+    }
+    CounterState::default()
+}
+
+// Write to a sibling temp file, then rename over the old one.
+fn write_state(path: &Path, state: &CounterState) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(state)
+        .map_err(|e| format!("Failed to serialize counter: {}", e))?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, content).map_err(|e| format!("Failed to write counter: {}", e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to write counter: {}", e))
+}
+
+// Take the next counter value and persist its successor.
+pub fn next_counter(path: &Path, start: u32, reset: CounterReset, today: NaiveDate) -> Result<u32, String> {
+    let _guard = COUNTER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut state = read_state(path);
+
+    let today = today.format("%Y-%m-%d").to_string();
+    let reset_today = reset == CounterReset::Daily && state.day.as_deref() != Some(today.as_str());
+    if reset_today || state.next < start {
+        state.next = start;
+    }
+
+    let value = state.next;
+    state.next = value.saturating_add(1);
+    state.day = Some(today);
+    write_state(path, &state)?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_file(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("screenshotapp-counter-{}", std::process::id()))
+            .join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("counter.json")
+    }
+
+    fn day(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn global_counter_keeps_counting_across_days() {
+        let path = scratch_file("global");
+
+        assert_eq!(next_counter(&path, 1, CounterReset::Global, day("2026-01-01")).unwrap(), 1);
+        assert_eq!(next_counter(&path, 1, CounterReset::Global, day("2026-01-01")).unwrap(), 2);
+        assert_eq!(next_counter(&path, 1, CounterReset::Global, day("2026-01-02")).unwrap(), 3);
+    }
+
+    #[test]
+    fn daily_counter_starts_again_on_a_new_day() {
+        let path = scratch_file("daily");
+
+        assert_eq!(next_counter(&path, 5, CounterReset::Daily, day("2026-01-01")).unwrap(), 5);
+        assert_eq!(next_counter(&path, 5, CounterReset::Daily, day("2026-01-01")).unwrap(), 6);
+        assert_eq!(next_counter(&path, 5, CounterReset::Daily, day("2026-01-02")).unwrap(), 5);
+    }
+
+    #[test]
+    fn raising_the_start_value_jumps_ahead() {
+        let path = scratch_file("start");
+        next_counter(&path, 1, CounterReset::Global, day("2026-01-01")).unwrap();
+
+        assert_eq!(next_counter(&path, 100, CounterReset::Global, day("2026-01-01")).unwrap(), 100);
+        // Lowering it again never rewinds
+        assert_eq!(next_counter(&path, 1, CounterReset::Global, day("2026-01-01")).unwrap(), 101);
+    }
+
+    #[test]
+    fn counter_saturates_at_the_maximum() {
+        let path = scratch_file("max");
+        write_state(
+            &path,
+            &CounterState {
+                next: u32::MAX,
+                day: None,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(next_counter(&path, 1, CounterReset::Global, day("2026-01-01")).unwrap(), u32::MAX);
+        assert_eq!(next_counter(&path, 1, CounterReset::Global, day("2026-01-01")).unwrap(), u32::MAX);
+    }
+
+    #[test]
+    fn broken_counter_file_is_moved_aside() {
+        let path = scratch_file("broken");
+        let backup = settings_file::unused_backup_path(&path);
+        std::fs::write(&path, r#"{ "next": "#).unwrap();
+
+        assert_eq!(next_counter(&path, 1, CounterReset::Global, day("2026-01-01")).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), r#"{ "next": "#);
+    }
+}
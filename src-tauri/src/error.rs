@@ -0,0 +1,137 @@
+use std::fmt;
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::capture::CaptureError;
+use crate::image_pipeline::ImageError;
+
+// Error returned by every command. Serialised as `{ kind, message, details }`
+// so the frontend can tell a cancelled capture from a real failure. Helpers on
+// a command's path return `AppError` too, so the kind survives to the frontend.
+// Leaf helpers that only fail one way return `String`; callers pick its kind
+// with `map_err`, so there is deliberately no `From<String>`.
+
+#[derive(Debug)]
+pub enum AppError {
+    Cancelled,
+    PermissionDenied(String),
+    NotFound(String),
+    Io { message: String, source: std::io::Error },
+    Decode(String),
+    Invalid(String),
+    Unsupported(String),
+    ShortcutConflict(String),
+    // A window the action needs is already open (e.g. the rename popup)
+    WindowBusy(String),
+    // Another job of the same kind is still running
+    Busy(String),
+    Failed(String),
+}
+
+impl AppError {
+    // Io errors caused by missing permissions are reported as such.
+    pub fn io(message: impl Into<String>, source: std::io::Error) -> Self {
+        let message = message.into();
+        match source.kind() {
+            std::io::ErrorKind::PermissionDenied => AppError::PermissionDenied(format!("{}: {}", message, source)),
+            std::io::ErrorKind::NotFound => AppError::NotFound(format!("{}: {}", message, source)),
+            _ => AppError::Io { message, source },
+        }
+    }
+
+    // Image errors keep their cause: unreadable files, undecodable data, unknown formats.
+    pub fn image(message: impl Into<String>, source: ImageError) -> Self {
+        let message = message.into();
+        match source {
+            ImageError::Io(e) => AppError::io(message, e),
+            ImageError::UnsupportedFormat => AppError::Unsupported(format!("{}: {}", message, source)),
+            ImageError::Decode(_) | ImageError::TooLarge { .. } => AppError::Decode(format!("{}: {}", message, source)),
+            ImageError::Encode(_) => AppError::Failed(format!("{}: {}", message, source)),
+        }
+    }
+
+    // Window creation fails when a window with the same label is still open.
+    pub fn window(label: &str, error: tauri::Error) -> Self {
+        match error {
+            tauri::Error::WindowLabelAlreadyExists(_) | tauri::Error::WebviewLabelAlreadyExists(_) => {
+                AppError::WindowBusy(format!("The {} window is already open", label))
+            }
+            e => AppError::Failed(format!("Failed to open {} window: {}", label, e)),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, AppError::Cancelled)
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::Cancelled => "cancelled",
+            AppError::PermissionDenied(_) => "permissionDenied",
+            AppError::NotFound(_) => "notFound",
+            AppError::Io { .. } => "io",
+            AppError::Decode(_) => "decode",
+            AppError::Invalid(_) => "invalid",
+            AppError::Unsupported(_) => "unsupported",
+            AppError::ShortcutConflict(_) => "shortcutConflict",
+            AppError::WindowBusy(_) => "windowBusy",
+            AppError::Busy(_) => "busy",
+            AppError::Failed(_) => "failed",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            AppError::Cancelled => "Cancelled".to_string(),
+            AppError::Io { message, .. } => message.clone(),
+            AppError::PermissionDenied(message)
+            | AppError::NotFound(message)
+            | AppError::Decode(message)
+            | AppError::Invalid(message)
+            | AppError::Unsupported(message)
+            | AppError::ShortcutConflict(message)
+            | AppError::WindowBusy(message)
+            | AppError::Busy(message)
+            | AppError::Failed(message) => message.clone(),
+        }
+    }
+
+    pub fn details(&self) -> Option<String> {
+        match self {
+            AppError::Io { source, .. } => Some(source.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.details() {
+            Some(details) => write!(f, "{}: {}", self.message(), details),
+            None => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.message())?;
+        state.serialize_field("details", &self.details())?;
+        state.end()
+    }
+}
+
+impl From<CaptureError> for AppError {
+    fn from(error: CaptureError) -> Self {
+        match error {
+            CaptureError::Cancelled => AppError::Cancelled,
+            CaptureError::Unsupported(e) => AppError::Unsupported(format!("Not supported: {}", e)),
+            CaptureError::Failed(e) => AppError::Failed(format!("Screenshot failed: {}", e)),
+            CaptureError::Io(e) => AppError::io("Screenshot failed", e),
+        }
+    }
+}
@@ -0,0 +1,656 @@
+use std::fmt;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::counter::CounterReset;
+
+// Filename template language, e.g. "{prefix}-{date:%Y%m%d}-{app}-{w}x{h}-{counter:03}".
+//
+// Tokens: prefix, date[:strftime], time[:strftime], quality, w, h, dimensions,
+// counter[:width], mode, app, title. "{{" and "}}" are literal braces.
+// Substituted values are sanitised; literal text may not contain path separators.
+
+const DEFAULT_PATTERN: &str = "{prefix}_{date:%m-%d}_{time:%H-%M-%S}_{quality}%_{w}x{h}";
+const DEFAULT_DATE_FORMAT: &str = "%m-%d";
+const DEFAULT_TIME_FORMAT: &str = "%H-%M-%S";
+const MAX_FILENAME_CHARS: usize = 200;
+const MAX_COUNTER_PADDING: usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilenameBlock {
+    pub id: String,
+    pub enabled: bool,
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FilenameTemplate {
+    pub pattern: String,
+    pub prefix: String,
+    pub counter_start: u32,
+    pub counter_padding: u32,
+    pub counter_reset: CounterReset,
+}
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        Self {
+            pattern: DEFAULT_PATTERN.to_string(),
+            prefix: "llm-scr".to_string(),
+            counter_start: 1,
+            counter_padding: 3,
+            counter_reset: CounterReset::default(),
+        }
+    }
+}
+
+// Settings files from before the pattern language describe the name as a block
+// list. Adds the matching `pattern` (and `prefix`) next to it; the blocks and
+// `useCounter` stay for older builds.
+pub fn migrate_legacy_blocks(template: &mut Map<String, Value>) {
+    if template.contains_key("pattern") {
+        return;
+    }
+    let Some(blocks) = template
+        .get("blocks")
+        .and_then(|blocks| serde_json::from_value::<Vec<FilenameBlock>>(blocks.clone()).ok())
+        .filter(|blocks| !blocks.is_empty())
+    else {
+        return;
+    };
+    let use_counter = template.get("useCounter").and_then(Value::as_bool).unwrap_or(false);
+    template.insert("pattern".to_string(), Value::from(pattern_from_blocks(&blocks, use_counter)));
+    if !template.contains_key("prefix") {
+        if let Some(prefix) = blocks.iter().find(|b| b.id == "prefix").and_then(|b| b.value.clone()) {
+            template.insert("prefix".to_string(), Value::from(prefix));
+        }
+    }
+}
+
+// Convert the old fixed block list (joined with "_") into a pattern.
+fn pattern_from_blocks(blocks: &[FilenameBlock], use_counter: bool) -> String {
+    let mut parts: Vec<&str> = blocks
+        .iter()
+        .filter(|b| b.enabled)
+        .filter_map(|b| match b.id.as_str() {
+            "prefix" => Some("{prefix}"),
+            "date" => Some("{date:%m-%d}"),
+            "time" => Some("{time:%H-%M-%S}"),
+            "quality" => Some("{quality}%"),
+            "dimensions" => Some("{w}x{h}"),
+            "counter" => Some("{counter}"),
+            _ => None,
+        })
+        .collect();
+    if use_counter && !parts.contains(&"{counter}") {
+        parts.push("{counter}");
+    }
+    if parts.is_empty() {
+        "screenshot".to_string()
+    } else {
+        parts.join("_")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    // 0-based character offset into the pattern
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid filename template at position {}: {}",
+            self.position + 1,
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Prefix,
+    Date(String),
+    Time(String),
+    Quality,
+    Width,
+    Height,
+    Dimensions,
+    Counter { padding: Option<usize> },
+    Mode,
+    App,
+    Title,
+}
+
+// Values available while naming a capture.
+#[derive(Debug, Clone)]
+pub struct NameContext {
+    pub now: DateTime<Local>,
+    pub mode: String,
+    pub quality: u32,
+    pub width: u32,
+    pub height: u32,
+    pub app: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParsedTemplate {
+    segments: Vec<Segment>,
+}
+
+fn validate_strftime(format: &str, position: usize) -> Result<(), TemplateError> {
+    if format.is_empty() {
+        return Err(TemplateError {
+            position,
+            message: "empty date/time format".to_string(),
+        });
+    }
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(TemplateError {
+            position,
+            message: format!("invalid strftime format \"{}\"", format),
+        });
+    }
+    Ok(())
+}
+
+fn parse_token(body: &str, position: usize) -> Result<Segment, TemplateError> {
+    let (raw_name, arg) = match body.split_once(':') {
+        Some((name, arg)) => (name, Some(arg)),
+        None => (body, None),
+    };
+    let name = raw_name.trim();
+    // '{' + the name as written, surrounding spaces included, + ':'
+    let arg_position = position + raw_name.chars().count() + 2;
+    let no_arg = |segment: Segment| match arg {
+        Some(_) => Err(TemplateError {
+            position: arg_position,
+            message: format!("token {{{}}} does not take a format", name),
+        }),
+        None => Ok(segment),
+    };
+
+    match name {
+        "prefix" => no_arg(Segment::Prefix),
+        "date" => {
+            let format = arg.unwrap_or(DEFAULT_DATE_FORMAT);
+            validate_strftime(format, arg_position)?;
+            Ok(Segment::Date(format.to_string()))
+        }
+        "time" => {
+            let format = arg.unwrap_or(DEFAULT_TIME_FORMAT);
+            validate_strftime(format, arg_position)?;
+            Ok(Segment::Time(format.to_string()))
+        }
+        "quality" => no_arg(Segment::Quality),
+        "w" | "width" => no_arg(Segment::Width),
+        "h" | "height" => no_arg(Segment::Height),
+        "dimensions" => no_arg(Segment::Dimensions),
+        "counter" => {
+            let padding = match arg {
+                None => None,
+                Some(spec) => {
+                    let digits = spec.trim_start_matches('0');
+                    let width = if digits.is_empty() && !spec.is_empty() {
+                        Ok(0)
+                    } else {
+                        digits.parse::<usize>()
+                    };
+                    match width {
+                        Ok(width) if width <= MAX_COUNTER_PADDING => Some(width),
+                        _ => {
+                            return Err(TemplateError {
+                                position: arg_position,
+                                message: format!(
+                                    "counter width must be a number between 0 and {}, got \"{}\"",
+                                    MAX_COUNTER_PADDING, spec
+                                ),
+                            })
+                        }
+                    }
+                }
+            };
+            Ok(Segment::Counter { padding })
+        }
+        "mode" => no_arg(Segment::Mode),
+        "app" => no_arg(Segment::App),
+        "title" => no_arg(Segment::Title),
+        "" => Err(TemplateError {
+            position,
+            message: "empty token {}".to_string(),
+        }),
+        _ => Err(TemplateError {
+            position,
+            message: format!(
+                "unknown token {{{}}} (expected prefix, date, time, quality, w, h, dimensions, counter, mode, app or title)",
+                name
+            ),
+        }),
+    }
+}
+
+impl ParsedTemplate {
+    pub fn parse(pattern: &str) -> Result<Self, TemplateError> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            match chars[i] {
+                '{' if chars.get(i + 1) == Some(&'{') => {
+                    literal.push('{');
+                    i += 2;
+                }
+                '}' if chars.get(i + 1) == Some(&'}') => {
+                    literal.push('}');
+                    i += 2;
+                }
+                '{' => {
+                    let close = chars[i + 1..]
+                        .iter()
+                        .position(|&c| c == '}')
+                        .map(|offset| i + 1 + offset)
+                        .ok_or(TemplateError {
+                            position: i,
+                            message: "unclosed '{'".to_string(),
+                        })?;
+                    let body: String = chars[i + 1..close].iter().collect();
+                    if body.contains('{') {
+                        return Err(TemplateError {
+                            position: i,
+                            message: "nested '{' inside token".to_string(),
+                        });
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(parse_token(&body, i)?);
+                    i = close + 1;
+                }
+                '}' => {
+                    return Err(TemplateError {
+                        position: i,
+                        message: "unmatched '}' (use '}}' for a literal brace)".to_string(),
+                    });
+                }
+                c @ ('/' | ':' | '\\') => {
+                    return Err(TemplateError {
+                        position: i,
+                        message: format!("'{}' is not allowed in filenames", c),
+                    });
+                }
+                c if c.is_control() => {
+                    return Err(TemplateError {
+                        position: i,
+                        message: "control characters are not allowed in filenames".to_string(),
+                    });
+                }
+                c => {
+                    literal.push(c);
+                    i += 1;
+                }
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        if segments.is_empty() {
+            return Err(TemplateError {
+                position: 0,
+                message: "template is empty".to_string(),
+            });
+        }
+        Ok(Self { segments })
+    }
+
+    pub fn uses_counter(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|s| matches!(s, Segment::Counter { .. }))
+    }
+
+    // Render a file stem (no extension). `counter` is ignored if the template has no counter token.
+    pub fn render(&self, template: &FilenameTemplate, ctx: &NameContext, counter: u32) -> String {
+        let mut pieces: Vec<(String, Fit)> = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(text) => (text.clone(), Fit::Fixed),
+                Segment::Prefix => (sanitize_component(&template.prefix), Fit::Variable),
+                Segment::Date(format) | Segment::Time(format) => {
+                    (sanitize_component(&ctx.now.format(format).to_string()), Fit::Fixed)
+                }
+                Segment::Quality => (ctx.quality.to_string(), Fit::Fixed),
+                Segment::Width => (ctx.width.to_string(), Fit::Fixed),
+                Segment::Height => (ctx.height.to_string(), Fit::Fixed),
+                Segment::Dimensions => (format!("{}x{}", ctx.width, ctx.height), Fit::Fixed),
+                Segment::Counter { padding } => {
+                    let width = padding.unwrap_or(template.counter_padding as usize);
+                    (format!("{:0width$}", counter, width = width), Fit::Keep)
+                }
+                Segment::Mode => (sanitize_component(&ctx.mode), Fit::Variable),
+                Segment::App => (sanitize_component(ctx.app.as_deref().unwrap_or("unknown")), Fit::Variable),
+                Segment::Title => (sanitize_component(ctx.title.as_deref().unwrap_or("")), Fit::Variable),
+            })
+            .collect();
+        fit_to_length(&mut pieces, MAX_FILENAME_CHARS);
+        let stem: String = pieces.into_iter().map(|(text, _)| text).collect();
+        finalize_stem(&stem)
+    }
+}
+
+// Replace characters that are unsafe in filenames on macOS, Linux or shared drives.
+pub fn sanitize_component(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut last_was_space = false;
+    for c in value.chars() {
+        let mapped = match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        };
+        if mapped.is_whitespace() {
+            if !last_was_space {
+                out.push(' ');
+            }
+            last_was_space = true;
+        } else {
+            out.push(mapped);
+            last_was_space = false;
+        }
+    }
+    out.trim().to_string()
+}
+
+// How a rendered segment may be shortened when the stem is too long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fit {
+    // Prefix, app, title, mode: shortened first
+    Variable,
+    // Literal text, dates, sizes: only when the variable parts are not enough
+    Fixed,
+    // The counter: never, or every value would render the same stem
+    Keep,
+}
+
+// Shorten pieces until the total is at most `max` characters: variable pieces
+// first, then fixed ones, always cutting the longest candidate.
+fn fit_to_length(pieces: &mut [(String, Fit)], max: usize) {
+    let total: usize = pieces.iter().map(|(text, _)| text.chars().count()).sum();
+    let mut excess = total.saturating_sub(max);
+    for fit in [Fit::Variable, Fit::Fixed] {
+        while excess > 0 {
+            let mut lengths: Vec<(usize, usize)> = pieces
+                .iter()
+                .enumerate()
+                .filter(|(_, (text, kind))| *kind == fit && !text.is_empty())
+                .map(|(index, (text, _))| (text.chars().count(), index))
+                .collect();
+            lengths.sort_unstable_by(|a, b| b.cmp(a));
+            let Some(&(longest, index)) = lengths.first() else {
+                break;
+            };
+            // Cut down to the next longest, so long pieces shrink evenly
+            let next = lengths.get(1).map(|(len, _)| *len).unwrap_or(0);
+            let cut = excess.min(longest - next).max(1);
+            let text = &mut pieces[index].0;
+            *text = text.chars().take(longest - cut).collect();
+            excess -= cut;
+        }
+    }
+}
+
+// Trim separators left over from empty tokens.
+fn finalize_stem(stem: &str) -> String {
+    let trimmed = stem.trim_matches(|c: char| c.is_whitespace() || c == '.');
+    if trimmed.is_empty() {
+        "screenshot".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+impl FilenameTemplate {
+    pub fn parse(&self) -> Result<ParsedTemplate, TemplateError> {
+        ParsedTemplate::parse(&self.pattern)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.parse().map_err(|e| e.to_string())?;
+        if self.counter_padding as usize > MAX_COUNTER_PADDING {
+            return Err(format!("Counter padding must be at most {}", MAX_COUNTER_PADDING));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn context() -> NameContext {
+        NameContext {
+            now: Local.with_ymd_and_hms(2025, 1, 20, 9, 5, 7).unwrap(),
+            mode: "area".to_string(),
+            quality: 70,
+            width: 1920,
+            height: 1080,
+            app: Some("Safari".to_string()),
+            title: Some("Docs: a/b".to_string()),
+        }
+    }
+
+    fn template(pattern: &str) -> FilenameTemplate {
+        FilenameTemplate {
+            pattern: pattern.to_string(),
+            ..FilenameTemplate::default()
+        }
+    }
+
+    fn render(pattern: &str, counter: u32) -> String {
+        let template = template(pattern);
+        template.parse().unwrap().render(&template, &context(), counter)
+    }
+
+    fn error(pattern: &str) -> TemplateError {
+        ParsedTemplate::parse(pattern).unwrap_err()
+    }
+
+    #[test]
+    fn renders_every_token() {
+        assert_eq!(
+            render("{prefix}_{date}_{time}_{quality}%_{w}x{h}", 0),
+            "llm-scr_01-20_09-05-07_70%_1920x1080"
+        );
+        assert_eq!(
+            render("{date:%Y%m%d}-{time:%H%M}-{dimensions}-{mode}-{app}-{title}", 0),
+            "20250120-0905-1920x1080-area-Safari-Docs- a-b"
+        );
+        assert_eq!(render("{width}x{height}", 0), "1920x1080");
+    }
+
+    #[test]
+    fn counter_uses_token_or_template_padding() {
+        assert_eq!(render("shot-{counter}", 7), "shot-007");
+        assert_eq!(render("shot-{counter:5}", 7), "shot-00007");
+        assert_eq!(render("shot-{counter:0}", 7), "shot-7");
+        assert_eq!(render("shot-{counter:2}", 1234), "shot-1234");
+        assert!(template("{counter}").parse().unwrap().uses_counter());
+        assert!(!template("{date}").parse().unwrap().uses_counter());
+    }
+
+    #[test]
+    fn braces_escape_and_tokens_tolerate_spaces() {
+        assert_eq!(render("{{{ app }}}", 0), "{Safari}");
+        assert_eq!(render("{ date :%d}", 0), "20");
+    }
+
+    #[test]
+    fn missing_values_fall_back_and_edges_are_trimmed() {
+        let template = template(" {title}.{app}. ");
+        let mut ctx = context();
+        ctx.app = None;
+        ctx.title = None;
+        assert_eq!(template.parse().unwrap().render(&template, &ctx, 0), "unknown");
+
+        let empty = self::template("{title}");
+        assert_eq!(empty.parse().unwrap().render(&empty, &ctx, 0), "screenshot");
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_character() {
+        let cases = [
+            ("{", 0, "unclosed '{'"),
+            ("ab}", 2, "unmatched '}'"),
+            ("a/b", 1, "'/' is not allowed"),
+            ("x:{app}", 1, "':' is not allowed"),
+            ("a{b{c}}", 1, "nested '{'"),
+            ("ab{nope}", 2, "unknown token {nope}"),
+            ("{}", 0, "empty token"),
+            ("", 0, "template is empty"),
+            ("x{app:foo}", 6, "does not take a format"),
+            ("x{date:%Q}", 7, "invalid strftime format"),
+            ("x{date:}", 7, "empty date/time format"),
+            ("x{counter:abc}", 10, "counter width"),
+            ("x{counter:11}", 10, "counter width"),
+        ];
+        for (pattern, position, message) in cases {
+            let error = error(pattern);
+            assert_eq!(error.position, position, "position for {:?}", pattern);
+            assert!(error.message.contains(message), "{:?}: {}", pattern, error.message);
+        }
+    }
+
+    #[test]
+    fn argument_errors_count_spaces_around_the_name() {
+        // '{' at 1, "  date " is 7 characters, ':' at 9, argument at 10
+        assert_eq!(error("x{  date :%Q}").position, 10);
+        assert_eq!(error("x{ app:foo}").position, 7);
+        assert_eq!(error("x{ counter :12}").position, 12);
+    }
+
+    #[test]
+    fn error_messages_are_one_based() {
+        assert_eq!(
+            error("ab}").to_string(),
+            "Invalid filename template at position 3: unmatched '}' (use '}}' for a literal brace)"
+        );
+    }
+
+    #[test]
+    fn long_titles_are_cut_before_the_counter() {
+        let mut template = template("{prefix}-{title}-{counter}");
+        template.prefix = "p".repeat(150);
+        let mut ctx = context();
+        ctx.title = Some("t".repeat(300));
+        let parsed = template.parse().unwrap();
+
+        let first = parsed.render(&template, &ctx, 1);
+        let second = parsed.render(&template, &ctx, 2);
+
+        assert_eq!(first.chars().count(), MAX_FILENAME_CHARS);
+        assert!(first.ends_with("-001"));
+        assert!(second.ends_with("-002"));
+        assert_ne!(first, second);
+        // Both long parts were shortened evenly
+        assert!(first.starts_with(&"p".repeat(97)));
+        assert!(first.contains(&format!("-{}-", "t".repeat(97))));
+    }
+
+    #[test]
+    fn long_literal_text_is_cut_but_the_counter_stays() {
+        let pattern = format!("{}{{counter:4}}", "x".repeat(250));
+        let stem = render(&pattern, 12);
+        assert_eq!(stem.chars().count(), MAX_FILENAME_CHARS);
+        assert!(stem.ends_with("0012"));
+    }
+
+    #[test]
+    fn multibyte_values_are_cut_on_character_boundaries() {
+        let mut ctx = context();
+        ctx.title = Some("é".repeat(400));
+        let template = template("{title}{counter}");
+        let stem = template.parse().unwrap().render(&template, &ctx, 3);
+        assert_eq!(stem.chars().count(), MAX_FILENAME_CHARS);
+        assert!(stem.ends_with("003"));
+    }
+
+    #[test]
+    fn sanitize_replaces_unsafe_characters_and_collapses_spaces() {
+        assert_eq!(sanitize_component(" a/b\\c:d*e?f\"g<h>i|j "), "a-b-c-d-e-f-g-h-i-j");
+        assert_eq!(sanitize_component("tab\there\n\nnew   line"), "tab here new line");
+    }
+
+    fn migrated(file: Value) -> FilenameTemplate {
+        let Value::Object(mut file) = file else {
+            unreachable!()
+        };
+        migrate_legacy_blocks(&mut file);
+        serde_json::from_value(Value::Object(file)).unwrap()
+    }
+
+    #[test]
+    fn legacy_block_lists_become_patterns() {
+        let file = serde_json::json!({
+            "blocks": [
+                { "id": "prefix", "enabled": true, "value": "old" },
+                { "id": "date", "enabled": true, "value": null },
+                { "id": "time", "enabled": false, "value": null },
+                { "id": "quality", "enabled": true, "value": null },
+                { "id": "dimensions", "enabled": true, "value": null },
+            ],
+            "useCounter": true,
+        });
+        let template = migrated(file);
+        assert_eq!(template.pattern, "{prefix}_{date:%m-%d}_{quality}%_{w}x{h}_{counter}");
+        assert_eq!(template.prefix, "old");
+        assert_eq!(template.counter_start, 1);
+
+        let none_enabled = serde_json::json!({ "blocks": [{ "id": "date", "enabled": false, "value": null }] });
+        let template = migrated(none_enabled);
+        assert_eq!(template.pattern, "screenshot");
+    }
+
+    #[test]
+    fn current_format_wins_over_blocks() {
+        let file = serde_json::json!({
+            "pattern": "{app}-{counter}",
+            "prefix": "new",
+            "counterPadding": 5,
+            "counterReset": "daily",
+            "blocks": [{ "id": "prefix", "enabled": true, "value": "old" }],
+        });
+        let template = migrated(file);
+        assert_eq!(template.pattern, "{app}-{counter}");
+        assert_eq!(template.prefix, "new");
+        assert_eq!(template.counter_padding, 5);
+        assert_eq!(template.counter_reset, CounterReset::Daily);
+
+        let defaults = migrated(serde_json::json!({}));
+        assert_eq!(defaults.pattern, DEFAULT_PATTERN);
+    }
+
+    #[test]
+    fn validate_rejects_bad_patterns_and_padding() {
+        assert!(FilenameTemplate::default().validate().is_ok());
+        assert!(template("{nope}").validate().is_err());
+        let mut padded = FilenameTemplate::default();
+        padded.counter_padding = 11;
+        assert!(padded.validate().is_err());
+    }
+}
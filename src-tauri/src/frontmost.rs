@@ -0,0 +1,162 @@
+// Best-effort lookup of the frontmost application and window title,
+// used by the {app} and {title} filename tokens, and of its window id
+// for the active-window capture. Captures go through `FrontmostLookup`, so
+// tests don't depend on the display or on whichever window has focus.
+
+use crate::command_runner::CommandRunner;
+
+#[derive(Debug, Clone, Default)]
+pub struct FrontmostWindow {
+    pub app: Option<String>,
+    pub title: Option<String>,
+}
+
+pub trait FrontmostLookup {
+    fn window(&self) -> FrontmostWindow;
+    fn window_id(&self) -> Option<u64>;
+}
+
+pub struct SystemFrontmost<'a> {
+    runner: &'a dyn CommandRunner,
+}
+
+impl<'a> SystemFrontmost<'a> {
+    pub fn new(runner: &'a dyn CommandRunner) -> Self {
+        Self { runner }
+    }
+}
+
+impl FrontmostLookup for SystemFrontmost<'_> {
+    fn window(&self) -> FrontmostWindow {
+        frontmost_window(self.runner)
+    }
+
+    fn window_id(&self) -> Option<u64> {
+        frontmost_window_id(self.runner)
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn frontmost_window(runner: &dyn CommandRunner) -> FrontmostWindow {
+    let script = r#"
+tell application "System Events"
+    set frontApp to first application process whose frontmost is true
+    set appName to name of frontApp
+    set windowTitle to ""
+    try
+        set windowTitle to name of front window of frontApp
+    end try
+end tell
+return appName & linefeed & windowTitle
+"#;
+
+    let Ok(output) = runner.output("osascript", &["-e", script]) else {
+        return FrontmostWindow::default();
+    };
+    if !output.success {
+        return FrontmostWindow::default();
+    }
+    let mut lines = output.stdout.lines();
+    FrontmostWindow {
+        app: lines.next().and_then(non_empty),
+        title: lines.next().and_then(non_empty),
+    }
+}
+
+// CGWindowID of the frontmost app's topmost normal window. System Events has no
+// window ids, so this goes through JXA and CGWindowListCopyWindowInfo.
+#[cfg(target_os = "macos")]
+pub fn frontmost_window_id(runner: &dyn CommandRunner) -> Option<u64> {
+    let script = r#"
+ObjC.import('AppKit');
+ObjC.import('CoreGraphics');
+const pid = $.NSWorkspace.sharedWorkspace.frontmostApplication.processIdentifier;
+const windows = ObjC.deepUnwrap(ObjC.castRefToObject(
+    $.CGWindowListCopyWindowInfo($.kCGWindowListOptionOnScreenOnly | $.kCGWindowListExcludeDesktopElements, $.kCGNullWindowID)));
+const front = windows.find((w) => w.kCGWindowOwnerPID === pid && w.kCGWindowLayer === 0);
+front ? String(front.kCGWindowNumber) : '';
+"#;
+
+    let output = runner.output("osascript", &["-l", "JavaScript", "-e", script]).ok()?;
+    if !output.success {
+        return None;
+    }
+    output.stdout.trim().parse().ok()
+}
+
+#[cfg(target_os = "linux")]
+pub fn frontmost_window(_runner: &dyn CommandRunner) -> FrontmostWindow {
+    x11_active_window().map(|(window, _)| window).unwrap_or_default()
+}
+
+#[cfg(target_os = "linux")]
+pub fn frontmost_window_id(_runner: &dyn CommandRunner) -> Option<u64> {
+    x11_active_window().map(|(_, id)| id.into())
+}
+
+// _NET_ACTIVE_WINDOW -> WM_CLASS (class part) and _NET_WM_NAME / WM_NAME.
+// Wayland sessions without XWayland focus simply yield nothing.
+#[cfg(target_os = "linux")]
+fn x11_active_window() -> Option<(FrontmostWindow, u32)> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _};
+
+    let (conn, screen_num) = x11rb::connect(None).ok()?;
+    let root = conn.setup().roots[screen_num].root;
+    let atom = |name: &[u8]| -> Option<u32> { Some(conn.intern_atom(false, name).ok()?.reply().ok()?.atom) };
+    let net_active_window = atom(b"_NET_ACTIVE_WINDOW")?;
+    let net_wm_name = atom(b"_NET_WM_NAME")?;
+    let utf8_string = atom(b"UTF8_STRING")?;
+
+    let active = conn
+        .get_property(false, root, net_active_window, AtomEnum::WINDOW, 0, 1)
+        .ok()?
+        .reply()
+        .ok()?;
+    let window = active.value32()?.next()?;
+    if window == 0 {
+        return None;
+    }
+
+    let text_property = |property: u32, kind: u32| -> Option<Vec<u8>> {
+        let reply = conn
+            .get_property(false, window, property, kind, 0, 1024)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(reply.value)
+    };
+
+    // WM_CLASS is "instance\0Class\0"; the class part is the readable app name
+    let app = text_property(AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into()).and_then(|raw| {
+        let parts: Vec<&[u8]> = raw.split(|&b| b == 0).filter(|p| !p.is_empty()).collect();
+        parts
+            .last()
+            .and_then(|class| non_empty(&String::from_utf8_lossy(class)))
+    });
+    let title = text_property(net_wm_name, utf8_string)
+        .filter(|raw| !raw.is_empty())
+        .or_else(|| text_property(AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()))
+        .and_then(|raw| non_empty(&String::from_utf8_lossy(&raw)));
+
+    Some((FrontmostWindow { app, title }, window))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub fn frontmost_window(_runner: &dyn CommandRunner) -> FrontmostWindow {
+    FrontmostWindow::default()
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub fn frontmost_window_id(_runner: &dyn CommandRunner) -> Option<u64> {
+    None
+}
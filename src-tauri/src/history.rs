@@ -0,0 +1,521 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::metadata::ScreenshotMetadata;
+
+// Index of saved screenshots, one JSON object per line in
+// ~/.screenshot_app_history.jsonl. New captures are appended; edits, renames and
+// deletes rewrite the file. Unreadable lines are skipped rather than failing the
+// whole list, and written back unchanged so a newer build's entries survive.
+
+const DEFAULT_LIMIT: usize = 200;
+
+static HISTORY_ID_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: String,
+    pub path: String,
+    // RFC 3339, local time
+    pub timestamp: String,
+    pub mode: String,
+    pub width: u32,
+    pub height: u32,
+    pub bytes: u64,
+    pub quality: u32,
+    #[serde(default)]
+    pub app: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub edited: bool,
+    #[serde(default)]
+    pub stitched: bool,
+    // Largest source image of a stitch
+    #[serde(default)]
+    pub stitch_source_width: Option<u32>,
+    #[serde(default)]
+    pub stitch_source_height: Option<u32>,
+    // Fields from a newer build, kept when the file is rewritten
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl HistoryEntry {
+    pub fn new(path: &str, mode: &str, width: u32, height: u32, quality: u32) -> Self {
+        let now = Local::now();
+        Self {
+            id: format!("{}-{}", now.timestamp_millis(), HISTORY_ID_COUNTER.fetch_add(1, Ordering::Relaxed)),
+            path: path.to_string(),
+            timestamp: now.to_rfc3339(),
+            mode: mode.to_string(),
+            width,
+            height,
+            bytes: file_size(path),
+            quality,
+            app: None,
+            note: None,
+            edited: false,
+            stitched: mode == "stitch",
+            stitch_source_width: None,
+            stitch_source_height: None,
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    pub fn metadata(&self) -> ScreenshotMetadata {
+        ScreenshotMetadata {
+            note: self.note.clone(),
+            captured_at: Some(self.timestamp.clone()),
+            mode: Some(self.mode.clone()),
+            source_app: self.app.clone(),
+            original_width: Some(self.width),
+            original_height: Some(self.height),
+            stitch_source_width: self.stitch_source_width,
+            stitch_source_height: self.stitch_source_height,
+        }
+    }
+
+    fn local_date(&self) -> Option<NaiveDate> {
+        DateTime::parse_from_rfc3339(&self.timestamp)
+            .ok()
+            .map(|t| t.with_timezone(&Local).date_naive())
+    }
+}
+
+// Entry as returned to the frontend, with a live check that the file is still there.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryItem {
+    #[serde(flatten)]
+    pub entry: HistoryEntry,
+    pub exists: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryQuery {
+    // Case-insensitive match against filename, note and mode; all words must match
+    #[serde(default)]
+    pub text: Option<String>,
+    // Inclusive "YYYY-MM-DD" bounds on the capture day
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    #[serde(default)]
+    pub mode: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+// Serializes read-modify-write between capture threads and commands.
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn history_path() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(home).join(".screenshot_app_history.jsonl")
+}
+
+fn file_size(path: &str) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+// One line of the history file. Lines this build can't parse are kept as written.
+enum Line {
+    Entry(HistoryEntry),
+    Raw(String),
+}
+
+fn read_lines(path: &Path) -> Vec<Line> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Line::Entry(entry),
+            Err(e) => {
+                println!("[history] Skipping unreadable entry: {}", e);
+                Line::Raw(line.to_string())
+            }
+        })
+        .collect()
+}
+
+fn read_entries(path: &Path) -> Vec<HistoryEntry> {
+    read_lines(path)
+        .into_iter()
+        .filter_map(|line| match line {
+            Line::Entry(entry) => Some(entry),
+            Line::Raw(_) => None,
+        })
+        .collect()
+}
+
+// Write to a sibling temp file, then rename over the old one.
+fn write_lines(path: &Path, lines: &[Line]) -> Result<(), String> {
+    let mut content = String::new();
+    for line in lines {
+        match line {
+            Line::Entry(entry) => {
+                let line = serde_json::to_string(entry)
+                    .map_err(|e| format!("Failed to serialize history: {}", e))?;
+                content.push_str(&line);
+            }
+            Line::Raw(line) => content.push_str(line),
+        }
+        content.push('\n');
+    }
+    let tmp = path.with_extension("jsonl.tmp");
+    std::fs::write(&tmp, content).map_err(|e| format!("Failed to write history: {}", e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to write history: {}", e))
+}
+
+// What `update_entries` does with one entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Update {
+    Unchanged,
+    Changed,
+    Remove,
+}
+
+// Apply `change` to every entry; only rewrites the file if something changed.
+// Unparsed lines stay where they were.
+fn update_file(path: &Path, change: impl Fn(&mut HistoryEntry) -> Update) -> Result<(), String> {
+    let mut changed = false;
+    let mut lines = read_lines(path);
+    lines.retain_mut(|line| match line {
+        Line::Entry(entry) => match change(entry) {
+            Update::Unchanged => true,
+            Update::Changed => {
+                changed = true;
+                true
+            }
+            Update::Remove => {
+                changed = true;
+                false
+            }
+        },
+        Line::Raw(_) => true,
+    });
+    if changed {
+        write_lines(path, &lines)?;
+    }
+    Ok(())
+}
+
+fn update_entries(history: &Path, change: impl Fn(&mut HistoryEntry) -> Update) -> Result<(), String> {
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    update_file(history, change)
+}
+
+// Every function below works on the file at `history`; the app passes
+// `DataPaths::history`, which is `history_path()` outside tests.
+pub fn record(history: &Path, entry: HistoryEntry) -> Result<(), String> {
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let line = serde_json::to_string(&entry)
+        .map_err(|e| format!("Failed to serialize history: {}", e))?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history)
+        .map_err(|e| format!("Failed to open history: {}", e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("Failed to write history: {}", e))
+}
+
+pub fn rename_path(history: &Path, old_path: &str, new_path: &str) -> Result<(), String> {
+    update_entries(history, |entry| {
+        if entry.path != old_path {
+            return Update::Unchanged;
+        }
+        entry.path = new_path.to_string();
+        Update::Changed
+    })
+}
+
+// The image was re-saved from the editor or note popup.
+pub fn mark_edited(history: &Path, path: &str, note: Option<String>) -> Result<(), String> {
+    let bytes = file_size(path);
+    update_entries(history, |entry| {
+        if entry.path != path {
+            return Update::Unchanged;
+        }
+        entry.edited = true;
+        entry.bytes = bytes;
+        if note.is_some() {
+            entry.note = note.clone();
+        }
+        Update::Changed
+    })
+}
+
+pub fn refresh_size(history: &Path, path: &str) -> Result<(), String> {
+    let bytes = file_size(path);
+    update_entries(history, |entry| {
+        if entry.path != path || entry.bytes == bytes {
+            return Update::Unchanged;
+        }
+        entry.bytes = bytes;
+        Update::Changed
+    })
+}
+
+pub fn find_by_path(history: &Path, path: &str) -> Option<HistoryEntry> {
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    read_entries(history).into_iter().rev().find(|e| e.path == path)
+}
+
+pub fn remove_path(history: &Path, path: &str) -> Result<(), String> {
+    update_entries(history, |entry| if entry.path == path { Update::Remove } else { Update::Unchanged })
+}
+
+pub fn forget(history: &Path, id: &str) -> Result<(), String> {
+    update_entries(history, |entry| if entry.id == id { Update::Remove } else { Update::Unchanged })
+}
+
+fn parse_day(value: &Option<String>, field: &str) -> Result<Option<NaiveDate>, AppError> {
+    match value.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+        None => Ok(None),
+        Some(day) => NaiveDate::parse_from_str(day, "%Y-%m-%d")
+            .map(Some)
+            .map_err(|_| AppError::Invalid(format!("Invalid {} date \"{}\", expected YYYY-MM-DD", field, day))),
+    }
+}
+
+fn matches_text(entry: &HistoryEntry, words: &[String]) -> bool {
+    let filename = Path::new(&entry.path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let note = entry.note.as_deref().unwrap_or("").to_lowercase();
+    let mode = entry.mode.to_lowercase();
+    words
+        .iter()
+        .all(|word| filename.contains(word) || note.contains(word) || mode.contains(word))
+}
+
+// Newest first.
+pub fn search(history: &Path, query: &HistoryQuery) -> Result<Vec<HistoryItem>, AppError> {
+    let from = parse_day(&query.from, "from")?;
+    let to = parse_day(&query.to, "to")?;
+    let words: Vec<String> = query
+        .text
+        .as_deref()
+        .unwrap_or("")
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect();
+    let mode = query.mode.as_deref().filter(|m| !m.is_empty());
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+
+    let entries = {
+        let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        read_entries(history)
+    };
+
+    Ok(entries
+        .into_iter()
+        .rev()
+        .filter(|entry| mode.is_none_or(|m| entry.mode == m))
+        .filter(|entry| {
+            if from.is_none() && to.is_none() {
+                return true;
+            }
+            let Some(day) = entry.local_date() else {
+                return false;
+            };
+            from.is_none_or(|from| day >= from) && to.is_none_or(|to| day <= to)
+        })
+        .filter(|entry| matches_text(entry, &words))
+        .take(limit)
+        .map(|entry| HistoryItem {
+            exists: Path::new(&entry.path).exists(),
+            entry,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_file(name: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("screenshotapp-history-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn entry_line(id: &str, path: &str) -> String {
+        let mut entry = HistoryEntry::new(path, "area", 10, 20, 70);
+        entry.id = id.to_string();
+        serde_json::to_string(&entry).unwrap()
+    }
+
+    // Captured at noon local time on `day` ("YYYY-MM-DD")
+    fn dated_line(id: &str, path: &str, mode: &str, day: &str, note: Option<&str>) -> String {
+        use chrono::TimeZone;
+        let day = NaiveDate::parse_from_str(day, "%Y-%m-%d").unwrap();
+        let noon = Local.from_local_datetime(&day.and_hms_opt(12, 0, 0).unwrap()).unwrap();
+        let mut entry = HistoryEntry::new(path, mode, 10, 20, 70);
+        entry.id = id.to_string();
+        entry.timestamp = noon.to_rfc3339();
+        entry.note = note.map(str::to_string);
+        serde_json::to_string(&entry).unwrap()
+    }
+
+    fn search_ids(history: &Path, query: HistoryQuery) -> Vec<String> {
+        search(history, &query).unwrap().into_iter().map(|item| item.entry.id).collect()
+    }
+
+    fn search_file(name: &str) -> PathBuf {
+        let lines = [
+            dated_line("a", "/shots/Invoice March.jpg", "area", "2026-03-01", None),
+            dated_line("b", "/shots/desktop.jpg", "fullscreen", "2026-03-05", Some("Release notes")),
+            dated_line("c", "/shots/login-bug.jpg", "area", "2026-03-09", Some("invoice page")),
+            dated_line("d", "/shots/long-page.jpg", "stitch", "2026-03-12", None),
+        ];
+        scratch_file(name, &format!("{}\n", lines.join("\n")))
+    }
+
+    #[test]
+    fn search_lists_newest_first_up_to_the_limit() {
+        let path = search_file("search-limit.jsonl");
+
+        assert_eq!(search_ids(&path, HistoryQuery::default()), ["d", "c", "b", "a"]);
+        let limited = HistoryQuery {
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(search_ids(&path, limited), ["d", "c"]);
+    }
+
+    #[test]
+    fn search_matches_every_word_in_filename_note_or_mode() {
+        let path = search_file("search-text.jsonl");
+        let text = |text: &str| HistoryQuery {
+            text: Some(text.to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(search_ids(&path, text("INVOICE")), ["c", "a"]);
+        assert_eq!(search_ids(&path, text("invoice bug")), ["c"]);
+        assert_eq!(search_ids(&path, text("release fullscreen")), ["b"]);
+        assert!(search_ids(&path, text("nothing")).is_empty());
+    }
+
+    #[test]
+    fn search_filters_by_mode_and_day_range() {
+        let path = search_file("search-filter.jsonl");
+
+        let area = HistoryQuery {
+            mode: Some("area".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(search_ids(&path, area), ["c", "a"]);
+
+        // Both bounds are inclusive
+        let range = HistoryQuery {
+            from: Some("2026-03-05".to_string()),
+            to: Some("2026-03-09".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(search_ids(&path, range), ["c", "b"]);
+
+        let since = HistoryQuery {
+            from: Some("2026-03-10".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(search_ids(&path, since), ["d"]);
+    }
+
+    #[test]
+    fn search_rejects_a_bad_date() {
+        let path = search_file("search-date.jsonl");
+        let query = HistoryQuery {
+            from: Some("March 5".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(search(&path, &query).unwrap_err().kind(), "invalid");
+    }
+
+    #[test]
+    fn unreadable_lines_are_skipped_on_read() {
+        let content = format!("{}\nnot json\n\n{}\n", entry_line("a", "/a.jpg"), entry_line("b", "/b.jpg"));
+        let path = scratch_file("read.jsonl", &content);
+
+        let ids: Vec<String> = read_entries(&path).into_iter().map(|e| e.id).collect();
+
+        assert_eq!(ids, ["a", "b"]);
+    }
+
+    #[test]
+    fn unreadable_lines_survive_a_rewrite_in_place() {
+        let raw = r#"{"id":"x","schema":"from a newer build"}"#;
+        let content = format!(
+            "{}\n{}\n{}\n{}\n",
+            entry_line("a", "/a.jpg"),
+            raw,
+            entry_line("b", "/b.jpg"),
+            entry_line("c", "/c.jpg")
+        );
+        let path = scratch_file("rewrite.jsonl", &content);
+
+        update_file(&path, |entry| if entry.id == "b" { Update::Remove } else { Update::Unchanged }).unwrap();
+        update_file(&path, |entry| {
+            if entry.id != "c" {
+                return Update::Unchanged;
+            }
+            entry.path = "/renamed.jpg".to_string();
+            Update::Changed
+        })
+        .unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], raw);
+        let ids: Vec<String> = read_entries(&path).into_iter().map(|e| e.id).collect();
+        assert_eq!(ids, ["a", "c"]);
+        assert_eq!(read_entries(&path)[1].path, "/renamed.jpg");
+    }
+
+    #[test]
+    fn unknown_fields_are_kept() {
+        let mut value: serde_json::Value = serde_json::from_str(&entry_line("a", "/a.jpg")).unwrap();
+        value["tags"] = serde_json::json!(["work"]);
+        let path = scratch_file("fields.jsonl", &format!("{}\n", value));
+
+        update_file(&path, |entry| {
+            entry.note = Some("hello".to_string());
+            Update::Changed
+        })
+        .unwrap();
+
+        let written: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!(written["note"], "hello");
+        assert_eq!(written["tags"][0], "work");
+    }
+
+    #[test]
+    fn unchanged_file_is_not_rewritten() {
+        let content = format!("{}\ngarbage", entry_line("a", "/a.jpg"));
+        let path = scratch_file("untouched.jsonl", &content);
+
+        update_file(&path, |_| Update::Unchanged).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+    }
+}
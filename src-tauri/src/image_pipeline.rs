@@ -0,0 +1,526 @@
+use std::fmt;
+use std::io::Cursor;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+// In-process image pipeline: decode (PNG/JPEG) -> resize -> encode JPEG.
+// Replaces the old `sips` round trips so capture works off macOS too.
+
+// Decoded images above this many bytes of RGBA are rejected (~1 GiB).
+pub const MAX_DECODED_BYTES: usize = 1 << 30;
+
+// JPEG stores dimensions as u16.
+const MAX_JPEG_DIMENSION: u32 = u16::MAX as u32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ResizeFilter {
+    #[default]
+    Lanczos,
+    Bilinear,
+}
+
+impl ResizeFilter {
+    fn support(self) -> f32 {
+        match self {
+            ResizeFilter::Lanczos => 3.0,
+            ResizeFilter::Bilinear => 1.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            ResizeFilter::Lanczos => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+            ResizeFilter::Bilinear => (1.0 - x).max(0.0),
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let a = x * std::f32::consts::PI;
+        a.sin() / a
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(std::io::Error),
+    UnsupportedFormat,
+    Decode(String),
+    Encode(String),
+    TooLarge { width: u32, height: u32 },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Io(e) => write!(f, "I/O error: {}", e),
+            ImageError::UnsupportedFormat => write!(f, "Unsupported or unknown image format"),
+            ImageError::Decode(e) => write!(f, "Failed to decode image: {}", e),
+            ImageError::Encode(e) => write!(f, "Failed to encode image: {}", e),
+            ImageError::TooLarge { width, height } => {
+                write!(f, "Image too large ({}x{})", width, height)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+impl From<std::io::Error> for ImageError {
+    fn from(e: std::io::Error) -> Self {
+        ImageError::Io(e)
+    }
+}
+
+// 8-bit RGBA pixels, row-major, no padding.
+#[derive(Debug, Clone)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OptimizeOptions {
+    pub quality: u32,
+    pub max_width: u32,
+    pub filter: ResizeFilter,
+}
+
+pub fn decode_file(path: &Path) -> Result<RgbaImage, ImageError> {
+    let bytes = std::fs::read(path)?;
+    decode_bytes(&bytes)
+}
+
+pub fn decode_bytes(bytes: &[u8]) -> Result<RgbaImage, ImageError> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        decode_png(bytes)
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        decode_jpeg(bytes)
+    } else {
+        Err(ImageError::UnsupportedFormat)
+    }
+}
+
+fn decode_png(bytes: &[u8]) -> Result<RgbaImage, ImageError> {
+    let mut decoder = png::Decoder::new_with_limits(
+        Cursor::new(bytes),
+        png::Limits { bytes: MAX_DECODED_BYTES },
+    );
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| ImageError::Decode(e.to_string()))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|e| ImageError::Decode(e.to_string()))?;
+    buf.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        png::ColorType::Indexed => {
+            return Err(ImageError::Decode("Unexpanded indexed PNG".to_string()));
+        }
+    };
+
+    Ok(RgbaImage {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+fn decode_jpeg(bytes: &[u8]) -> Result<RgbaImage, ImageError> {
+    let mut decoder = jpeg_decoder::Decoder::new(Cursor::new(bytes));
+    decoder.set_max_decoding_buffer_size(MAX_DECODED_BYTES);
+    let data = decoder
+        .decode()
+        .map_err(|e| ImageError::Decode(e.to_string()))?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| ImageError::Decode("Missing JPEG header".to_string()))?;
+
+    let pixels = match info.pixel_format {
+        jpeg_decoder::PixelFormat::RGB24 => data
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        jpeg_decoder::PixelFormat::L8 => data.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        // Big-endian 16-bit luma: keep the high byte.
+        jpeg_decoder::PixelFormat::L16 => data
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], 255])
+            .collect(),
+        jpeg_decoder::PixelFormat::CMYK32 => data
+            .chunks_exact(4)
+            .flat_map(|p| {
+                let k = p[3] as u16;
+                let channel = |c: u8| ((c as u16 * k) / 255) as u8;
+                [channel(p[0]), channel(p[1]), channel(p[2]), 255]
+            })
+            .collect(),
+    };
+
+    Ok(RgbaImage {
+        width: info.width as u32,
+        height: info.height as u32,
+        pixels,
+    })
+}
+
+// Per output pixel: index of the first contributing source pixel and the normalized weights.
+fn compute_weights(src_len: u32, dst_len: u32, filter: ResizeFilter) -> Vec<(usize, Vec<f32>)> {
+    let scale = src_len as f32 / dst_len as f32;
+    let filter_scale = scale.max(1.0);
+    let radius = filter.support() * filter_scale;
+
+    (0..dst_len)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let start = (center - radius).floor().max(0.0) as usize;
+            let end = ((center + radius).ceil() as usize).min(src_len as usize);
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| filter.weight((j as f32 + 0.5 - center) / filter_scale))
+                .collect();
+            let sum: f32 = weights.iter().sum();
+            if sum.abs() > f32::EPSILON {
+                weights.iter_mut().for_each(|w| *w /= sum);
+                (start, weights)
+            } else {
+                // Degenerate kernel: fall back to nearest neighbour.
+                let nearest = (center as usize).min(src_len as usize - 1);
+                (nearest, vec![1.0])
+            }
+        })
+        .collect()
+}
+
+fn clamp_channel(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+pub fn resize(image: &RgbaImage, width: u32, height: u32, filter: ResizeFilter) -> RgbaImage {
+    if width == image.width && height == image.height {
+        return image.clone();
+    }
+    let width = width.max(1);
+    let height = height.max(1);
+    let src_w = image.width as usize;
+
+    // Horizontal pass: src_w x src_h -> width x src_h
+    let horizontal = compute_weights(image.width, width, filter);
+    let mut tmp = vec![0u8; width as usize * image.height as usize * 4];
+    for y in 0..image.height as usize {
+        let src_row = &image.pixels[y * src_w * 4..(y + 1) * src_w * 4];
+        let dst_row = &mut tmp[y * width as usize * 4..(y + 1) * width as usize * 4];
+        for (x, (start, weights)) in horizontal.iter().enumerate() {
+            let mut acc = [0.0f32; 4];
+            for (k, w) in weights.iter().enumerate() {
+                let p = &src_row[(start + k) * 4..(start + k) * 4 + 4];
+                for c in 0..4 {
+                    acc[c] += p[c] as f32 * w;
+                }
+            }
+            for c in 0..4 {
+                dst_row[x * 4 + c] = clamp_channel(acc[c]);
+            }
+        }
+    }
+
+    // Vertical pass: width x src_h -> width x height
+    let vertical = compute_weights(image.height, height, filter);
+    let mut out = RgbaImage::new(width, height);
+    let row_len = width as usize * 4;
+    for (y, (start, weights)) in vertical.iter().enumerate() {
+        let dst_row = &mut out.pixels[y * row_len..(y + 1) * row_len];
+        for i in 0..row_len {
+            let mut acc = 0.0f32;
+            for (k, w) in weights.iter().enumerate() {
+                acc += tmp[(start + k) * row_len + i] as f32 * w;
+            }
+            dst_row[i] = clamp_channel(acc);
+        }
+    }
+
+    out
+}
+
+// Scale down to max_width keeping the aspect ratio. 0 means no limit.
+pub fn fit_to_width(image: &RgbaImage, max_width: u32, filter: ResizeFilter) -> Option<RgbaImage> {
+    if max_width == 0 || image.width <= max_width {
+        return None;
+    }
+    let height = ((image.height as u64 * max_width as u64) as f64 / image.width as f64).round() as u32;
+    Some(resize(image, max_width, height.max(1), filter))
+}
+
+// JPEG has no alpha channel; flatten onto white like the stitch background.
+fn flatten_to_rgb(image: &RgbaImage) -> Vec<u8> {
+    image
+        .pixels
+        .chunks_exact(4)
+        .flat_map(|p| {
+            let a = p[3] as u32;
+            let blend = |c: u8| ((c as u32 * a + 255 * (255 - a) + 127) / 255) as u8;
+            [blend(p[0]), blend(p[1]), blend(p[2])]
+        })
+        .collect()
+}
+
+pub fn encode_jpeg(image: &RgbaImage, quality: u32) -> Result<Vec<u8>, ImageError> {
+    if image.width > MAX_JPEG_DIMENSION || image.height > MAX_JPEG_DIMENSION {
+        return Err(ImageError::TooLarge {
+            width: image.width,
+            height: image.height,
+        });
+    }
+    let rgb = flatten_to_rgb(image);
+    let mut out = Vec::new();
+    let encoder = jpeg_encoder::Encoder::new(&mut out, quality.clamp(1, 100) as u8);
+    encoder
+        .encode(&rgb, image.width as u16, image.height as u16, jpeg_encoder::ColorType::Rgb)
+        .map_err(|e| ImageError::Encode(e.to_string()))?;
+    Ok(out)
+}
+
+pub fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, ImageError> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder
+        .write_header()
+        .map_err(|e| ImageError::Encode(e.to_string()))?;
+    writer
+        .write_image_data(&image.pixels)
+        .map_err(|e| ImageError::Encode(e.to_string()))?;
+    writer.finish().map_err(|e| ImageError::Encode(e.to_string()))?;
+    Ok(out)
+}
+
+// Shrink to `max_width` and encode as a JPEG at `quality`.
+pub fn optimize_image(image: &RgbaImage, options: &OptimizeOptions) -> Result<Vec<u8>, ImageError> {
+    match fit_to_width(image, options.max_width, options.filter) {
+        Some(resized) => encode_jpeg(&resized, options.quality),
+        None => encode_jpeg(image, options.quality),
+    }
+}
+
+// Decode `src`, shrink to `max_width` and write a JPEG at `quality` to `dest`.
+// `src` and `dest` may be the same path.
+pub fn optimize_file(src: &Path, dest: &Path, options: &OptimizeOptions) -> Result<(), ImageError> {
+    let bytes = optimize_image(&decode_file(src)?, options)?;
+    std::fs::write(dest, bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> RgbaImage {
+        RgbaImage {
+            width,
+            height,
+            pixels: rgba.repeat(width as usize * height as usize),
+        }
+    }
+
+    fn pixel(image: &RgbaImage, x: u32, y: u32) -> [u8; 4] {
+        let i = (y * image.width + x) as usize * 4;
+        image.pixels[i..i + 4].try_into().unwrap()
+    }
+
+    fn assert_close(actual: [u8; 4], expected: [u8; 4], tolerance: u8) {
+        for c in 0..4 {
+            assert!(
+                actual[c].abs_diff(expected[c]) <= tolerance,
+                "{:?} is not within {} of {:?}",
+                actual,
+                tolerance,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn png_round_trip_keeps_pixels() {
+        let mut image = solid(3, 2, [10, 20, 30, 255]);
+        image.pixels[4..8].copy_from_slice(&[200, 100, 50, 128]);
+
+        let decoded = decode_bytes(&encode_png(&image).unwrap()).unwrap();
+
+        assert_eq!((decoded.width, decoded.height), (3, 2));
+        assert_eq!(decoded.pixels, image.pixels);
+    }
+
+    #[test]
+    fn grayscale_png_is_expanded_to_rgba() {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0, 200]).unwrap();
+        writer.finish().unwrap();
+
+        let decoded = decode_bytes(&bytes).unwrap();
+
+        assert_eq!(decoded.pixels, [0, 0, 0, 255, 200, 200, 200, 255]);
+    }
+
+    #[test]
+    fn jpeg_round_trip_is_close() {
+        let image = solid(16, 8, [200, 40, 90, 255]);
+
+        let decoded = decode_bytes(&encode_jpeg(&image, 95).unwrap()).unwrap();
+
+        assert_eq!((decoded.width, decoded.height), (16, 8));
+        assert_close(pixel(&decoded, 5, 5), [200, 40, 90, 255], 6);
+    }
+
+    #[test]
+    fn jpeg_flattens_transparency_onto_white() {
+        let image = solid(8, 8, [0, 0, 0, 0]);
+
+        let decoded = decode_bytes(&encode_jpeg(&image, 95).unwrap()).unwrap();
+
+        assert_close(pixel(&decoded, 4, 4), [255, 255, 255, 255], 2);
+    }
+
+    #[test]
+    fn unknown_and_truncated_input_is_rejected() {
+        assert!(matches!(decode_bytes(b"GIF89a"), Err(ImageError::UnsupportedFormat)));
+        let png = encode_png(&solid(4, 4, [1, 2, 3, 255])).unwrap();
+        assert!(matches!(decode_bytes(&png[..png.len() / 2]), Err(ImageError::Decode(_))));
+    }
+
+    #[test]
+    fn jpeg_over_the_decode_limit_is_rejected() {
+        // SOF0 header claiming 65535x65535 RGB, ~12 GB decoded
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0xFF, 0xFF, 0xFF, 0xFF, 0x03];
+        for id in 1..=3 {
+            jpeg.extend_from_slice(&[id, 0x11, 0x00]);
+        }
+        jpeg.extend_from_slice(&[0xFF, 0xD9]);
+
+        assert!(matches!(decode_bytes(&jpeg), Err(ImageError::Decode(_))));
+    }
+
+    #[test]
+    fn jpeg_encode_rejects_dimensions_over_u16() {
+        let image = RgbaImage::new(MAX_JPEG_DIMENSION + 1, 1);
+
+        assert!(matches!(
+            encode_jpeg(&image, 80),
+            Err(ImageError::TooLarge { width, height: 1 }) if width == MAX_JPEG_DIMENSION + 1
+        ));
+    }
+
+    #[test]
+    fn resize_keeps_solid_colour_with_both_filters() {
+        let image = solid(40, 30, [12, 34, 56, 255]);
+        for filter in [ResizeFilter::Lanczos, ResizeFilter::Bilinear] {
+            let down = resize(&image, 13, 7, filter);
+            assert_eq!((down.width, down.height), (13, 7));
+            assert!(down.pixels.chunks_exact(4).all(|p| p == [12, 34, 56, 255]));
+
+            let up = resize(&image, 97, 61, filter);
+            assert_eq!((up.width, up.height), (97, 61));
+            assert!(up.pixels.chunks_exact(4).all(|p| p == [12, 34, 56, 255]));
+        }
+    }
+
+    #[test]
+    fn bilinear_halving_averages_pairs() {
+        let image = RgbaImage {
+            width: 2,
+            height: 1,
+            pixels: vec![0, 0, 0, 255, 200, 200, 200, 255],
+        };
+
+        let half = resize(&image, 1, 1, ResizeFilter::Bilinear);
+
+        assert_eq!(half.pixels, [100, 100, 100, 255]);
+    }
+
+    #[test]
+    fn lanczos_keeps_a_sharp_edge_sharper_than_bilinear() {
+        let mut image = solid(64, 1, [0, 0, 0, 255]);
+        for x in 32..64 {
+            image.pixels[x * 4..x * 4 + 3].copy_from_slice(&[255, 255, 255]);
+        }
+
+        let lanczos = resize(&image, 16, 1, ResizeFilter::Lanczos);
+        let bilinear = resize(&image, 16, 1, ResizeFilter::Bilinear);
+
+        // The pixel just before the edge: Lanczos rings below bilinear's blur
+        assert!(pixel(&lanczos, 7, 0)[0] < pixel(&bilinear, 7, 0)[0]);
+        assert_eq!(pixel(&lanczos, 0, 0), [0, 0, 0, 255]);
+        assert_eq!(pixel(&lanczos, 15, 0), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn fit_to_width_only_shrinks_and_keeps_aspect() {
+        let image = RgbaImage::new(2000, 999);
+
+        assert!(fit_to_width(&image, 0, ResizeFilter::Lanczos).is_none());
+        assert!(fit_to_width(&image, 2000, ResizeFilter::Lanczos).is_none());
+        let fitted = fit_to_width(&image, 1000, ResizeFilter::Bilinear).unwrap();
+        assert_eq!((fitted.width, fitted.height), (1000, 500));
+        let thin = fit_to_width(&RgbaImage::new(3000, 1), 10, ResizeFilter::Bilinear).unwrap();
+        assert_eq!((thin.width, thin.height), (10, 1));
+    }
+
+    #[test]
+    fn optimize_file_writes_a_resized_jpeg() {
+        let dir = std::env::temp_dir().join(format!("screenshotapp-pipeline-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("capture.png");
+        let dest = dir.join("capture.jpg");
+        std::fs::write(&src, encode_png(&solid(300, 100, [90, 90, 90, 255])).unwrap()).unwrap();
+        let options = OptimizeOptions {
+            quality: 70,
+            max_width: 150,
+            filter: ResizeFilter::Lanczos,
+        };
+
+        optimize_file(&src, &dest, &options).unwrap();
+
+        let written = decode_file(&dest).unwrap();
+        assert_eq!((written.width, written.height), (150, 50));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
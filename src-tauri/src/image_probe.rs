@@ -0,0 +1,287 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::image_pipeline::ImageError;
+
+// Header-only dimension probing for PNG, JPEG, WebP and GIF.
+// Reads at most a few segments, never decodes pixel data.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    WebP,
+    Gif,
+}
+
+pub fn detect_format(header: &[u8]) -> Option<ImageFormat> {
+    if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(ImageFormat::Png)
+    } else if header.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(ImageFormat::Jpeg)
+    } else if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WEBP" {
+        Some(ImageFormat::WebP)
+    } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        Some(ImageFormat::Gif)
+    } else {
+        None
+    }
+}
+
+pub fn probe_dimensions(path: &Path) -> Result<(u32, u32), ImageError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    probe_reader(&mut reader)
+}
+
+pub fn probe_reader<R: Read + Seek>(reader: &mut R) -> Result<(u32, u32), ImageError> {
+    let mut header = [0u8; 30];
+    let len = read_up_to(reader, &mut header)?;
+    let header = &header[..len];
+
+    let (width, height) = match detect_format(header).ok_or(ImageError::UnsupportedFormat)? {
+        ImageFormat::Png => probe_png(header)?,
+        ImageFormat::Gif => probe_gif(header)?,
+        ImageFormat::WebP => probe_webp(header)?,
+        ImageFormat::Jpeg => {
+            reader.seek(SeekFrom::Start(2))?;
+            probe_jpeg(reader)?
+        }
+    };
+
+    if width == 0 || height == 0 {
+        return Err(ImageError::Decode(format!("Invalid dimensions {}x{}", width, height)));
+    }
+    Ok((width, height))
+}
+
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize, ImageError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+fn truncated(format: &str) -> ImageError {
+    ImageError::Decode(format!("Truncated {} header", format))
+}
+
+fn be_u16(b: &[u8]) -> u32 {
+    u16::from_be_bytes([b[0], b[1]]) as u32
+}
+
+fn le_u16(b: &[u8]) -> u32 {
+    u16::from_le_bytes([b[0], b[1]]) as u32
+}
+
+fn le_u24(b: &[u8]) -> u32 {
+    b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16
+}
+
+// Signature (8) + IHDR length (4) + "IHDR" (4) + width (4) + height (4)
+fn probe_png(header: &[u8]) -> Result<(u32, u32), ImageError> {
+    if header.len() < 24 || &header[12..16] != b"IHDR" {
+        return Err(truncated("PNG"));
+    }
+    let width = u32::from_be_bytes([header[16], header[17], header[18], header[19]]);
+    let height = u32::from_be_bytes([header[20], header[21], header[22], header[23]]);
+    Ok((width, height))
+}
+
+// Signature (6) + logical screen width (2, LE) + height (2, LE)
+fn probe_gif(header: &[u8]) -> Result<(u32, u32), ImageError> {
+    if header.len() < 10 {
+        return Err(truncated("GIF"));
+    }
+    Ok((le_u16(&header[6..8]), le_u16(&header[8..10])))
+}
+
+fn probe_webp(header: &[u8]) -> Result<(u32, u32), ImageError> {
+    if header.len() < 30 {
+        return Err(truncated("WebP"));
+    }
+    match &header[12..16] {
+        // Lossy: frame tag (3) + start code 9d 01 2a + 14-bit width/height
+        b"VP8 " => {
+            if header[23..26] != [0x9D, 0x01, 0x2A] {
+                return Err(ImageError::Decode("Invalid VP8 start code".to_string()));
+            }
+            Ok((le_u16(&header[26..28]) & 0x3FFF, le_u16(&header[28..30]) & 0x3FFF))
+        }
+        // Lossless: signature 0x2f + 14-bit (width - 1), 14-bit (height - 1)
+        b"VP8L" => {
+            if header[20] != 0x2F {
+                return Err(ImageError::Decode("Invalid VP8L signature".to_string()));
+            }
+            let bits = u32::from_le_bytes([header[21], header[22], header[23], header[24]]);
+            Ok(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        // Extended: flags (4) + 24-bit (canvas width - 1) + 24-bit (canvas height - 1)
+        b"VP8X" => Ok((le_u24(&header[24..27]) + 1, le_u24(&header[27..30]) + 1)),
+        _ => Err(ImageError::Decode("Unknown WebP chunk".to_string())),
+    }
+}
+
+// Walk JPEG markers (reader positioned after SOI) until a start-of-frame segment.
+fn probe_jpeg<R: Read + Seek>(reader: &mut R) -> Result<(u32, u32), ImageError> {
+    let mut byte = [0u8; 1];
+    loop {
+        // Find the next marker, skipping fill bytes
+        reader.read_exact(&mut byte).map_err(|_| truncated("JPEG"))?;
+        if byte[0] != 0xFF {
+            return Err(ImageError::Decode("Invalid JPEG marker".to_string()));
+        }
+        let mut marker = 0xFF;
+        while marker == 0xFF {
+            reader.read_exact(&mut byte).map_err(|_| truncated("JPEG"))?;
+            marker = byte[0];
+        }
+
+        match marker {
+            // Standalone markers without a length field
+            0x01 | 0xD0..=0xD7 => continue,
+            0xD9 | 0xDA => return Err(ImageError::Decode("No JPEG frame header".to_string())),
+            _ => {}
+        }
+
+        let mut len_bytes = [0u8; 2];
+        reader.read_exact(&mut len_bytes).map_err(|_| truncated("JPEG"))?;
+        let len = be_u16(&len_bytes);
+        if len < 2 {
+            return Err(ImageError::Decode("Invalid JPEG segment length".to_string()));
+        }
+
+        // SOF0..SOF15, excluding DHT (C4), JPG (C8) and DAC (CC)
+        if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let mut sof = [0u8; 5];
+            reader.read_exact(&mut sof).map_err(|_| truncated("JPEG"))?;
+            // precision (1) + height (2) + width (2)
+            return Ok((be_u16(&sof[3..5]), be_u16(&sof[1..3])));
+        }
+
+        reader.seek(SeekFrom::Current(len as i64 - 2))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::image_pipeline::{encode_jpeg, encode_png, RgbaImage};
+
+    fn probe(bytes: &[u8]) -> Result<(u32, u32), ImageError> {
+        probe_reader(&mut Cursor::new(bytes))
+    }
+
+    fn webp(chunk: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut bytes = b"RIFF\0\0\0\0WEBP".to_vec();
+        bytes.extend_from_slice(chunk);
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        bytes.extend_from_slice(payload);
+        bytes.resize(bytes.len().max(30), 0);
+        bytes
+    }
+
+    // SOI, then `segments`, then SOF0 for `width` x `height`
+    fn jpeg_with(segments: &[u8], width: u16, height: u16) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xD8];
+        bytes.extend_from_slice(segments);
+        bytes.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x0B, 0x08]);
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&[0x01, 0x01, 0x11, 0x00]);
+        bytes
+    }
+
+    #[test]
+    fn detects_formats_from_magic_bytes() {
+        assert_eq!(detect_format(b"\x89PNG\r\n\x1a\n...."), Some(ImageFormat::Png));
+        assert_eq!(detect_format(&[0xFF, 0xD8, 0xFF, 0xE0]), Some(ImageFormat::Jpeg));
+        assert_eq!(detect_format(b"RIFF\0\0\0\0WEBPVP8 "), Some(ImageFormat::WebP));
+        assert_eq!(detect_format(b"GIF87a"), Some(ImageFormat::Gif));
+        assert_eq!(detect_format(b"GIF89a"), Some(ImageFormat::Gif));
+        assert_eq!(detect_format(b"RIFF\0\0\0\0WAVE"), None);
+        assert_eq!(detect_format(b"RIFF"), None);
+        assert_eq!(detect_format(&[]), None);
+    }
+
+    #[test]
+    fn reads_png_and_encoded_jpeg_sizes() {
+        let image = RgbaImage::new(37, 11);
+        assert_eq!(probe(&encode_png(&image).unwrap()).unwrap(), (37, 11));
+        assert_eq!(probe(&encode_jpeg(&image, 80).unwrap()).unwrap(), (37, 11));
+    }
+
+    #[test]
+    fn reads_gif_and_webp_sizes() {
+        assert_eq!(probe(b"GIF89a\x40\x01\xF0\x00").unwrap(), (320, 240));
+
+        let lossy = webp(b"VP8 ", &[0, 0, 0, 0x9D, 0x01, 0x2A, 0x80, 0x02, 0xE0, 0x01]);
+        assert_eq!(probe(&lossy).unwrap(), (640, 480));
+
+        // (width - 1) = 99, (height - 1) = 49, packed as 14-bit fields
+        let bits: u32 = 99 | (49 << 14);
+        let mut lossless = vec![0x2F];
+        lossless.extend_from_slice(&bits.to_le_bytes());
+        assert_eq!(probe(&webp(b"VP8L", &lossless)).unwrap(), (100, 50));
+
+        let extended = webp(b"VP8X", &[0, 0, 0, 0, 0xFF, 0x0F, 0x00, 0x37, 0x04, 0x00]);
+        assert_eq!(probe(&extended).unwrap(), (4096, 1080));
+    }
+
+    #[test]
+    fn jpeg_scan_skips_segments_and_fill_bytes() {
+        let app0 = [0xFF, 0xE0, 0x00, 0x06, b'J', b'F', b'I', b'F'];
+        let dqt_with_fill = [0xFF, 0xFF, 0xFF, 0xDB, 0x00, 0x03, 0x00];
+        let mut segments = app0.to_vec();
+        segments.extend_from_slice(&dqt_with_fill);
+        // DHT (C4) has an SOF-range marker but is not a frame header
+        segments.extend_from_slice(&[0xFF, 0xC4, 0x00, 0x04, 0x00, 0x00]);
+
+        assert_eq!(probe(&jpeg_with(&segments, 1920, 1080)).unwrap(), (1920, 1080));
+    }
+
+    #[test]
+    fn jpeg_without_frame_header_is_an_error() {
+        let scan_first = [0xFF, 0xD8, 0xFF, 0xDA, 0x00, 0x02];
+        assert!(matches!(probe(&scan_first), Err(ImageError::Decode(_))));
+
+        let bad_marker = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0x12, 0x34];
+        assert!(matches!(probe(&bad_marker), Err(ImageError::Decode(_))));
+    }
+
+    #[test]
+    fn truncated_headers_are_errors() {
+        // SOI (2) + SOF marker (2) + length (2) + precision (1) + height (2) + width (2)
+        let jpeg = jpeg_with(&[], 10, 10);
+        for len in 3..11 {
+            assert!(probe(&jpeg[..len]).is_err(), "JPEG cut at {} bytes", len);
+        }
+        let png = encode_png(&RgbaImage::new(5, 5)).unwrap();
+        assert!(matches!(probe(&png[..20]), Err(ImageError::Decode(_))));
+        assert!(matches!(probe(b"GIF89a\x01\x00"), Err(ImageError::Decode(_))));
+        assert!(matches!(probe(&webp(b"VP8 ", &[])[..20]), Err(ImageError::Decode(_))));
+        assert!(matches!(probe(b"BM\0\0"), Err(ImageError::UnsupportedFormat)));
+    }
+
+    #[test]
+    fn zero_dimensions_are_rejected() {
+        assert!(matches!(probe(&jpeg_with(&[], 0, 10)), Err(ImageError::Decode(_))));
+        assert!(matches!(probe(b"GIF89a\x00\x00\x10\x00"), Err(ImageError::Decode(_))));
+    }
+
+    #[test]
+    fn probe_dimensions_reads_files() {
+        let path = std::env::temp_dir().join(format!("screenshotapp-probe-{}.png", std::process::id()));
+        std::fs::write(&path, encode_png(&RgbaImage::new(8, 3)).unwrap()).unwrap();
+
+        assert_eq!(probe_dimensions(&path).unwrap(), (8, 3));
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(probe_dimensions(&path), Err(ImageError::Io(_))));
+    }
+}
@@ -1,47 +1,57 @@
-use std::collections::HashMap;
-use std::process::Command;
+mod capture;
+mod capture_job;
+mod clipboard;
+mod clipboard_cache;
+mod command_runner;
+mod counter;
+mod error;
+mod filename_template;
+mod frontmost;
+mod history;
+mod image_pipeline;
+mod image_probe;
+mod metadata;
+mod note_burn;
+mod output;
+mod permissions;
+mod settings_file;
+mod shortcut_conflicts;
+mod shortcuts;
+mod stitch;
+mod stitch_jobs;
+mod stitch_sources;
+mod stitch_session;
+
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use chrono::Local;
 use serde::{Deserialize, Serialize};
 use tauri::{
-    menu::{Menu, MenuItem},
+    menu::{IsMenuItem, Menu, MenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
     webview::WebviewWindowBuilder,
-    Emitter, Manager, WindowEvent, State,
+    Manager, WindowEvent, State,
 };
-use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct FilenameBlock {
-    pub id: String,
-    pub enabled: bool,
-    pub value: Option<String>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct FilenameTemplate {
-    pub blocks: Vec<FilenameBlock>,
-    pub use_counter: bool,
-}
-
-impl Default for FilenameTemplate {
-    fn default() -> Self {
-        Self {
-            blocks: vec![
-                FilenameBlock { id: "prefix".to_string(), enabled: true, value: Some("llm-scr".to_string()) },
-                FilenameBlock { id: "date".to_string(), enabled: true, value: None },
-                FilenameBlock { id: "time".to_string(), enabled: true, value: None },
-                FilenameBlock { id: "quality".to_string(), enabled: true, value: None },
-                FilenameBlock { id: "dimensions".to_string(), enabled: true, value: None },
-                FilenameBlock { id: "counter".to_string(), enabled: false, value: None },
-            ],
-            use_counter: false,
-        }
-    }
-}
+use capture::{CaptureBackendKind, CaptureRegion, CaptureTarget};
+use capture_job::{run_capture_job, CaptureMode};
+use clipboard::ClipboardPayload;
+use clipboard_cache::ClipboardCache;
+use command_runner::{CommandRunner, SystemRunner};
+use filename_template::NameContext;
+use history::{HistoryEntry, HistoryItem, HistoryQuery};
+pub use filename_template::{FilenameBlock, FilenameTemplate};
+use image_pipeline::{OptimizeOptions, ResizeFilter};
+use metadata::ScreenshotMetadata;
+use note_burn::{BurnNoteOptions, NotePlacement};
+use output::{OutputDestination, OutputSettings};
+use stitch::StitchLayout;
+use stitch_jobs::{JobHandle, JobRegistry};
+use stitch_session::StitchSession;
+use error::AppError;
+use permissions::{PermissionDiagnostics, PermissionState};
+use shortcuts::{ShortcutAction, ShortcutBinding, ShortcutRegistry};
+use stitch_sources::StitchSource;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -49,29 +59,26 @@ pub struct Settings {
     pub quality: u32,
     pub max_width: u32,
     #[serde(default)]
+    pub resize_filter: ResizeFilter,
+    #[serde(default)]
+    pub capture_backend: CaptureBackendKind,
+    #[serde(default)]
+    pub output: OutputSettings,
+    #[serde(default)]
     pub note_prefix_enabled: bool,
     #[serde(default)]
     pub note_prefix: String,
     #[serde(default)]
+    pub note_placement: NotePlacement,
+    #[serde(default)]
     pub filename_template: FilenameTemplate,
-    #[serde(default = "default_fullscreen_shortcut")]
-    pub fullscreen_shortcut: String,
-    #[serde(default = "default_area_shortcut")]
-    pub area_shortcut: String,
-    #[serde(default = "default_stitch_shortcut")]
-    pub stitch_shortcut: String,
-}
-
-fn default_fullscreen_shortcut() -> String {
-    "Cmd+Shift+3".to_string()
-}
-
-fn default_area_shortcut() -> String {
-    "Cmd+Shift+4".to_string()
-}
-
-fn default_stitch_shortcut() -> String {
-    "Cmd+Shift+2".to_string()
+    #[serde(default)]
+    pub stitch_layout: StitchLayout,
+    // What the stitch shortcut and tray item stitch
+    #[serde(default)]
+    pub stitch_source: StitchSource,
+    #[serde(default = "shortcuts::default_bindings")]
+    pub shortcuts: Vec<ShortcutBinding>,
 }
 
 impl Default for Settings {
@@ -79,65 +86,85 @@ impl Default for Settings {
         Self {
             quality: 70,
             max_width: 1024,
+            resize_filter: ResizeFilter::default(),
+            capture_backend: CaptureBackendKind::default(),
+            output: OutputSettings::default(),
             note_prefix_enabled: false,
             note_prefix: String::new(),
+            note_placement: NotePlacement::default(),
             filename_template: FilenameTemplate::default(),
-            fullscreen_shortcut: default_fullscreen_shortcut(),
-            area_shortcut: default_area_shortcut(),
-            stitch_shortcut: default_stitch_shortcut(),
+            stitch_layout: StitchLayout::default(),
+            stitch_source: StitchSource::default(),
+            shortcuts: shortcuts::default_bindings(),
         }
     }
 }
 
 pub struct AppState {
     pub settings: Mutex<Settings>,
-    pub active_fullscreen_shortcut: Mutex<Shortcut>,
-    pub active_area_shortcut: Mutex<Shortcut>,
-    pub active_stitch_shortcut: Mutex<Shortcut>,
-    pub stitch_lock: Mutex<bool>,
-    pub stitched_dimensions: Mutex<HashMap<String, (u32, u32)>>,
+    pub shortcuts: ShortcutRegistry,
+    // Last area selection, for repeating it
+    pub last_region: Mutex<Option<CaptureRegion>>,
+    pub stitch_session: Mutex<StitchSession>,
+    pub stitch_jobs: JobRegistry,
+    pub clipboard_cache: ClipboardCache,
+    pub permissions: PermissionDiagnostics,
+    // Runs screencapture, osascript and open; replaced by a fake in tests
+    pub runner: Arc<dyn CommandRunner>,
+    // Counter, history and temp capture files
+    pub data: DataPaths,
 }
 
-static CLIPBOARD_CACHE_COUNTER: AtomicU64 = AtomicU64::new(0);
+static TEMP_CAPTURE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Counter values tried for one capture before giving up on finding a free name
+const MAX_COUNTER_ATTEMPTS: usize = 1000;
 
 fn get_settings_path() -> std::path::PathBuf {
     let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
     std::path::PathBuf::from(home).join(".screenshot_app_settings.json")
 }
 
-fn settings_file_has_stitch_shortcut() -> bool {
-    let path = get_settings_path();
-    if !path.exists() {
-        return true;
-    }
-    let Ok(content) = std::fs::read_to_string(&path) else {
-        return false;
-    };
-    let Ok(value) = serde_json::from_str::<serde_json::Value>(&content) else {
-        return false;
-    };
-    value.get("stitchShortcut").is_some()
+fn get_counter_path() -> std::path::PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    std::path::PathBuf::from(home).join(".screenshot_app_counter.json")
 }
 
-fn load_settings_from_file() -> Settings {
-    let path = get_settings_path();
-    if path.exists() {
-        if let Ok(content) = std::fs::read_to_string(&path) {
-            if let Ok(settings) = serde_json::from_str::<Settings>(&content) {
-                return settings;
-            }
+// Files the app keeps for itself. Held in `AppState` and handed to captures
+// through `CaptureEnv`, so tests can point them at a scratch directory instead
+// of changing $HOME.
+#[derive(Debug, Clone)]
+pub struct DataPaths {
+    pub counter: std::path::PathBuf,
+    pub history: std::path::PathBuf,
+    pub temp_captures: std::path::PathBuf,
+}
+
+impl DataPaths {
+    pub fn system() -> Self {
+        Self {
+            counter: get_counter_path(),
+            history: history::history_path(),
+            temp_captures: output::temp_capture_dir(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn under(base: &std::path::Path) -> Self {
+        Self {
+            counter: base.join(".screenshot_app_counter.json"),
+            history: base.join(".screenshot_app_history.jsonl"),
+            temp_captures: base.join("captures"),
         }
     }
-    Settings::default()
+}
+
+fn load_settings_from_file() -> Settings {
+    settings_file::load(&get_settings_path())
 }
 
 fn save_settings_to_file(settings: &Settings) -> Result<(), String> {
-    let path = get_settings_path();
-    let content = serde_json::to_string_pretty(settings)
-        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
-    std::fs::write(&path, content)
-        .map_err(|e| format!("Failed to write settings: {}", e))?;
-    Ok(())
+    settings_file::save(&get_settings_path(), settings)
 }
 
 #[tauri::command]
@@ -146,234 +173,137 @@ fn get_settings(state: State<AppState>) -> Settings {
 }
 
 #[tauri::command]
-fn save_settings(state: State<AppState>, settings: Settings) -> Result<(), String> {
+fn save_settings(state: State<AppState>, mut settings: Settings) -> Result<(), AppError> {
+    settings.output.validate().map_err(AppError::Invalid)?;
+    settings.filename_template.validate().map_err(AppError::Invalid)?;
+    settings.stitch_layout.validate().map_err(AppError::Invalid)?;
+    settings.stitch_source.validate().map_err(AppError::Invalid)?;
     let mut current = state.settings.lock().unwrap();
+    // Shortcuts only change through update_shortcuts, which registers them first
+    settings.shortcuts = current.shortcuts.clone();
     *current = settings.clone();
-    save_settings_to_file(&settings)
+    save_settings_to_file(&settings).map_err(AppError::Failed)
 }
 
-#[tauri::command]
-async fn update_shortcuts(
-    app: tauri::AppHandle,
-    state: State<'_, AppState>,
-    fullscreen_shortcut: String,
-    area_shortcut: String,
-    stitch_shortcut: String,
-) -> Result<(), String> {
-    let fullscreen_shortcut = normalize_shortcut_string(&fullscreen_shortcut)?;
-    let area_shortcut = normalize_shortcut_string(&area_shortcut)?;
-    let stitch_shortcut = normalize_shortcut_string(&stitch_shortcut)?;
-    let new_full = parse_shortcut(&fullscreen_shortcut)?;
-    let new_area = parse_shortcut(&area_shortcut)?;
-    let new_stitch = parse_shortcut(&stitch_shortcut)?;
-    if new_full.id() == new_area.id()
-        || new_full.id() == new_stitch.id()
-        || new_area.id() == new_stitch.id()
-    {
-        return Err("Shortcuts must be different".to_string());
-    }
-
-    let (old_full_str, old_area_str, old_stitch_str) = {
-        let settings = state.settings.lock().unwrap();
-        (
-            settings.fullscreen_shortcut.clone(),
-            settings.area_shortcut.clone(),
-            settings.stitch_shortcut.clone(),
-        )
-    };
-
-    let old_full = parse_shortcut(&old_full_str).ok();
-    let old_area = parse_shortcut(&old_area_str).ok();
-    let old_stitch = parse_shortcut(&old_stitch_str).ok();
-
-    let global_shortcut = app.global_shortcut();
-
-    if let Some(ref s) = old_full {
-        let _ = global_shortcut.unregister(*s);
-    }
-    if let Some(ref s) = old_area {
-        let _ = global_shortcut.unregister(*s);
-    }
-    if let Some(ref s) = old_stitch {
-        let _ = global_shortcut.unregister(*s);
-    }
-
-    if let Err(e) = global_shortcut.register(new_full) {
-        if let Some(ref s) = old_full {
-            let _ = global_shortcut.register(*s);
-        }
-        if let Some(ref s) = old_area {
-            let _ = global_shortcut.register(*s);
-        }
-        if let Some(ref s) = old_stitch {
-            let _ = global_shortcut.register(*s);
-        }
-        return Err(format!("Failed to register fullscreen shortcut: {}", e));
-    }
-
-    if let Err(e) = global_shortcut.register(new_area) {
-        let _ = global_shortcut.unregister(new_full);
-        if let Some(ref s) = old_full {
-            let _ = global_shortcut.register(*s);
-        }
-        if let Some(ref s) = old_area {
-            let _ = global_shortcut.register(*s);
-        }
-        if let Some(ref s) = old_stitch {
-            let _ = global_shortcut.register(*s);
-        }
-        return Err(format!("Failed to register area shortcut: {}", e));
-    }
-
-    if let Err(e) = global_shortcut.register(new_stitch) {
-        let _ = global_shortcut.unregister(new_full);
-        let _ = global_shortcut.unregister(new_area);
-        if let Some(ref s) = old_full {
-            let _ = global_shortcut.register(*s);
-        }
-        if let Some(ref s) = old_area {
-            let _ = global_shortcut.register(*s);
-        }
-        if let Some(ref s) = old_stitch {
-            let _ = global_shortcut.register(*s);
-        }
-        return Err(format!("Failed to register stitch shortcut: {}", e));
-    }
-
-    let settings_snapshot = {
-        let mut settings = state.settings.lock().unwrap();
-        settings.fullscreen_shortcut = fullscreen_shortcut;
-        settings.area_shortcut = area_shortcut;
-        settings.stitch_shortcut = stitch_shortcut;
-        settings.clone()
-    };
-
-    *state.active_fullscreen_shortcut.lock().unwrap() = new_full;
-    *state.active_area_shortcut.lock().unwrap() = new_area;
-    *state.active_stitch_shortcut.lock().unwrap() = new_stitch;
-    if let Err(e) = save_settings_to_file(&settings_snapshot) {
-        let mut settings = state.settings.lock().unwrap();
-        settings.fullscreen_shortcut = old_full_str.clone();
-        settings.area_shortcut = old_area_str.clone();
-        settings.stitch_shortcut = old_stitch_str.clone();
-        drop(settings);
-
-        let _ = global_shortcut.unregister(new_full);
-        let _ = global_shortcut.unregister(new_area);
-        let _ = global_shortcut.unregister(new_stitch);
-        if let Some(ref s) = old_full {
-            let _ = global_shortcut.register(*s);
-        }
-        if let Some(ref s) = old_area {
-            let _ = global_shortcut.register(*s);
-        }
-        if let Some(ref s) = old_stitch {
-            let _ = global_shortcut.register(*s);
-        }
-        if let Some(s) = old_full {
-            *state.active_fullscreen_shortcut.lock().unwrap() = s;
-        }
-        if let Some(s) = old_area {
-            *state.active_area_shortcut.lock().unwrap() = s;
-        }
-        if let Some(s) = old_stitch {
-            *state.active_stitch_shortcut.lock().unwrap() = s;
-        }
-
-        return Err(e);
-    }
-
-    update_tray_labels(&app)?;
-
-    Ok(())
-}
-
-fn generate_temp_screenshot_path(extension: &str) -> String {
+fn generate_temp_screenshot_path(data: &DataPaths, extension: &str) -> Result<String, AppError> {
     let timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
         .as_millis();
-    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-    format!("{}/Desktop/llm-scr_tmp_{}.{}", home, timestamp, extension)
+    let temp_dir = &data.temp_captures;
+    std::fs::create_dir_all(temp_dir)
+        .map_err(|e| AppError::io("Failed to create temp capture dir", e))?;
+    // Several temp files can be created within one millisecond (stitch pages, parallel jobs)
+    let counter = TEMP_CAPTURE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    Ok(temp_dir
+        .join(format!("llm-scr_tmp_{}_{}.{}", timestamp, counter, extension))
+        .to_string_lossy()
+        .to_string())
 }
 
-fn generate_screenshot_path(extension: &str, settings: &Settings, width: u32, height: u32) -> String {
-    let now = Local::now();
-    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-    let template = &settings.filename_template;
-    
-    let mut parts: Vec<String> = Vec::new();
-    
-    for block in &template.blocks {
-        if !block.enabled {
-            continue;
-        }
-        
-        let part = match block.id.as_str() {
-            "prefix" => block.value.clone().unwrap_or_else(|| "llm-scr".to_string()),
-            "date" => now.format("%m-%d").to_string(),
-            "time" => now.format("%H-%M-%S").to_string(),
-            "quality" => format!("{}%", settings.quality),
-            "dimensions" => format!("{}x{}", width, height),
-            "counter" => String::new(), // handled separately below
-            _ => continue,
-        };
-        
-        if block.id != "counter" && !part.is_empty() {
-            parts.push(part);
+fn cleanup_temp_capture_dir() {
+    let temp_dir = output::temp_capture_dir();
+    if temp_dir.exists() {
+        if let Ok(entries) = std::fs::read_dir(&temp_dir) {
+            for entry in entries.flatten() {
+                let _ = std::fs::remove_file(entry.path());
+            }
         }
     }
-    
-    let mut base_name = parts.join("_");
-    if base_name.is_empty() {
-        base_name = "screenshot".to_string();
-    }
-    let counter_enabled = template.blocks.iter().any(|b| b.id == "counter" && b.enabled);
-    
-    if counter_enabled || template.use_counter {
-        // Use counter for uniqueness
-        let mut counter = 1u32;
-        loop {
-            let filename = if counter == 1 {
-                format!("{}/Desktop/{}.{}", home, base_name, extension)
-            } else {
-                format!("{}/Desktop/{}_{}.{}", home, base_name, counter, extension)
-            };
-            
-            if !std::path::Path::new(&filename).exists() {
-                return filename;
+}
+
+// Picks the final path and claims it with an empty placeholder, so neither existing files
+// nor a concurrent capture can be overwritten. The caller moves the real file over it.
+fn generate_screenshot_path(
+    data: &DataPaths,
+    extension: &str,
+    settings: &Settings,
+    destination: OutputDestination,
+    context: &NameContext,
+) -> Result<String, AppError> {
+    let mut paths = generate_screenshot_paths(data, extension, settings, destination, context, 1)?;
+    Ok(paths.remove(0))
+}
+
+// Multi-file variant: with `parts > 1` every file shares one stem (and counter value)
+// with a `_part1`, `_part2`... suffix.
+fn generate_screenshot_paths(
+    data: &DataPaths,
+    extension: &str,
+    settings: &Settings,
+    destination: OutputDestination,
+    context: &NameContext,
+    parts: usize,
+) -> Result<Vec<String>, AppError> {
+    let dir = output::resolve_output_dir(&settings.output, destination)?;
+    let template = &settings.filename_template;
+    let parsed = template.parse().map_err(|e| AppError::Invalid(e.to_string()))?;
+    let reserve_error = |e: std::io::Error| AppError::io("Failed to reserve screenshot filename", e);
+    let suffixes: Vec<String> = if parts > 1 {
+        (1..=parts).map(|part| format!("_part{}", part)).collect()
+    } else {
+        vec![String::new()]
+    };
+    let to_strings = |paths: Vec<std::path::PathBuf>| {
+        paths
+            .into_iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect()
+    };
+
+    if parsed.uses_counter() {
+        // Each attempt consumes a counter value, so the counter never goes backwards
+        let mut previous = None;
+        for _ in 0..MAX_COUNTER_ATTEMPTS {
+            let counter = counter::next_counter(
+                &data.counter,
+                template.counter_start,
+                template.counter_reset,
+                context.now.date_naive(),
+            )
+            .map_err(AppError::Failed)?;
+            // The counter saturates instead of wrapping
+            if previous == Some(counter) {
+                return Err(AppError::Failed(format!("The filename counter has reached its maximum ({})", counter)));
+            }
+            previous = Some(counter);
+            let stem = parsed.render(template, context, counter);
+            if let Some(paths) = output::try_reserve_all(&dir, &stem, extension, &suffixes).map_err(reserve_error)? {
+                return Ok(to_strings(paths));
             }
-            counter += 1;
         }
+        Err(AppError::Failed(format!(
+            "No free filename in {} after {} counter values",
+            dir.display(),
+            MAX_COUNTER_ATTEMPTS
+        )))
     } else {
-        format!("{}/Desktop/{}.{}", home, base_name, extension)
+        let stem = parsed.render(template, context, 0);
+        let paths = output::reserve_unique_paths(&dir, &stem, extension, &suffixes).map_err(reserve_error)?;
+        Ok(to_strings(paths))
     }
 }
 
-// Get image dimensions using sips (macOS)
-fn get_image_dimensions(filepath: &str) -> Result<(u32, u32), String> {
-    let output = Command::new("sips")
-        .args(["-g", "pixelWidth", "-g", "pixelHeight", filepath])
-        .output()
-        .map_err(|e| format!("Failed to get image dimensions: {}", e))?;
+#[tauri::command]
+fn preview_filename_template(template: FilenameTemplate) -> Result<String, AppError> {
+    template.validate().map_err(AppError::Invalid)?;
+    let parsed = template.parse().map_err(|e| AppError::Invalid(e.to_string()))?;
+    let context = NameContext {
+        now: Local::now(),
+        mode: "area".to_string(),
+        quality: 70,
+        width: 1920,
+        height: 1080,
+        app: Some("Safari".to_string()),
+        title: Some("Example Page".to_string()),
+    };
+    Ok(format!("{}.jpg", parsed.render(&template, &context, template.counter_start)))
+}
 
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    
-    let width: u32 = output_str
-        .lines()
-        .find(|line| line.contains("pixelWidth"))
-        .and_then(|line| line.split_whitespace().last())
-        .and_then(|w| w.parse().ok())
-        .unwrap_or(800);
-    
-    let height: u32 = output_str
-        .lines()
-        .find(|line| line.contains("pixelHeight"))
-        .and_then(|line| line.split_whitespace().last())
-        .and_then(|h| h.parse().ok())
-        .unwrap_or(600);
-    
-    Ok((width, height))
+// Get image dimensions from the file header (PNG/JPEG/WebP/GIF)
+fn get_image_dimensions(filepath: &str) -> Result<(u32, u32), AppError> {
+    image_probe::probe_dimensions(std::path::Path::new(filepath))
+        .map_err(|e| AppError::image(format!("Failed to get image dimensions of {}", filepath), e))
 }
 
 // Calculate editor window size based on image dimensions and padding
@@ -411,133 +341,64 @@ fn calculate_editor_window_size(img_width: u32, img_height: u32, padding: f64) -
     (window_w, window_h)
 }
 
-// Image optimization: configurable quality and max width via Settings
-// Default: 50% quality, 1280px max width
-// Resizes images wider than max_width to maintain performance
-fn optimize_screenshot(filepath: &str, settings: &Settings) -> Result<String, String> {
-    // Convert to JPEG with configured quality and resize to max width
-    let jpeg_path = filepath.replace(".png", ".jpg");
-
-    // Use sips to resize (if wider than max_width) and convert to JPEG
-    // First, get the width
-    let width_output = Command::new("sips")
-        .args(["-g", "pixelWidth", filepath])
-        .output()
-        .map_err(|e| format!("Failed to get image width: {}", e))?;
-
-    let width_str = String::from_utf8_lossy(&width_output.stdout);
-    let width: u32 = width_str
-        .lines()
-        .find(|line| line.contains("pixelWidth"))
-        .and_then(|line| line.split_whitespace().last())
-        .and_then(|w| w.parse().ok())
-        .unwrap_or(0);
-
-    // Resize if wider than max_width (0 means no resize)
-    if settings.max_width > 0 && width > settings.max_width {
-        Command::new("sips")
-            .args(["--resampleWidth", &settings.max_width.to_string(), filepath])
-            .output()
-            .map_err(|e| format!("Failed to resize: {}", e))?;
+// Image optimization: configurable quality, max width and resize filter via Settings
+// Decodes, resizes images wider than max_width and re-encodes as JPEG in-process
+fn optimize_options(settings: &Settings) -> OptimizeOptions {
+    OptimizeOptions {
+        quality: settings.quality,
+        max_width: settings.max_width,
+        filter: settings.resize_filter,
     }
+}
 
-    // Convert to JPEG with configured quality
-    let quality_str = settings.quality.to_string();
-    let output = Command::new("sips")
-        .args(["-s", "format", "jpeg", "-s", "formatOptions", &quality_str, filepath, "--out", &jpeg_path])
-        .output()
-        .map_err(|e| format!("Failed to convert to JPEG: {}", e))?;
+fn optimize_screenshot(filepath: &str, settings: &Settings) -> Result<String, AppError> {
+    let source = std::path::Path::new(filepath);
+    let jpeg_path = source.with_extension("jpg");
 
-    if output.status.success() {
+    image_pipeline::optimize_file(source, &jpeg_path, &optimize_options(settings))
+        .map_err(|e| AppError::image("Failed to optimize screenshot", e))?;
+
+    if jpeg_path != source {
         // Remove the original PNG
-        let _ = std::fs::remove_file(filepath);
-        Ok(jpeg_path)
-    } else {
-        // Fallback to PNG if conversion fails
-        Ok(filepath.to_string())
+        let _ = std::fs::remove_file(source);
     }
+    Ok(jpeg_path.to_string_lossy().to_string())
 }
 
-fn do_area_screenshot(app: &tauri::AppHandle) -> Result<String, String> {
-    if app.get_webview_window("rename").is_some() {
-        return Err("Please finish renaming the current screenshot first".to_string());
-    }
-
-    let state = app.state::<AppState>();
-    let settings = state.settings.lock().unwrap().clone();
-    let filepath = generate_temp_screenshot_path("png");
-
-    let output = Command::new("screencapture")
-        .args(["-i", "-x", &filepath])
-        .output()
-        .map_err(|e| format!("Failed to run screencapture: {}", e))?;
-
-    if output.status.success() {
-        if std::path::Path::new(&filepath).exists() {
-            let optimized_path = optimize_screenshot(&filepath, &settings)?;
-            let (width, height) = get_image_dimensions(&optimized_path)?;
-            let extension = std::path::Path::new(&optimized_path)
-                .extension()
-                .and_then(|e| e.to_str())
-                .unwrap_or("png");
-            let final_path = generate_screenshot_path(extension, &settings, width, height);
-            std::fs::rename(&optimized_path, &final_path)
-                .map_err(|e| format!("Failed to rename screenshot: {}", e))?;
-            Ok(final_path)
-        } else {
-            Err("Screenshot cancelled".to_string())
-        }
-    } else {
-        Err("Screenshot cancelled or failed".to_string())
-    }
+fn run_capture(
+    runner: &Arc<dyn CommandRunner>,
+    settings: &Settings,
+    target: CaptureTarget,
+    filepath: &str,
+) -> Result<Option<CaptureRegion>, AppError> {
+    let backend = capture::backend_for(settings.capture_backend, runner.clone())?;
+    Ok(backend.capture(target, std::path::Path::new(filepath))?)
 }
 
 #[tauri::command]
-fn take_screenshot(app: tauri::AppHandle, _state: State<AppState>) -> Result<String, String> {
-    do_area_screenshot(&app)
+fn take_screenshot(app: tauri::AppHandle, _state: State<AppState>) -> Result<String, AppError> {
+    run_capture_job(&app, CaptureMode::Area)
 }
 
-fn do_fullscreen_screenshot(app: &tauri::AppHandle) -> Result<String, String> {
-    if app.get_webview_window("rename").is_some() {
-        return Err("Please finish renaming the current screenshot first".to_string());
-    }
-
-    let state = app.state::<AppState>();
-    let settings = state.settings.lock().unwrap().clone();
-    let filepath = generate_temp_screenshot_path("png");
-
-    let output = Command::new("screencapture")
-        .args(["-x", &filepath])
-        .output()
-        .map_err(|e| format!("Failed to run screencapture: {}", e))?;
-
-    if output.status.success() {
-        if std::path::Path::new(&filepath).exists() {
-            let optimized_path = optimize_screenshot(&filepath, &settings)?;
-            let (width, height) = get_image_dimensions(&optimized_path)?;
-            let extension = std::path::Path::new(&optimized_path)
-                .extension()
-                .and_then(|e| e.to_str())
-                .unwrap_or("png");
-            let final_path = generate_screenshot_path(extension, &settings, width, height);
-            std::fs::rename(&optimized_path, &final_path)
-                .map_err(|e| format!("Failed to rename screenshot: {}", e))?;
-            Ok(final_path)
-        } else {
-            Err("Screenshot cancelled".to_string())
-        }
-    } else {
-        Err("Screenshot failed".to_string())
-    }
+#[tauri::command]
+fn take_fullscreen_screenshot(app: tauri::AppHandle, _state: State<AppState>) -> Result<String, AppError> {
+    run_capture_job(&app, CaptureMode::Fullscreen)
 }
 
 #[tauri::command]
-fn take_fullscreen_screenshot(app: tauri::AppHandle, _state: State<AppState>) -> Result<String, String> {
-    do_fullscreen_screenshot(&app)
+fn take_capture(app: tauri::AppHandle, mode: CaptureMode) -> Result<String, AppError> {
+    run_capture_job(&app, mode)
 }
 
 #[tauri::command]
-fn get_finder_selection() -> Result<Vec<String>, String> {
+fn get_finder_selection(state: State<AppState>) -> Result<Vec<String>, AppError> {
+    read_finder_selection(state.runner.as_ref(), &state.permissions)
+}
+
+fn read_finder_selection(
+    runner: &dyn CommandRunner,
+    diagnostics: &PermissionDiagnostics,
+) -> Result<Vec<String>, AppError> {
     println!("[stitch] get_finder_selection called");
     let script = r#"
 tell application "Finder"
@@ -557,96 +418,258 @@ end tell
 return output
 "#;
 
-    let output = Command::new("osascript")
-        .args(["-e", script])
-        .output()
-        .map_err(|e| format!("Failed to read Finder selection: {}", e))?;
+    let output = runner
+        .output("osascript", &["-e", script])
+        .map_err(|e| AppError::io("Failed to read Finder selection", e))?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let message = stderr.trim();
+    if !output.success {
+        let message = output.stderr.trim();
         println!("[stitch] finder selection error: {}", message);
         if message.is_empty() {
-            return Err("Failed to read Finder selection".to_string());
+            return Err(AppError::Failed("Failed to read Finder selection".to_string()));
         }
-        return Err(message.to_string());
+        if permissions::is_automation_denied(message) {
+            diagnostics.record_automation("Finder", PermissionState::Denied);
+        }
+        return Err(permissions::osascript_error("Finder", message));
     }
+    diagnostics.record_automation("Finder", PermissionState::Granted);
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    println!("[stitch] finder selection raw: {}", stdout.trim());
-    let paths = stdout
-        .lines()
-        .map(|line| line.trim())
-        .filter(|line| !line.is_empty())
-        .filter(|line| std::path::Path::new(line).is_file())
-        .filter(|line| {
-            let ext = std::path::Path::new(line)
-                .extension()
-                .and_then(|e| e.to_str())
-                .unwrap_or("")
-                .to_lowercase();
-            matches!(ext.as_str(), "png" | "jpg" | "jpeg")
-        })
-        .map(|line| line.to_string())
-        .collect::<Vec<String>>();
+    println!("[stitch] finder selection raw: {}", output.stdout.trim());
+    let paths = stitch_sources::filter_stitchable(output.stdout.lines());
 
     println!("[stitch] finder selection filtered count: {}", paths.len());
     Ok(paths)
 }
 
+// Resolve stitch sources to image paths, in order and without duplicates.
+// Falls back to the `stitchSource` setting when no sources are given.
 #[tauri::command]
-fn save_stitch_temp(
+fn collect_stitch_sources(
     state: State<AppState>,
-    base64_data: String,
-    max_single_image_height: u32,
-    max_single_image_width: u32,
-) -> Result<String, String> {
-    use base64::Engine;
-    use std::io::Write;
+    sources: Option<Vec<StitchSource>>,
+) -> Result<Vec<String>, AppError> {
+    collect_sources(&state, sources)
+}
+
+fn collect_sources(state: &AppState, sources: Option<Vec<StitchSource>>) -> Result<Vec<String>, AppError> {
+    let sources = sources.unwrap_or_else(|| vec![state.settings.lock().unwrap().stitch_source.clone()]);
+    let mut paths: Vec<String> = Vec::new();
+    for source in sources {
+        source.validate().map_err(AppError::Invalid)?;
+        let found = match source {
+            StitchSource::Finder => read_finder_selection(state.runner.as_ref(), &state.permissions)?,
+            StitchSource::History { count, mode } => stitch_sources::from_history(&state.data.history, count, mode)?,
+            StitchSource::Paths { paths } => stitch_sources::filter_stitchable(paths),
+            StitchSource::Directory { path, pattern } => stitch_sources::from_directory(&path, pattern.as_deref())?,
+            StitchSource::Clipboard => {
+                let contents = stitch_sources::read_clipboard().map_err(AppError::Failed)?;
+                let mut found = contents.paths;
+                if let Some(image) = contents.image {
+                    // Pasted pixels become a temp file so the stitcher can read them like any other source
+                    let temp_path = generate_temp_screenshot_path(&state.data, "png")?;
+                    let bytes = image_pipeline::encode_png(&image)
+                        .map_err(|e| AppError::image("Failed to encode clipboard image", e))?;
+                    std::fs::write(&temp_path, bytes)
+                        .map_err(|e| AppError::io("Failed to write clipboard image", e))?;
+                    found.push(temp_path);
+                }
+                found
+            }
+        };
+        for path in found {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    println!("[stitch] collected {} source image(s)", paths.len());
+    Ok(paths)
+}
 
-    println!("[stitch] save_stitch_temp called (bytes: {})", base64_data.len());
-    let bytes = base64::engine::general_purpose::STANDARD
-        .decode(&base64_data)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+// Starts a background stitch job and returns its id. The result arrives in
+// `stitch-job-updated` events: one path, or several (`_part1`, `_part2`...) when
+// the layout's page height or byte limits split the output.
+#[tauri::command]
+fn stitch_images(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+    layout: Option<StitchLayout>,
+) -> Result<u64, AppError> {
+    println!("[stitch] stitch_images called ({} images)", paths.len());
+    if paths.len() < 2 {
+        return Err(AppError::Invalid("Need at least 2 images to stitch".to_string()));
+    }
+    stitch_jobs::spawn_job(&app, paths.len(), false, move |state, job| run_stitch(state, job, paths, layout))
+}
+
+// Sources are read from their pre-edit backups when present, like the editor does.
+fn run_stitch(
+    state: &AppState,
+    job: &JobHandle,
+    paths: Vec<String>,
+    layout: Option<StitchLayout>,
+) -> Result<Vec<String>, AppError> {
+    if paths.len() < 2 {
+        return Err(AppError::Invalid("Need at least 2 images to stitch".to_string()));
+    }
+    let settings = state.settings.lock().unwrap().clone();
+    let layout = layout.unwrap_or_else(|| settings.stitch_layout.clone());
+    let data = &state.data;
+
+    let sources = paths
+        .iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let backup_path = get_original_backup_path(path);
+            let source_path = if std::path::Path::new(&backup_path).exists() {
+                backup_path
+            } else {
+                path.clone()
+            };
+            let label = stitch::label_for(layout.labels, std::path::Path::new(path), index);
+            (std::path::PathBuf::from(source_path), label)
+        })
+        .collect();
+    let items = stitch::prepare_items(sources, &layout)?;
+
+    // Render page by page; a page over the byte budget is split in half (between
+    // grid rows for a grid) and retried
+    let mut pending: std::collections::VecDeque<std::ops::Range<usize>> =
+        stitch::paginate(&items, &layout).map_err(AppError::Invalid)?.into_iter().collect();
+    let mut pages: Vec<(String, u32, u32)> = Vec::new();
+    let discard = |pages: &[(String, u32, u32)]| {
+        for (path, _, _) in pages {
+            let _ = std::fs::remove_file(path);
+        }
+    };
+    let mut completed = 0;
+    while let Some(range) = pending.pop_front() {
+        let page = match render_stitch_page(&items, range.clone(), &layout, &settings, data, job) {
+            Ok(page) => page,
+            Err(e) => {
+                discard(&pages);
+                return Err(e);
+            }
+        };
+        let bytes = std::fs::metadata(&page.0).map(|m| m.len()).unwrap_or(0);
+        let over_budget = layout.max_page_bytes.is_some_and(|limit| bytes > limit);
+        if over_budget {
+            let halves = match stitch::split_page(&items, &layout, range.clone()) {
+                Ok(halves) => halves,
+                Err(e) => {
+                    let _ = std::fs::remove_file(&page.0);
+                    discard(&pages);
+                    return Err(AppError::Invalid(e));
+                }
+            };
+            if let Some((first, second)) = halves {
+                let _ = std::fs::remove_file(&page.0);
+                job.set_completed(completed);
+                pending.push_front(second);
+                pending.push_front(first);
+                continue;
+            }
+            println!(
+                "[stitch] images {}-{} can't be split further and exceed the page byte limit ({} bytes)",
+                range.start + 1,
+                range.end,
+                bytes
+            );
+        }
+        completed += range.len();
+        pages.push(page);
+    }
+    println!("[stitch] rendered {} page(s)", pages.len());
+    if let Err(e) = job.check_cancelled() {
+        discard(&pages);
+        return Err(e);
+    }
 
-    let temp_path = generate_temp_screenshot_path("png");
-    let mut file = std::fs::File::create(&temp_path)
-        .map_err(|e| format!("Failed to create temp stitch file: {}", e))?;
-    file.write_all(&bytes)
-        .map_err(|e| format!("Failed to write temp stitch file: {}", e))?;
+    let first = &pages[0].0;
+    let (width, height) = get_image_dimensions(first)?;
+    let extension = std::path::Path::new(first)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("jpg")
+        .to_string();
+    let context = NameContext {
+        now: Local::now(),
+        mode: "stitch".to_string(),
+        quality: settings.quality,
+        width,
+        height,
+        app: None,
+        title: None,
+    };
+    let final_paths = match generate_screenshot_paths(data, &extension, &settings, OutputDestination::Stitch, &context, pages.len()) {
+        Ok(final_paths) => final_paths,
+        Err(e) => {
+            discard(&pages);
+            return Err(e);
+        }
+    };
 
-    let settings = state.settings.lock().unwrap().clone();
-    let optimized = optimize_screenshot(&temp_path, &settings)?;
-    {
-        let mut stitched_dimensions = state.stitched_dimensions.lock().unwrap();
-        stitched_dimensions.insert(optimized.clone(), (max_single_image_width, max_single_image_height));
+    for ((page_path, max_single_width, max_single_height), final_path) in pages.iter().zip(&final_paths) {
+        if let Err(e) = output::move_file(std::path::Path::new(page_path), std::path::Path::new(final_path)) {
+            discard(&pages);
+            for path in &final_paths {
+                let _ = std::fs::remove_file(path);
+            }
+            return Err(AppError::io("Failed to move stitched image", e));
+        }
+        let (width, height) = get_image_dimensions(final_path).unwrap_or((0, 0));
+        let mut entry = HistoryEntry::new(final_path, "stitch", width, height, settings.quality);
+        entry.stitch_source_width = Some(*max_single_width);
+        entry.stitch_source_height = Some(*max_single_height);
+        if let Err(e) = history::record(&data.history, entry) {
+            println!("[history] Failed to record stitch: {}", e);
+        }
+        // The source size travels in the file's XMP so the editor can size it after a restart
+        embed_screenshot_metadata(data, final_path);
+        println!("[stitch] stitch_images saved path: {}", final_path);
     }
-    println!("[stitch] save_stitch_temp optimized path: {}", optimized);
-    Ok(optimized)
+    Ok(final_paths)
 }
 
-#[tauri::command]
-fn clear_stitch_lock(state: State<AppState>) -> Result<(), String> {
-    let mut lock = state.stitch_lock.lock().unwrap();
-    *lock = false;
-    println!("[stitch] stitch lock cleared");
-    Ok(())
+// Compose one page into an optimized temp file. Returns (path, max single width, max single height).
+fn render_stitch_page(
+    items: &[stitch::StitchItem],
+    range: std::ops::Range<usize>,
+    layout: &StitchLayout,
+    settings: &Settings,
+    data: &DataPaths,
+    job: &JobHandle,
+) -> Result<(String, u32, u32), AppError> {
+    let page = stitch::render_page(&items[range], layout, settings.resize_filter, items.len(), &mut || job.image_done())?;
+    // Encoded straight from the composed pixels, without a temporary PNG
+    let bytes = image_pipeline::optimize_image(&page.image, &optimize_options(settings))
+        .map_err(|e| AppError::image("Failed to optimize screenshot", e))?;
+    drop(page.image);
+    let temp_path = generate_temp_screenshot_path(data, "jpg")?;
+    std::fs::write(&temp_path, bytes)
+        .map_err(|e| AppError::io("Failed to write temp stitch file", e))?;
+    Ok((temp_path, page.max_single_width, page.max_single_height))
 }
 
 #[tauri::command]
-fn show_alert(title: String, message: String) -> Result<(), String> {
+fn show_alert(title: String, message: String) -> Result<(), AppError> {
     println!("{}: {}", title, message);
     Ok(())
 }
 
 #[tauri::command]
-fn rename_screenshot(old_path: String, new_name: String, state: State<AppState>) -> Result<String, String> {
+fn rename_screenshot(old_path: String, new_name: String, state: State<AppState>) -> Result<String, AppError> {
+    rename_screenshot_file(&state.data, old_path, new_name)
+}
+
+fn rename_screenshot_file(data: &DataPaths, old_path: String, new_name: String) -> Result<String, AppError> {
     use std::path::Path;
 
     let old = Path::new(&old_path);
 
     // Get the directory and extension from the old path
-    let dir = old.parent().ok_or("Invalid path")?;
+    let dir = old.parent().ok_or_else(|| AppError::Invalid(format!("Invalid path: {}", old_path)))?;
     let ext = old.extension().and_then(|e| e.to_str()).unwrap_or("jpg");
 
     // Sanitize the new name - only remove macOS forbidden characters (/ and :)
@@ -655,28 +678,39 @@ fn rename_screenshot(old_path: String, new_name: String, state: State<AppState>)
         .filter(|c| *c != '/' && *c != ':')
         .collect();
 
-    let new_path = dir.join(format!("{}.{}", sanitized.trim(), ext));
+    let stem = sanitized.trim();
+    let mut new_path = dir.join(format!("{}.{}", stem, ext));
+
+    // Renaming to the same file (or a case-only change) is not a collision
+    let same_file = new_path.exists()
+        && std::fs::canonicalize(&new_path).ok() == std::fs::canonicalize(old).ok();
+    if !same_file {
+        new_path = output::reserve_unique_path(dir, stem, ext)
+            .map_err(|e| AppError::io("Failed to rename", e))?;
+    }
 
     // Rename the file
-    std::fs::rename(&old_path, &new_path)
-        .map_err(|e| format!("Failed to rename: {}", e))?;
+    if let Err(e) = std::fs::rename(&old_path, &new_path) {
+        if !same_file {
+            let _ = std::fs::remove_file(&new_path);
+        }
+        return Err(AppError::io("Failed to rename", e));
+    }
 
     let new_path_str = new_path.to_string_lossy().to_string();
-    {
-        let mut stitched_dimensions = state.stitched_dimensions.lock().unwrap();
-        if let Some(dimensions) = stitched_dimensions.remove(&old_path) {
-            stitched_dimensions.insert(new_path_str.clone(), dimensions);
-        }
+    if let Err(e) = history::rename_path(&data.history, &old_path, &new_path_str) {
+        println!("[history] Failed to update renamed screenshot: {}", e);
     }
+    embed_screenshot_metadata(data, &new_path_str);
 
     Ok(new_path_str)
 }
 
 #[tauri::command]
-fn read_image_base64(filepath: String) -> Result<String, String> {
+fn read_image_base64(filepath: String) -> Result<String, AppError> {
     use base64::Engine;
     let bytes = std::fs::read(&filepath)
-        .map_err(|e| format!("Failed to read file: {}", e))?;
+        .map_err(|e| AppError::io("Failed to read file", e))?;
     let base64_data = base64::engine::general_purpose::STANDARD.encode(&bytes);
     
     // Determine MIME type from file extension
@@ -691,8 +725,7 @@ fn read_image_base64(filepath: String) -> Result<String, String> {
 }
 
 fn get_backup_cache_dir() -> std::path::PathBuf {
-    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-    std::path::PathBuf::from(format!("{}/Library/Caches/screenshotapp/backups", home))
+    output::cache_dir().join("backups")
 }
 
 fn compute_path_hash(filepath: &str) -> String {
@@ -721,21 +754,21 @@ fn get_original_backup_path(filepath: &str) -> String {
 }
 
 #[tauri::command]
-fn ensure_original_backup(filepath: String) -> Result<bool, String> {
+fn ensure_original_backup(filepath: String) -> Result<bool, AppError> {
     let backup_path = get_original_backup_path(&filepath);
     if std::path::Path::new(&backup_path).exists() {
         return Ok(false);
     }
     let cache_dir = get_backup_cache_dir();
     std::fs::create_dir_all(&cache_dir)
-        .map_err(|e| format!("Failed to create cache directory: {}", e))?;
+        .map_err(|e| AppError::io("Failed to create cache directory", e))?;
     std::fs::copy(&filepath, &backup_path)
-        .map_err(|e| format!("Failed to create backup: {}", e))?;
+        .map_err(|e| AppError::io("Failed to create backup", e))?;
     Ok(true)
 }
 
 #[tauri::command]
-fn read_original_image_base64(filepath: String) -> Result<String, String> {
+fn read_original_image_base64(filepath: String) -> Result<String, AppError> {
     use base64::Engine;
     let backup_path = get_original_backup_path(&filepath);
     let source_path = if std::path::Path::new(&backup_path).exists() {
@@ -744,7 +777,7 @@ fn read_original_image_base64(filepath: String) -> Result<String, String> {
         filepath.clone()
     };
     let bytes = std::fs::read(&source_path)
-        .map_err(|e| format!("Failed to read file: {}", e))?;
+        .map_err(|e| AppError::io("Failed to read file", e))?;
     let base64_data = base64::engine::general_purpose::STANDARD.encode(&bytes);
     let mime_type = if filepath.to_lowercase().ends_with(".jpg") 
         || filepath.to_lowercase().ends_with(".jpeg") {
@@ -756,37 +789,115 @@ fn read_original_image_base64(filepath: String) -> Result<String, String> {
 }
 
 #[tauri::command]
-fn delete_original_backup(filepath: String) -> Result<(), String> {
+fn delete_original_backup(filepath: String) -> Result<(), AppError> {
     let backup_path = get_original_backup_path(&filepath);
     if std::path::Path::new(&backup_path).exists() {
         std::fs::remove_file(&backup_path)
-            .map_err(|e| format!("Failed to delete backup: {}", e))?;
+            .map_err(|e| AppError::io("Failed to delete backup", e))?;
     }
     Ok(())
 }
 
 #[tauri::command]
-fn save_edited_screenshot(filepath: String, base64_data: String) -> Result<String, String> {
+fn save_edited_screenshot(filepath: String, base64_data: String, state: State<AppState>) -> Result<String, AppError> {
     use base64::Engine;
     use std::io::Write;
 
     // Decode base64
     let bytes = base64::engine::general_purpose::STANDARD.decode(&base64_data)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+        .map_err(|e| AppError::Decode(format!("Failed to decode base64: {}", e)))?;
+    let previous_metadata = metadata::read_metadata(std::path::Path::new(&filepath)).ok().flatten();
 
     // Write to file (overwrite original)
     let mut file = std::fs::File::create(&filepath)
-        .map_err(|e| format!("Failed to create file: {}", e))?;
+        .map_err(|e| AppError::io("Failed to create file", e))?;
     file.write_all(&bytes)
-        .map_err(|e| format!("Failed to write file: {}", e))?;
+        .map_err(|e| AppError::io("Failed to write file", e))?;
+
+    let data = &state.data;
+    if let Err(e) = history::mark_edited(&data.history, &filepath, None) {
+        println!("[history] Failed to update edited screenshot: {}", e);
+    }
+    // The webview's encoder drops all metadata
+    if history::find_by_path(&data.history, &filepath).is_some() {
+        embed_screenshot_metadata(data, &filepath);
+    } else if let Some(previous) = previous_metadata {
+        // Not in the index (e.g. renamed outside the app): carry the old packet over
+        if let Err(e) = metadata::write_metadata(std::path::Path::new(&filepath), &previous) {
+            println!("[metadata] Failed to restore metadata in {}: {}", filepath, e);
+        }
+    }
 
     Ok(filepath)
 }
 
+// Writes the history record (note, capture time, mode, source app, original size)
+// into the file as XMP. Best effort: files without a history entry are left alone.
+fn embed_screenshot_metadata(data: &DataPaths, filepath: &str) {
+    let Some(entry) = history::find_by_path(&data.history, filepath) else {
+        return;
+    };
+    if let Err(e) = metadata::write_metadata(std::path::Path::new(filepath), &entry.metadata()) {
+        println!("[metadata] Failed to embed metadata in {}: {}", filepath, e);
+        return;
+    }
+    let _ = history::refresh_size(&data.history, filepath);
+}
+
+#[tauri::command]
+fn read_screenshot_metadata(filepath: String) -> Result<Option<ScreenshotMetadata>, AppError> {
+    metadata::read_metadata(std::path::Path::new(&filepath)).map_err(AppError::Decode)
+}
+
+// Burns the note into the pre-edit original (when a backup exists), so changing
+// the note replaces the previous one instead of stacking a second bar.
+#[tauri::command]
+fn burn_note(
+    filepath: String,
+    note: String,
+    options: Option<BurnNoteOptions>,
+    state: State<AppState>,
+) -> Result<String, AppError> {
+    let note = note.trim();
+    if note.is_empty() {
+        return Err(AppError::Invalid("Note is empty".to_string()));
+    }
+    let settings = state.settings.lock().unwrap().clone();
+    let options = options.unwrap_or_default();
+
+    let final_note = if settings.note_prefix_enabled && !settings.note_prefix.is_empty() {
+        format!("{}{}", settings.note_prefix, note)
+    } else {
+        note.to_string()
+    };
+
+    let backup_path = get_original_backup_path(&filepath);
+    let source = if std::path::Path::new(&backup_path).exists() {
+        backup_path
+    } else {
+        filepath.clone()
+    };
+
+    note_burn::burn_note_file(
+        std::path::Path::new(&source),
+        std::path::Path::new(&filepath),
+        &final_note,
+        options.placement.unwrap_or(settings.note_placement),
+        options.font_size,
+        settings.quality,
+    )?;
+
+    if let Err(e) = history::mark_edited(&state.data.history, &filepath, Some(note.to_string())) {
+        println!("[history] Failed to update noted screenshot: {}", e);
+    }
+    embed_screenshot_metadata(&state.data, &filepath);
+    Ok(filepath)
+}
+
 // Rename popup: 410x215 fixed size
 // Compact dimensions for filename input, note field, and shortcuts bar
 #[tauri::command]
-fn open_rename_popup(app: tauri::AppHandle, filepath: String) -> Result<(), String> {
+fn open_rename_popup(app: tauri::AppHandle, filepath: String) -> Result<(), AppError> {
     // URL encode the filepath for the query param
     let encoded_path = urlencoding::encode(&filepath);
     let url = format!("/rename.html?path={}", encoded_path);
@@ -802,7 +913,7 @@ fn open_rename_popup(app: tauri::AppHandle, filepath: String) -> Result<(), Stri
         .decorations(false)
         .transparent(true)
         .build()
-        .map_err(|e| format!("Failed to open rename window: {}", e))?;
+        .map_err(|e| AppError::window("rename", e))?;
 
     Ok(())
 }
@@ -815,7 +926,7 @@ fn close_rename_popup(app: tauri::AppHandle) {
 }
 
 #[tauri::command]
-fn open_note_popup(app: tauri::AppHandle, filepath: String, note: Option<String>, burned_note: Option<String>) -> Result<(), String> {
+fn open_note_popup(app: tauri::AppHandle, filepath: String, note: Option<String>, burned_note: Option<String>) -> Result<(), AppError> {
     if let Some(rename_window) = app.get_webview_window("rename") {
         let _ = rename_window.close();
     }
@@ -835,7 +946,7 @@ fn open_note_popup(app: tauri::AppHandle, filepath: String, note: Option<String>
         .center()
         .focused(true)
         .build()
-        .map_err(|e| format!("Failed to open note window: {}", e))?;
+        .map_err(|e| AppError::window("note", e))?;
 
     Ok(())
 }
@@ -848,7 +959,7 @@ fn close_note_popup(app: tauri::AppHandle) {
 }
 
 #[tauri::command]
-fn close_note_and_open_rename(app: tauri::AppHandle, filepath: String, note: Option<String>, burned_note: Option<String>) -> Result<(), String> {
+fn close_note_and_open_rename(app: tauri::AppHandle, filepath: String, note: Option<String>, burned_note: Option<String>) -> Result<(), AppError> {
     if let Some(note_window) = app.get_webview_window("note") {
         let _ = note_window.close();
     }
@@ -870,7 +981,7 @@ fn close_note_and_open_rename(app: tauri::AppHandle, filepath: String, note: Opt
         .decorations(false)
         .transparent(true)
         .build()
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::window("rename", e))?;
 
     Ok(())
 }
@@ -881,7 +992,7 @@ fn open_shortcut_config(
     target: String,
     current_shortcut: String,
     other_shortcut: String,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     if let Some(window) = app.get_webview_window("shortcut-config") {
         let _ = window.close();
     }
@@ -895,7 +1006,7 @@ fn open_shortcut_config(
 
     WebviewWindowBuilder::new(&app, "shortcut-config", tauri::WebviewUrl::App(url.into()))
         .title("Configure Shortcut")
-        .inner_size(260.0, 180.0)
+        .inner_size(260.0, 210.0)
         .resizable(false)
         .always_on_top(true)
         .center()
@@ -903,7 +1014,7 @@ fn open_shortcut_config(
         .decorations(false)
         .transparent(true)
         .build()
-        .map_err(|e| format!("Failed to open shortcut config: {}", e))?;
+        .map_err(|e| AppError::window("shortcut-config", e))?;
 
     Ok(())
 }
@@ -916,13 +1027,7 @@ fn close_shortcut_config(app: tauri::AppHandle) {
 }
 
 #[tauri::command]
-fn delete_screenshot(app: tauri::AppHandle, filepath: String) -> Result<(), String> {
-    {
-        let state = app.state::<AppState>();
-        let mut stitched_dimensions = state.stitched_dimensions.lock().unwrap();
-        stitched_dimensions.remove(&filepath);
-    }
-
+fn delete_screenshot(app: tauri::AppHandle, filepath: String, state: State<AppState>) -> Result<(), AppError> {
     // Delete the backup if it exists
     let backup_path = get_original_backup_path(&filepath);
     if std::path::Path::new(&backup_path).exists() {
@@ -931,7 +1036,10 @@ fn delete_screenshot(app: tauri::AppHandle, filepath: String) -> Result<(), Stri
 
     // Delete the file
     std::fs::remove_file(&filepath)
-        .map_err(|e| format!("Failed to delete: {}", e))?;
+        .map_err(|e| AppError::io("Failed to delete", e))?;
+    if let Err(e) = history::remove_path(&state.data.history, &filepath) {
+        println!("[history] Failed to remove deleted screenshot: {}", e);
+    }
 
     if let Some(window) = app.get_webview_window("rename") {
         let _ = window.close();
@@ -948,6 +1056,73 @@ fn delete_screenshot(app: tauri::AppHandle, filepath: String) -> Result<(), Stri
     Ok(())
 }
 
+#[tauri::command]
+fn list_history(limit: Option<usize>, state: State<AppState>) -> Result<Vec<HistoryItem>, AppError> {
+    history::search(&state.data.history, &HistoryQuery {
+        limit,
+        ..HistoryQuery::default()
+    })
+}
+
+#[tauri::command]
+fn search_history(query: HistoryQuery, state: State<AppState>) -> Result<Vec<HistoryItem>, AppError> {
+    history::search(&state.data.history, &query)
+}
+
+// Drops the entry from history; the file itself is left alone.
+#[tauri::command]
+fn forget(id: String, state: State<AppState>) -> Result<(), AppError> {
+    history::forget(&state.data.history, &id).map_err(AppError::Failed)
+}
+
+fn open_history_window(app: &tauri::AppHandle) -> Result<(), AppError> {
+    if let Some(window) = app.get_webview_window("history") {
+        let _ = window.show();
+        let _ = window.set_focus();
+        return Ok(());
+    }
+
+    WebviewWindowBuilder::new(app, "history", tauri::WebviewUrl::App("/history.html".into()))
+        .title("History")
+        .inner_size(520.0, 480.0)
+        .center()
+        .focused(true)
+        .build()
+        .map_err(|e| AppError::window("history", e))?;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn reveal(state: State<AppState>, path: String) -> Result<(), AppError> {
+    if !std::path::Path::new(&path).exists() {
+        return Err(AppError::NotFound(format!("File no longer exists: {}", path)));
+    }
+    reveal_in_file_manager(state.runner.as_ref(), &path).map_err(AppError::Failed)
+}
+
+#[cfg(target_os = "macos")]
+fn reveal_in_file_manager(runner: &dyn CommandRunner, path: &str) -> Result<(), String> {
+    let output = runner
+        .output("open", &["-R", path])
+        .map_err(|e| format!("Failed to reveal file: {}", e))?;
+    if !output.success {
+        return Err("Failed to reveal file in Finder".to_string());
+    }
+    Ok(())
+}
+
+// No portable "select this file" on Linux; open the containing folder instead.
+#[cfg(not(target_os = "macos"))]
+fn reveal_in_file_manager(runner: &dyn CommandRunner, path: &str) -> Result<(), String> {
+    let dir = std::path::Path::new(path).parent().ok_or("Invalid path")?;
+    let dir = dir.to_string_lossy();
+    runner
+        .spawn("xdg-open", &[dir.as_ref()])
+        .map_err(|e| format!("Failed to reveal file: {}", e))?;
+    Ok(())
+}
+
 // Calculate padding for the editor based on image dimensions and whether it was resized
 fn calculate_editor_padding(img_width: u32, img_height: u32, was_resized: bool) -> f64 {
     const TOOLBAR_HEIGHT: f64 = 72.0;
@@ -971,7 +1146,7 @@ fn calculate_editor_padding(img_width: u32, img_height: u32, was_resized: bool)
 // Canvas scales within window (see editor.html resizeCanvas)
 // Window size calculated to fit image with toolbar and padding
 #[tauri::command]
-fn open_editor_window(app: tauri::AppHandle, filepath: String, note: Option<String>, burned_note: Option<String>, state: State<AppState>) -> Result<(), String> {
+fn open_editor_window(app: tauri::AppHandle, filepath: String, note: Option<String>, burned_note: Option<String>, state: State<AppState>) -> Result<(), AppError> {
     if let Some(rename_window) = app.get_webview_window("rename") {
         let _ = rename_window.close();
     }
@@ -980,11 +1155,12 @@ fn open_editor_window(app: tauri::AppHandle, filepath: String, note: Option<Stri
     }
 
     // Get image dimensions and calculate appropriate window size
-    let (img_width, img_height) = get_image_dimensions(&filepath).unwrap_or((800, 600));
-    let reference_dimensions = {
-        let stitched_dimensions = state.stitched_dimensions.lock().unwrap();
-        stitched_dimensions.get(&filepath).copied()
-    };
+    let (img_width, img_height) = get_image_dimensions(&filepath)?;
+    // Stitched images are sized like their largest source. The file's own metadata
+    // survives renames outside the app; the history index covers files without it.
+    let reference_dimensions = metadata::stitch_source_size(std::path::Path::new(&filepath)).or_else(|| {
+        history::find_by_path(&state.data.history, &filepath).and_then(|entry| Some((entry.stitch_source_width?, entry.stitch_source_height?)))
+    });
     let (reference_width, reference_height) = reference_dimensions.unwrap_or((img_width, img_height));
     
     // Check if image was resized down by optimize_screenshot
@@ -1010,13 +1186,13 @@ fn open_editor_window(app: tauri::AppHandle, filepath: String, note: Option<Stri
         .resizable(true)
         .center()
         .build()
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::window("editor", e))?;
 
     Ok(())
 }
 
 #[tauri::command]
-fn close_editor_and_open_rename(app: tauri::AppHandle, filepath: String, note: Option<String>, burned_note: Option<String>) -> Result<(), String> {
+fn close_editor_and_open_rename(app: tauri::AppHandle, filepath: String, note: Option<String>, burned_note: Option<String>) -> Result<(), AppError> {
     // Close editor window first
     if let Some(editor_window) = app.get_webview_window("editor") {
         let _ = editor_window.close();
@@ -1040,7 +1216,7 @@ fn close_editor_and_open_rename(app: tauri::AppHandle, filepath: String, note: O
         .decorations(false)
         .transparent(true)
         .build()
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::window("rename", e))?;
 
     Ok(())
 }
@@ -1053,7 +1229,7 @@ fn close_editor_window(app: tauri::AppHandle) {
 }
 
 #[tauri::command]
-fn close_editor_and_open_note(app: tauri::AppHandle, filepath: String, note: Option<String>, burned_note: Option<String>) -> Result<(), String> {
+fn close_editor_and_open_note(app: tauri::AppHandle, filepath: String, note: Option<String>, burned_note: Option<String>) -> Result<(), AppError> {
     if let Some(editor_window) = app.get_webview_window("editor") {
         let _ = editor_window.close();
     }
@@ -1073,396 +1249,69 @@ fn close_editor_and_open_note(app: tauri::AppHandle, filepath: String, note: Opt
         .center()
         .focused(true)
         .build()
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::window("note", e))?;
 
     Ok(())
 }
 
-fn get_clipboard_cache_dir() -> std::path::PathBuf {
-    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-    std::path::PathBuf::from(home)
-        .join("Library")
-        .join("Caches")
-        .join("screenshotapp")
-        .join("clipboard")
-}
-
-fn generate_clipboard_cache_path(extension: &str) -> Result<String, String> {
-    let sanitized_extension = extension.trim_start_matches('.');
-    if sanitized_extension.is_empty() {
-        return Err("Missing clipboard cache file extension".to_string());
-    }
-
-    let millis = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| format!("Failed to read system time: {}", e))?
-        .as_millis();
-    let counter = CLIPBOARD_CACHE_COUNTER.fetch_add(1, Ordering::Relaxed);
-    let filename = format!("clipboard-{}-{}.{}", millis, counter, sanitized_extension);
-
-    Ok(get_clipboard_cache_dir()
-        .join(filename)
-        .to_string_lossy()
-        .to_string())
-}
-
-fn write_clipboard_cache_file(bytes: &[u8], extension: &str) -> Result<String, String> {
-    let cache_dir = get_clipboard_cache_dir();
-    std::fs::create_dir_all(&cache_dir)
-        .map_err(|e| format!("Failed to create clipboard cache dir: {}", e))?;
-
-    let cached_path = generate_clipboard_cache_path(extension)?;
-    std::fs::write(&cached_path, bytes)
-        .map_err(|e| format!("Failed to write clipboard cache file: {}", e))?;
-
-    Ok(cached_path)
-}
-
-fn cleanup_clipboard_cache() {
-    let cache_dir = get_clipboard_cache_dir();
-    if cache_dir.exists() {
-        if let Ok(entries) = std::fs::read_dir(&cache_dir) {
-            for entry in entries.flatten() {
-                let _ = std::fs::remove_file(entry.path());
-            }
-        }
-    }
-}
-
-#[cfg(target_os = "macos")]
-fn write_file_url_to_clipboard(filepath: &str) -> Result<(), String> {
-    use objc2::rc::{autoreleasepool, Retained};
-    use objc2::runtime::ProtocolObject;
-    use objc2_app_kit::{NSPasteboard, NSPasteboardWriting};
-    use objc2_foundation::{NSArray, NSString, NSURL};
-
-    autoreleasepool(|_| {
-        let path = NSString::from_str(filepath);
-        let url = unsafe { NSURL::fileURLWithPath(&path) };
-        let url_object: Retained<ProtocolObject<dyn NSPasteboardWriting>> =
-            ProtocolObject::from_retained(url);
-        let objects = NSArray::from_vec(vec![url_object]);
-
-        let pasteboard = unsafe { NSPasteboard::generalPasteboard() };
-        unsafe {
-            pasteboard.clearContents();
-        }
-
-        let success = unsafe { pasteboard.writeObjects(&objects) };
-        if success {
-            Ok(())
-        } else {
-            Err("Failed to write file URL to clipboard".to_string())
-        }
-    })
-}
-
-#[cfg(not(target_os = "macos"))]
-fn write_file_url_to_clipboard(_filepath: &str) -> Result<(), String> {
-    Err("File URL clipboard copy is only supported on macOS".to_string())
+// Puts the image bytes, a file URL to `url_path` and the note on the clipboard.
+fn copy_to_clipboard(source: &str, url_path: &str) -> Result<(), String> {
+    let payload = ClipboardPayload::from_file(std::path::Path::new(source), std::path::Path::new(url_path))?;
+    clipboard::write(payload)
 }
 
 #[tauri::command]
-fn copy_image_to_clipboard(base64_data: String) -> Result<(), String> {
+fn copy_image_to_clipboard(state: tauri::State<AppState>, base64_data: String) -> Result<(), AppError> {
     use base64::Engine;
 
     let image_bytes = base64::engine::general_purpose::STANDARD
         .decode(&base64_data)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
-
-    let cached_path = write_clipboard_cache_file(&image_bytes, "jpg")?;
-    write_file_url_to_clipboard(&cached_path)?;
+        .map_err(|e| AppError::Decode(format!("Failed to decode base64: {}", e)))?;
+    let extension = match image_probe::detect_format(&image_bytes) {
+        Some(image_probe::ImageFormat::Png) => "png",
+        _ => "jpg",
+    };
 
+    let cached_path = state.clipboard_cache.write_bytes(&image_bytes, extension).map_err(AppError::Failed)?;
+    copy_to_clipboard(&cached_path, &cached_path).map_err(AppError::Failed)?;
+    state.clipboard_cache.set_current(&cached_path);
     Ok(())
 }
 
 #[tauri::command]
-fn copy_file_to_clipboard(filepath: String) -> Result<(), String> {
-    write_file_url_to_clipboard(&filepath)
+fn copy_file_to_clipboard(filepath: String) -> Result<(), AppError> {
+    copy_to_clipboard(&filepath, &filepath).map_err(AppError::Failed)
 }
 
 #[tauri::command]
-fn copy_file_to_clipboard_cached(filepath: String) -> Result<(), String> {
+fn copy_file_to_clipboard_cached(state: tauri::State<AppState>, filepath: String) -> Result<(), AppError> {
     let extension = std::path::Path::new(&filepath)
         .extension()
         .and_then(|ext| ext.to_str())
-        .ok_or("Failed to determine file extension for clipboard cache".to_string())?;
-
-    let cache_dir = get_clipboard_cache_dir();
-    std::fs::create_dir_all(&cache_dir)
-        .map_err(|e| format!("Failed to create clipboard cache dir: {}", e))?;
-
-    let cached_path = generate_clipboard_cache_path(extension)?;
-    std::fs::copy(&filepath, &cached_path)
-        .map_err(|e| format!("Failed to cache file for clipboard: {}", e))?;
-
-    write_file_url_to_clipboard(&cached_path)
-}
-
-#[derive(Clone, Copy)]
-struct ShortcutParts {
-    modifiers: Modifiers,
-    key: Code,
-}
+        .ok_or_else(|| AppError::Invalid("Failed to determine file extension for clipboard cache".to_string()))?;
 
-fn parse_shortcut(shortcut_str: &str) -> Result<Shortcut, String> {
-    let parts = parse_shortcut_parts(shortcut_str)?;
-    Ok(Shortcut::new(Some(parts.modifiers), parts.key))
-}
-
-fn normalize_and_parse(shortcut_str: &str) -> Result<(String, Shortcut), String> {
-    let parts = parse_shortcut_parts(shortcut_str)?;
-    let normalized = shortcut_parts_to_string(&parts)?;
-    Ok((normalized, Shortcut::new(Some(parts.modifiers), parts.key)))
-}
-
-fn normalize_shortcut_string(shortcut_str: &str) -> Result<String, String> {
-    let parts = parse_shortcut_parts(shortcut_str)?;
-    shortcut_parts_to_string(&parts)
-}
-
-fn parse_shortcut_parts(shortcut_str: &str) -> Result<ShortcutParts, String> {
-    let parts: Vec<&str> = shortcut_str.split('+').collect();
-    if parts.len() < 2 {
-        return Err("Shortcut must have at least one modifier and one key".to_string());
-    }
-
-    let mut modifiers = Modifiers::empty();
-    let mut key_code: Option<Code> = None;
-
-    for part in parts {
-        let token = part.trim();
-        if token.is_empty() {
-            return Err("Shortcut contains an empty token".to_string());
-        }
-
-        match token.to_lowercase().as_str() {
-            "cmd" | "command" | "super" | "meta" => modifiers |= Modifiers::SUPER,
-            "shift" => modifiers |= Modifiers::SHIFT,
-            "alt" | "option" => modifiers |= Modifiers::ALT,
-            "ctrl" | "control" => modifiers |= Modifiers::CONTROL,
-            _ => {
-                if key_code.is_some() {
-                    return Err("Shortcut must have only one main key".to_string());
-                }
-                key_code = Some(string_to_code(token)?);
-            }
-        }
-    }
-
-    let code = key_code.ok_or("No key specified in shortcut")?;
-    if modifiers.is_empty() {
-        return Err("At least one modifier required".to_string());
-    }
-
-    Ok(ShortcutParts { modifiers, key: code })
-}
-
-fn string_to_code(s: &str) -> Result<Code, String> {
-    match s.to_lowercase().as_str() {
-        "0" | "digit0" => Ok(Code::Digit0),
-        "1" | "digit1" => Ok(Code::Digit1),
-        "2" | "digit2" => Ok(Code::Digit2),
-        "3" | "digit3" => Ok(Code::Digit3),
-        "4" | "digit4" => Ok(Code::Digit4),
-        "5" | "digit5" => Ok(Code::Digit5),
-        "6" | "digit6" => Ok(Code::Digit6),
-        "7" | "digit7" => Ok(Code::Digit7),
-        "8" | "digit8" => Ok(Code::Digit8),
-        "9" | "digit9" => Ok(Code::Digit9),
-        "a" | "keya" => Ok(Code::KeyA),
-        "b" | "keyb" => Ok(Code::KeyB),
-        "c" | "keyc" => Ok(Code::KeyC),
-        "d" | "keyd" => Ok(Code::KeyD),
-        "e" | "keye" => Ok(Code::KeyE),
-        "f" | "keyf" => Ok(Code::KeyF),
-        "g" | "keyg" => Ok(Code::KeyG),
-        "h" | "keyh" => Ok(Code::KeyH),
-        "i" | "keyi" => Ok(Code::KeyI),
-        "j" | "keyj" => Ok(Code::KeyJ),
-        "k" | "keyk" => Ok(Code::KeyK),
-        "l" | "keyl" => Ok(Code::KeyL),
-        "m" | "keym" => Ok(Code::KeyM),
-        "n" | "keyn" => Ok(Code::KeyN),
-        "o" | "keyo" => Ok(Code::KeyO),
-        "p" | "keyp" => Ok(Code::KeyP),
-        "q" | "keyq" => Ok(Code::KeyQ),
-        "r" | "keyr" => Ok(Code::KeyR),
-        "s" | "keys" => Ok(Code::KeyS),
-        "t" | "keyt" => Ok(Code::KeyT),
-        "u" | "keyu" => Ok(Code::KeyU),
-        "v" | "keyv" => Ok(Code::KeyV),
-        "w" | "keyw" => Ok(Code::KeyW),
-        "x" | "keyx" => Ok(Code::KeyX),
-        "y" | "keyy" => Ok(Code::KeyY),
-        "z" | "keyz" => Ok(Code::KeyZ),
-        "f1" => Ok(Code::F1),
-        "f2" => Ok(Code::F2),
-        "f3" => Ok(Code::F3),
-        "f4" => Ok(Code::F4),
-        "f5" => Ok(Code::F5),
-        "f6" => Ok(Code::F6),
-        "f7" => Ok(Code::F7),
-        "f8" => Ok(Code::F8),
-        "f9" => Ok(Code::F9),
-        "f10" => Ok(Code::F10),
-        "f11" => Ok(Code::F11),
-        "f12" => Ok(Code::F12),
-        "space" => Ok(Code::Space),
-        "enter" => Ok(Code::Enter),
-        "tab" => Ok(Code::Tab),
-        "escape" | "esc" => Ok(Code::Escape),
-        "backspace" => Ok(Code::Backspace),
-        "minus" | "-" => Ok(Code::Minus),
-        "equal" | "equals" | "=" => Ok(Code::Equal),
-        "bracketleft" | "lbracket" | "[" => Ok(Code::BracketLeft),
-        "bracketright" | "rbracket" | "]" => Ok(Code::BracketRight),
-        "semicolon" | ";" => Ok(Code::Semicolon),
-        "quote" | "'" => Ok(Code::Quote),
-        "comma" | "," => Ok(Code::Comma),
-        "period" | "." => Ok(Code::Period),
-        "slash" | "/" => Ok(Code::Slash),
-        "backslash" | "\\" => Ok(Code::Backslash),
-        "intlbackslash" => Ok(Code::IntlBackslash),
-        "backquote" | "grave" | "`" => Ok(Code::Backquote),
-        _ => Err(format!("Unknown key: {}", s)),
-    }
-}
-
-fn code_to_string(code: Code) -> Result<String, String> {
-    match code {
-        Code::Digit0 => Ok("0".to_string()),
-        Code::Digit1 => Ok("1".to_string()),
-        Code::Digit2 => Ok("2".to_string()),
-        Code::Digit3 => Ok("3".to_string()),
-        Code::Digit4 => Ok("4".to_string()),
-        Code::Digit5 => Ok("5".to_string()),
-        Code::Digit6 => Ok("6".to_string()),
-        Code::Digit7 => Ok("7".to_string()),
-        Code::Digit8 => Ok("8".to_string()),
-        Code::Digit9 => Ok("9".to_string()),
-        Code::KeyA => Ok("A".to_string()),
-        Code::KeyB => Ok("B".to_string()),
-        Code::KeyC => Ok("C".to_string()),
-        Code::KeyD => Ok("D".to_string()),
-        Code::KeyE => Ok("E".to_string()),
-        Code::KeyF => Ok("F".to_string()),
-        Code::KeyG => Ok("G".to_string()),
-        Code::KeyH => Ok("H".to_string()),
-        Code::KeyI => Ok("I".to_string()),
-        Code::KeyJ => Ok("J".to_string()),
-        Code::KeyK => Ok("K".to_string()),
-        Code::KeyL => Ok("L".to_string()),
-        Code::KeyM => Ok("M".to_string()),
-        Code::KeyN => Ok("N".to_string()),
-        Code::KeyO => Ok("O".to_string()),
-        Code::KeyP => Ok("P".to_string()),
-        Code::KeyQ => Ok("Q".to_string()),
-        Code::KeyR => Ok("R".to_string()),
-        Code::KeyS => Ok("S".to_string()),
-        Code::KeyT => Ok("T".to_string()),
-        Code::KeyU => Ok("U".to_string()),
-        Code::KeyV => Ok("V".to_string()),
-        Code::KeyW => Ok("W".to_string()),
-        Code::KeyX => Ok("X".to_string()),
-        Code::KeyY => Ok("Y".to_string()),
-        Code::KeyZ => Ok("Z".to_string()),
-        Code::F1 => Ok("F1".to_string()),
-        Code::F2 => Ok("F2".to_string()),
-        Code::F3 => Ok("F3".to_string()),
-        Code::F4 => Ok("F4".to_string()),
-        Code::F5 => Ok("F5".to_string()),
-        Code::F6 => Ok("F6".to_string()),
-        Code::F7 => Ok("F7".to_string()),
-        Code::F8 => Ok("F8".to_string()),
-        Code::F9 => Ok("F9".to_string()),
-        Code::F10 => Ok("F10".to_string()),
-        Code::F11 => Ok("F11".to_string()),
-        Code::F12 => Ok("F12".to_string()),
-        Code::Space => Ok("Space".to_string()),
-        Code::Enter => Ok("Enter".to_string()),
-        Code::Tab => Ok("Tab".to_string()),
-        Code::Escape => Ok("Escape".to_string()),
-        Code::Backspace => Ok("Backspace".to_string()),
-        Code::Minus => Ok("-".to_string()),
-        Code::Equal => Ok("=".to_string()),
-        Code::BracketLeft => Ok("[".to_string()),
-        Code::BracketRight => Ok("]".to_string()),
-        Code::Semicolon => Ok(";".to_string()),
-        Code::Quote => Ok("'".to_string()),
-        Code::Comma => Ok(",".to_string()),
-        Code::Period => Ok(".".to_string()),
-        Code::Slash => Ok("/".to_string()),
-        Code::Backslash => Ok("\\".to_string()),
-        Code::IntlBackslash => Ok("\\".to_string()),
-        Code::Backquote => Ok("`".to_string()),
-        _ => Err(format!("Unsupported key: {:?}", code)),
-    }
-}
-
-fn shortcut_parts_to_string(parts: &ShortcutParts) -> Result<String, String> {
-    let mut tokens = Vec::new();
-
-    if parts.modifiers.contains(Modifiers::SUPER) {
-        tokens.push("Cmd".to_string());
-    }
-    if parts.modifiers.contains(Modifiers::SHIFT) {
-        tokens.push("Shift".to_string());
-    }
-    if parts.modifiers.contains(Modifiers::ALT) {
-        tokens.push("Alt".to_string());
-    }
-    if parts.modifiers.contains(Modifiers::CONTROL) {
-        tokens.push("Ctrl".to_string());
-    }
-
-    tokens.push(code_to_string(parts.key)?);
-    Ok(tokens.join("+"))
-}
-
-fn shortcut_to_display(shortcut_str: &str) -> String {
-    let normalized = normalize_shortcut_string(shortcut_str).unwrap_or_else(|_| shortcut_str.to_string());
-    normalized
-        .replace("Cmd", "⌘")
-        .replace("Shift", "⇧")
-        .replace("Alt", "⌥")
-        .replace("Ctrl", "⌃")
-        .replace("+", "")
+    let cached_path = state.clipboard_cache.copy_file(&filepath, extension).map_err(AppError::Failed)?;
+    copy_to_clipboard(&cached_path, &cached_path).map_err(AppError::Failed)?;
+    state.clipboard_cache.set_current(&cached_path);
+    Ok(())
 }
 
 fn build_tray_menu<R: tauri::Runtime>(
     app: &tauri::AppHandle<R>,
     settings: &Settings,
 ) -> tauri::Result<Menu<R>> {
-    let full_display = shortcut_to_display(&settings.fullscreen_shortcut);
-    let area_display = shortcut_to_display(&settings.area_shortcut);
-    let stitch_display = shortcut_to_display(&settings.stitch_shortcut);
-
-    let screenshot_i = MenuItem::with_id(
-        app,
-        "screenshot",
-        format!("Screenshot Area ({})", area_display),
-        true,
-        None::<&str>,
-    )?;
-    let stitch_i = MenuItem::with_id(
-        app,
-        "stitch",
-        format!("Stitch Images ({})", stitch_display),
-        true,
-        None::<&str>,
-    )?;
-    let fullscreen_i = MenuItem::with_id(
-        app,
-        "fullscreen",
-        format!("Screenshot Full ({})", full_display),
-        true,
-        None::<&str>,
-    )?;
-    let show_i = MenuItem::with_id(app, "show", "Show App", true, None::<&str>)?;
-    let quit_i = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let mut items = Vec::new();
+    for action in ShortcutAction::ALL {
+        let label = match shortcuts::display_for(&settings.shortcuts, action) {
+            Some(display) => format!("{} ({})", action.title(), display),
+            None => action.title().to_string(),
+        };
+        items.push(MenuItem::with_id(app, action.id(), label, true, None::<&str>)?);
+    }
+    items.push(MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?);
 
-    Menu::with_items(app, &[&fullscreen_i, &screenshot_i, &stitch_i, &show_i, &quit_i])
+    let refs: Vec<&dyn IsMenuItem<R>> = items.iter().map(|item| item as &dyn IsMenuItem<R>).collect();
+    Menu::with_items(app, &refs)
 }
 
 fn update_tray_labels(app: &tauri::AppHandle) -> Result<(), String> {
@@ -1479,186 +1328,31 @@ fn update_tray_labels(app: &tauri::AppHandle) -> Result<(), String> {
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     cleanup_backup_cache();
-    cleanup_clipboard_cache();
+    cleanup_temp_capture_dir();
     
-    let mut initial_settings = load_settings_from_file();
-    let mut settings_changed = !settings_file_has_stitch_shortcut();
-
-    let (mut shortcut_full, mut shortcut_area, mut shortcut_stitch);
-
-    match normalize_and_parse(&initial_settings.fullscreen_shortcut) {
-        Ok((normalized, shortcut)) => {
-            if normalized != initial_settings.fullscreen_shortcut {
-                initial_settings.fullscreen_shortcut = normalized;
-                settings_changed = true;
-            }
-            shortcut_full = shortcut;
-        }
-        Err(_) => {
-            initial_settings.fullscreen_shortcut = default_fullscreen_shortcut();
-            settings_changed = true;
-            let (normalized, shortcut) = normalize_and_parse(&initial_settings.fullscreen_shortcut)
-                .unwrap_or_else(|_| {
-                    (
-                        default_fullscreen_shortcut(),
-                        Shortcut::new(Some(Modifiers::SUPER | Modifiers::SHIFT), Code::Digit3),
-                    )
-                });
-            initial_settings.fullscreen_shortcut = normalized;
-            shortcut_full = shortcut;
-        }
-    }
-
-    match normalize_and_parse(&initial_settings.area_shortcut) {
-        Ok((normalized, shortcut)) => {
-            if normalized != initial_settings.area_shortcut {
-                initial_settings.area_shortcut = normalized;
-                settings_changed = true;
-            }
-            shortcut_area = shortcut;
-        }
-        Err(_) => {
-            initial_settings.area_shortcut = default_area_shortcut();
-            settings_changed = true;
-            let (normalized, shortcut) = normalize_and_parse(&initial_settings.area_shortcut)
-                .unwrap_or_else(|_| {
-                    (
-                        default_area_shortcut(),
-                        Shortcut::new(Some(Modifiers::SUPER | Modifiers::SHIFT), Code::Digit4),
-                    )
-                });
-            initial_settings.area_shortcut = normalized;
-            shortcut_area = shortcut;
-        }
-    }
-
-    match normalize_and_parse(&initial_settings.stitch_shortcut) {
-        Ok((normalized, shortcut)) => {
-            if normalized != initial_settings.stitch_shortcut {
-                initial_settings.stitch_shortcut = normalized;
-                settings_changed = true;
-            }
-            shortcut_stitch = shortcut;
-        }
-        Err(_) => {
-            initial_settings.stitch_shortcut = default_stitch_shortcut();
-            settings_changed = true;
-            let (normalized, shortcut) = normalize_and_parse(&initial_settings.stitch_shortcut)
-                .unwrap_or_else(|_| {
-                    (
-                        default_stitch_shortcut(),
-                        Shortcut::new(Some(Modifiers::SUPER | Modifiers::SHIFT), Code::Digit2),
-                    )
-                });
-            initial_settings.stitch_shortcut = normalized;
-            shortcut_stitch = shortcut;
-        }
-    }
-
-    if shortcut_full.id() == shortcut_area.id()
-        || shortcut_full.id() == shortcut_stitch.id()
-        || shortcut_area.id() == shortcut_stitch.id()
-    {
-        initial_settings.fullscreen_shortcut = default_fullscreen_shortcut();
-        initial_settings.area_shortcut = default_area_shortcut();
-        initial_settings.stitch_shortcut = default_stitch_shortcut();
-        settings_changed = true;
-
-        let (normalized_full, full) = normalize_and_parse(&initial_settings.fullscreen_shortcut)
-            .unwrap_or_else(|_| {
-                (
-                    default_fullscreen_shortcut(),
-                    Shortcut::new(Some(Modifiers::SUPER | Modifiers::SHIFT), Code::Digit3),
-                )
-            });
-        let (normalized_area, area) = normalize_and_parse(&initial_settings.area_shortcut)
-            .unwrap_or_else(|_| {
-                (
-                    default_area_shortcut(),
-                    Shortcut::new(Some(Modifiers::SUPER | Modifiers::SHIFT), Code::Digit4),
-                )
-            });
-        let (normalized_stitch, stitch) = normalize_and_parse(&initial_settings.stitch_shortcut)
-            .unwrap_or_else(|_| {
-                (
-                    default_stitch_shortcut(),
-                    Shortcut::new(Some(Modifiers::SUPER | Modifiers::SHIFT), Code::Digit2),
-                )
-            });
-        initial_settings.fullscreen_shortcut = normalized_full;
-        initial_settings.area_shortcut = normalized_area;
-        initial_settings.stitch_shortcut = normalized_stitch;
-        shortcut_full = full;
-        shortcut_area = area;
-        shortcut_stitch = stitch;
-    }
-
-    if settings_changed {
-        let _ = save_settings_to_file(&initial_settings);
-    }
+    let initial_settings = load_settings_from_file();
 
     tauri::Builder::default()
         .manage(AppState {
             settings: Mutex::new(initial_settings),
-            active_fullscreen_shortcut: Mutex::new(shortcut_full),
-            active_area_shortcut: Mutex::new(shortcut_area),
-            active_stitch_shortcut: Mutex::new(shortcut_stitch),
-            stitch_lock: Mutex::new(false),
-            stitched_dimensions: Mutex::new(HashMap::new()),
+            shortcuts: ShortcutRegistry::default(),
+            last_region: Mutex::new(None),
+            stitch_session: Mutex::new(StitchSession::default()),
+            stitch_jobs: JobRegistry::default(),
+            clipboard_cache: ClipboardCache::system(),
+            permissions: PermissionDiagnostics::default(),
+            runner: Arc::new(SystemRunner),
+            data: DataPaths::system(),
         })
         .plugin(
             tauri_plugin_global_shortcut::Builder::new()
-                .with_shortcuts([shortcut_area, shortcut_full, shortcut_stitch])
-                .unwrap()
-                .with_handler(move |app, shortcut, event| {
-                    if event.state == ShortcutState::Pressed {
-                        if app.get_webview_window("shortcut-config").is_some() {
-                            return;
-                        }
-                        let state = app.state::<AppState>();
-                        let fullscreen_shortcut = *state.active_fullscreen_shortcut.lock().unwrap();
-                        let area_shortcut = *state.active_area_shortcut.lock().unwrap();
-                        let stitch_shortcut = *state.active_stitch_shortcut.lock().unwrap();
-
-                        if shortcut.id() == area_shortcut.id() {
-                            let app_clone = app.clone();
-                            std::thread::spawn(move || {
-                                if let Ok(path) = do_area_screenshot(&app_clone) {
-                                    let _ = open_rename_popup(app_clone, path);
-                                }
-                            });
-                        } else if shortcut.id() == fullscreen_shortcut.id() {
-                            let app_clone = app.clone();
-                            std::thread::spawn(move || {
-                                if let Ok(path) = do_fullscreen_screenshot(&app_clone) {
-                                    let _ = open_rename_popup(app_clone, path);
-                                }
-                            });
-                        } else if shortcut.id() == stitch_shortcut.id() {
-                            let mut lock = state.stitch_lock.lock().unwrap();
-                            if *lock {
-                                println!("[stitch] shortcut ignored: lock already set");
-                                return;
-                            }
-                            *lock = true;
-                            println!("[stitch] shortcut accepted: lock set, emitting event");
-                            let app_clone = app.clone();
-                            std::thread::spawn(move || {
-                                std::thread::sleep(std::time::Duration::from_secs(10));
-                                let state = app_clone.state::<AppState>();
-                                let mut lock = state.stitch_lock.lock().unwrap();
-                                if *lock {
-                                    *lock = false;
-                                    println!("[stitch] lock auto-cleared after timeout");
-                                }
-                            });
-                            let _ = app.emit("stitch-images", ());
-                        }
-                    }
-                })
+                .with_handler(|app, shortcut, event| shortcuts::handle(app, shortcut, event.state))
                 .build(),
         )
         .setup(|app| {
+            clipboard_cache::spawn_eviction(app.handle());
+            permissions::spawn_self_test(app.handle());
+            shortcuts::register_initial(app.handle());
             let settings = app.state::<AppState>().settings.lock().unwrap().clone();
             let menu = build_tray_menu(app.handle(), &settings)?;
 
@@ -1668,50 +1362,14 @@ pub fn run() {
                 .menu(&menu)
                 .show_menu_on_left_click(false)
                 .on_menu_event(|app, event| match event.id.as_ref() {
-                    "screenshot" => {
-                        let app_clone = app.clone();
-                        std::thread::spawn(move || {
-                            if let Ok(path) = do_area_screenshot(&app_clone) {
-                                let _ = open_rename_popup(app_clone, path);
-                            }
-                        });
-                    }
-                    "fullscreen" => {
-                        let app_clone = app.clone();
-                        std::thread::spawn(move || {
-                            if let Ok(path) = do_fullscreen_screenshot(&app_clone) {
-                                let _ = open_rename_popup(app_clone, path);
-                            }
-                        });
-                    }
-                    "stitch" => {
-                        let state = app.state::<AppState>();
-                        let mut lock = state.stitch_lock.lock().unwrap();
-                        if *lock {
-                            return;
-                        }
-                        *lock = true;
-                        let app_clone = app.clone();
-                        std::thread::spawn(move || {
-                            std::thread::sleep(std::time::Duration::from_secs(10));
-                            let state = app_clone.state::<AppState>();
-                            let mut lock = state.stitch_lock.lock().unwrap();
-                            if *lock {
-                                *lock = false;
-                            }
-                        });
-                        let _ = app.emit("stitch-images", ());
-                    }
-                    "show" => {
-                        if let Some(window) = app.get_webview_window("main") {
-                            let _ = window.show();
-                            let _ = window.set_focus();
-                        }
-                    }
                     "quit" => {
                         app.exit(0);
                     }
-                    _ => {}
+                    id => {
+                        if let Some(action) = ShortcutAction::from_id(id) {
+                            shortcuts::run_action(app, action);
+                        }
+                    }
                 })
                 .on_tray_icon_event(|tray, event| {
                     if let TrayIconEvent::Click {
@@ -1731,7 +1389,7 @@ pub fn run() {
 
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![take_screenshot, take_fullscreen_screenshot, get_finder_selection, save_stitch_temp, clear_stitch_lock, show_alert, rename_screenshot, save_edited_screenshot, read_image_base64, ensure_original_backup, read_original_image_base64, delete_original_backup, open_rename_popup, close_rename_popup, open_note_popup, close_note_popup, close_note_and_open_rename, delete_screenshot, open_editor_window, close_editor_and_open_rename, close_editor_and_open_note, close_editor_window, copy_image_to_clipboard, copy_file_to_clipboard, copy_file_to_clipboard_cached, get_settings, save_settings, update_shortcuts, open_shortcut_config, close_shortcut_config])
+        .invoke_handler(tauri::generate_handler![take_screenshot, take_fullscreen_screenshot, take_capture, get_finder_selection, collect_stitch_sources, stitch_images, stitch_session::get_stitch_session, stitch_session::finish_stitch_session, stitch_session::cancel_stitch_session, stitch_jobs::list_stitch_jobs, stitch_jobs::cancel_stitch_job, show_alert, rename_screenshot, save_edited_screenshot, burn_note, read_screenshot_metadata, read_image_base64, ensure_original_backup, read_original_image_base64, delete_original_backup, open_rename_popup, close_rename_popup, open_note_popup, close_note_popup, close_note_and_open_rename, delete_screenshot, open_editor_window, close_editor_and_open_rename, close_editor_and_open_note, close_editor_window, copy_image_to_clipboard, copy_file_to_clipboard, copy_file_to_clipboard_cached, clipboard_cache::get_cache_stats, clipboard_cache::clear_cache, get_settings, save_settings, preview_filename_template, shortcuts::update_shortcuts, shortcut_conflicts::check_shortcut_conflicts, list_history, search_history, forget, reveal, open_shortcut_config, close_shortcut_config, permissions::check_permissions, permissions::open_permission_settings])
         .on_window_event(|window, event| {
             // Only prevent close for main window, let rename popup close normally
             if window.label() == "main" {
@@ -1745,3 +1403,57 @@ pub fn run() {
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use command_runner::{CommandOutput, FakeRunner};
+    use image_pipeline::RgbaImage;
+
+    #[test]
+    fn finder_selection_keeps_stitchable_images() {
+        let dir = std::env::temp_dir().join(format!("screenshotapp-finder-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let png = image_pipeline::encode_png(&RgbaImage::new(4, 4)).unwrap();
+        let first = dir.join("first.png");
+        let second = dir.join("second.png");
+        let notes = dir.join("notes.txt");
+        std::fs::write(&first, &png).unwrap();
+        std::fs::write(&second, &png).unwrap();
+        std::fs::write(&notes, "not an image").unwrap();
+
+        let runner = FakeRunner::default();
+        let stdout = format!("{}\n{}\n{}\n", first.display(), notes.display(), second.display());
+        runner.on("osascript", move |_| Ok(CommandOutput::ok(&stdout)));
+
+        let paths = read_finder_selection(&runner, &PermissionDiagnostics::default()).unwrap();
+
+        assert_eq!(paths, [first.to_string_lossy(), second.to_string_lossy()]);
+        assert_eq!(runner.calls("osascript")[0][0], "-e");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn finder_selection_without_automation_access_is_permission_denied() {
+        let runner = FakeRunner::default();
+        runner.on("osascript", |_| {
+            Ok(CommandOutput::failed(
+                "execution error: Not authorized to send Apple events to Finder. (-1743)\n",
+            ))
+        });
+
+        let error = read_finder_selection(&runner, &PermissionDiagnostics::default()).unwrap_err();
+
+        assert_eq!(error.kind(), "permissionDenied");
+        assert!(error.to_string().contains("Privacy & Security → Automation"));
+    }
+
+    #[test]
+    fn finder_selection_fails_when_osascript_is_missing() {
+        let runner = FakeRunner::default();
+
+        let error = read_finder_selection(&runner, &PermissionDiagnostics::default()).unwrap_err();
+
+        assert_eq!(error.kind(), "notFound");
+    }
+}
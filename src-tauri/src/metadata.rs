@@ -0,0 +1,448 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::image_probe::{detect_format, ImageFormat};
+
+// Capture metadata stored inside the image file as an XMP packet: a JPEG APP1
+// segment or a PNG iTXt chunk keyed "XML:com.adobe.xmp". Pixels are never
+// re-encoded; only the metadata block is replaced. This keeps the exact note
+// text available to tools that would otherwise have to OCR the burned-in bar.
+
+const XMP_NAMESPACE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const APP_NAMESPACE: &str = "https://github.com/bald-ai/mac_screenshot/ns/1.0/";
+
+// A JPEG segment length is a u16 that includes its own two bytes.
+const MAX_JPEG_SEGMENT: usize = u16::MAX as usize - 2;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenshotMetadata {
+    pub note: Option<String>,
+    // RFC 3339
+    pub captured_at: Option<String>,
+    pub mode: Option<String>,
+    pub source_app: Option<String>,
+    pub original_width: Option<u32>,
+    pub original_height: Option<u32>,
+    // Stitched images: size of the largest source, used to size the editor
+    #[serde(default)]
+    pub stitch_source_width: Option<u32>,
+    #[serde(default)]
+    pub stitch_source_height: Option<u32>,
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            '\t' => escaped.push_str("&#9;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_xml(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find(';').map(|i| start + i) else {
+            result.push_str(&rest[start..]);
+            return result;
+        };
+        let entity = &rest[start + 1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => result.push(c),
+            None => result.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+pub fn to_xmp(metadata: &ScreenshotMetadata) -> String {
+    let mut attributes = String::new();
+    let mut attribute = |name: &str, value: Option<String>| {
+        if let Some(value) = value {
+            attributes.push_str(&format!("\n   {}=\"{}\"", name, escape_xml(&value)));
+        }
+    };
+    attribute("xmp:CreateDate", metadata.captured_at.clone());
+    attribute("xmp:CreatorTool", Some("screenshotapp".to_string()));
+    attribute("scr:Mode", metadata.mode.clone());
+    attribute("scr:SourceApp", metadata.source_app.clone());
+    attribute("scr:OriginalWidth", metadata.original_width.map(|w| w.to_string()));
+    attribute("scr:OriginalHeight", metadata.original_height.map(|h| h.to_string()));
+    attribute("scr:StitchSourceWidth", metadata.stitch_source_width.map(|w| w.to_string()));
+    attribute("scr:StitchSourceHeight", metadata.stitch_source_height.map(|h| h.to_string()));
+
+    let description = match &metadata.note {
+        Some(note) => format!(
+            ">\n   <dc:description>\n    <rdf:Alt>\n     <rdf:li xml:lang=\"x-default\">{}</rdf:li>\n    </rdf:Alt>\n   </dc:description>\n  </rdf:Description>",
+            escape_xml(note)
+        ),
+        None => "/>".to_string(),
+    };
+
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+<rdf:Description rdf:about=\"\"\n   \
+xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n   \
+xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n   \
+xmlns:scr=\"{}\"{}{}\n \
+</rdf:RDF>\n\
+</x:xmpmeta>\n\
+<?xpacket end=\"w\"?>",
+        APP_NAMESPACE, attributes, description
+    )
+}
+
+// Reads back what `to_xmp` writes. Not a general XMP parser.
+pub fn from_xmp(xmp: &str) -> ScreenshotMetadata {
+    let attribute = |name: &str| -> Option<String> {
+        let marker = format!("{}=\"", name);
+        let start = xmp.find(&marker)? + marker.len();
+        let end = xmp[start..].find('"')? + start;
+        Some(unescape_xml(&xmp[start..end]))
+    };
+    let note = xmp.find("<dc:description>").and_then(|description| {
+        let rest = &xmp[description..];
+        let li = rest.find("<rdf:li")?;
+        let start = li + rest[li..].find('>')? + 1;
+        let end = rest[start..].find("</rdf:li>")? + start;
+        Some(unescape_xml(&rest[start..end]))
+    });
+
+    ScreenshotMetadata {
+        note,
+        captured_at: attribute("xmp:CreateDate"),
+        mode: attribute("scr:Mode"),
+        source_app: attribute("scr:SourceApp"),
+        original_width: attribute("scr:OriginalWidth").and_then(|w| w.parse().ok()),
+        original_height: attribute("scr:OriginalHeight").and_then(|h| h.parse().ok()),
+        stitch_source_width: attribute("scr:StitchSourceWidth").and_then(|w| w.parse().ok()),
+        stitch_source_height: attribute("scr:StitchSourceHeight").and_then(|h| h.parse().ok()),
+    }
+}
+
+// JPEG: drop any existing XMP APP1 segment and insert ours after JFIF/EXIF headers.
+fn jpeg_with_xmp(bytes: &[u8], xmp: &str) -> Result<Vec<u8>, String> {
+    let payload_len = XMP_NAMESPACE.len() + xmp.len();
+    if payload_len > MAX_JPEG_SEGMENT {
+        return Err("Metadata is too large for a JPEG XMP segment".to_string());
+    }
+
+    let soi = bytes.get(..2).ok_or("Corrupt JPEG header")?;
+    let mut output = Vec::with_capacity(bytes.len() + payload_len + 4);
+    output.extend_from_slice(soi);
+    let mut pos = 2;
+    let mut inserted = false;
+    let insert = |output: &mut Vec<u8>| {
+        output.extend_from_slice(&[0xFF, 0xE1]);
+        output.extend_from_slice(&((payload_len + 2) as u16).to_be_bytes());
+        output.extend_from_slice(XMP_NAMESPACE);
+        output.extend_from_slice(xmp.as_bytes());
+    };
+
+    while pos + 4 <= bytes.len() && bytes[pos] == 0xFF {
+        let marker = bytes[pos + 1];
+        // Only walk the APPn header block; everything from the first other marker is copied as-is
+        if !(0xE0..=0xEF).contains(&marker) {
+            break;
+        }
+        let length = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let end = pos + 2 + length;
+        if length < 2 || end > bytes.len() {
+            return Err("Corrupt JPEG header".to_string());
+        }
+        let is_xmp = marker == 0xE1 && bytes.get(pos + 4..end).is_some_and(|payload| payload.starts_with(XMP_NAMESPACE));
+        if !is_xmp {
+            // Keep APP0 (JFIF) and APP1 (EXIF) first, as readers expect
+            if !inserted && marker > 0xE1 {
+                insert(&mut output);
+                inserted = true;
+            }
+            output.extend_from_slice(&bytes[pos..end]);
+        }
+        pos = end;
+    }
+    if !inserted {
+        insert(&mut output);
+    }
+    output.extend_from_slice(&bytes[pos..]);
+    Ok(output)
+}
+
+fn jpeg_xmp(bytes: &[u8]) -> Option<String> {
+    let mut pos = 2;
+    while pos + 4 <= bytes.len() && bytes[pos] == 0xFF {
+        let marker = bytes[pos + 1];
+        if marker == 0xDA || marker == 0xD9 {
+            return None;
+        }
+        let length = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let end = (pos + 2 + length).min(bytes.len());
+        if marker == 0xE1 {
+            if let Some(packet) = bytes.get(pos + 4..end).and_then(|payload| payload.strip_prefix(XMP_NAMESPACE)) {
+                return Some(String::from_utf8_lossy(packet).to_string());
+            }
+        }
+        pos = end;
+    }
+    None
+}
+
+fn crc32(data: &[&[u8]]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for chunk in data {
+        for &byte in *chunk {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            }
+        }
+    }
+    !crc
+}
+
+// Yields (type, data, start, end) for each chunk; stops at the first malformed one.
+fn png_chunks(bytes: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8], usize, usize)> {
+    let mut pos = PNG_SIGNATURE.len();
+    std::iter::from_fn(move || {
+        if pos + 12 > bytes.len() {
+            return None;
+        }
+        let length = u32::from_be_bytes(bytes[pos..pos + 4].try_into().ok()?) as usize;
+        let kind: [u8; 4] = bytes[pos + 4..pos + 8].try_into().ok()?;
+        let end = pos.checked_add(12)?.checked_add(length)?;
+        if end > bytes.len() {
+            return None;
+        }
+        let chunk = (kind, &bytes[pos + 8..pos + 8 + length], pos, end);
+        pos = end;
+        Some(chunk)
+    })
+}
+
+fn is_xmp_itxt(kind: &[u8; 4], data: &[u8]) -> bool {
+    kind == b"iTXt" && data.starts_with(PNG_XMP_KEYWORD) && data.get(PNG_XMP_KEYWORD.len()) == Some(&0)
+}
+
+// PNG: drop any existing XMP iTXt chunk and insert ours right after IHDR.
+fn png_with_xmp(bytes: &[u8], xmp: &str) -> Result<Vec<u8>, String> {
+    // keyword \0, compression flag 0, method 0, empty language \0, empty translated keyword \0
+    let mut data = PNG_XMP_KEYWORD.to_vec();
+    data.extend_from_slice(&[0, 0, 0, 0, 0]);
+    data.extend_from_slice(xmp.as_bytes());
+
+    let mut output = Vec::with_capacity(bytes.len() + data.len() + 12);
+    output.extend_from_slice(PNG_SIGNATURE);
+    let mut saw_iend = false;
+    for (kind, chunk_data, start, end) in png_chunks(bytes) {
+        if is_xmp_itxt(&kind, chunk_data) {
+            continue;
+        }
+        output.extend_from_slice(&bytes[start..end]);
+        if &kind == b"IHDR" {
+            output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            output.extend_from_slice(b"iTXt");
+            output.extend_from_slice(&data);
+            output.extend_from_slice(&crc32(&[b"iTXt", &data]).to_be_bytes());
+        }
+        if &kind == b"IEND" {
+            saw_iend = true;
+            break;
+        }
+    }
+    if !saw_iend {
+        return Err("Corrupt PNG: missing IEND".to_string());
+    }
+    Ok(output)
+}
+
+fn png_xmp(bytes: &[u8]) -> Option<String> {
+    png_chunks(bytes).find_map(|(kind, data, _, _)| {
+        if !is_xmp_itxt(&kind, data) {
+            return None;
+        }
+        // The chunk may be cut short anywhere, so every slice is checked
+        let rest = data.get(PNG_XMP_KEYWORD.len() + 1..)?;
+        let (&compressed, rest) = rest.split_first()?;
+        if compressed != 0 {
+            return None;
+        }
+        // Skip compression method, language tag and translated keyword
+        let rest = rest.get(1..)?;
+        let language_end = rest.iter().position(|&b| b == 0)?;
+        let rest = rest.get(language_end + 1..)?;
+        let translated_end = rest.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(rest.get(translated_end + 1..)?).to_string())
+    })
+}
+
+// Rewrite `path` with the metadata embedded. Other formats are left untouched.
+pub fn write_metadata(path: &Path, metadata: &ScreenshotMetadata) -> Result<(), String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read image: {}", e))?;
+    let xmp = to_xmp(metadata);
+    let updated = match detect_format(&bytes) {
+        Some(ImageFormat::Jpeg) => jpeg_with_xmp(&bytes, &xmp)?,
+        Some(ImageFormat::Png) => png_with_xmp(&bytes, &xmp)?,
+        _ => return Ok(()),
+    };
+
+    let tmp = path.with_extension("meta.tmp");
+    std::fs::write(&tmp, updated).map_err(|e| format!("Failed to write metadata: {}", e))?;
+    std::fs::rename(&tmp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        format!("Failed to write metadata: {}", e)
+    })
+}
+
+// Largest source size recorded in a stitched image, if any.
+pub fn stitch_source_size(path: &Path) -> Option<(u32, u32)> {
+    let metadata = read_metadata(path).ok()??;
+    Some((metadata.stitch_source_width?, metadata.stitch_source_height?))
+}
+
+pub fn read_metadata(path: &Path) -> Result<Option<ScreenshotMetadata>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read image: {}", e))?;
+    let xmp = match detect_format(&bytes) {
+        Some(ImageFormat::Jpeg) => jpeg_xmp(&bytes),
+        Some(ImageFormat::Png) => png_xmp(&bytes),
+        _ => None,
+    };
+    Ok(xmp.map(|xmp| from_xmp(&xmp)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_pipeline::{decode_bytes, encode_jpeg, encode_png, RgbaImage};
+
+    fn sample() -> ScreenshotMetadata {
+        ScreenshotMetadata {
+            note: Some("Fix <this> & \"that\"\nsecond line".to_string()),
+            captured_at: Some("2025-01-20T09:05:07+01:00".to_string()),
+            mode: Some("area".to_string()),
+            source_app: Some("Safari".to_string()),
+            original_width: Some(1920),
+            original_height: Some(1080),
+            stitch_source_width: Some(800),
+            stitch_source_height: Some(600),
+        }
+    }
+
+    fn itxt_chunk(data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(b"iTXt");
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&crc32(&[b"iTXt", data]).to_be_bytes());
+        chunk
+    }
+
+    #[test]
+    fn xmp_text_round_trips() {
+        assert_eq!(from_xmp(&to_xmp(&sample())), sample());
+        let empty = ScreenshotMetadata::default();
+        assert_eq!(from_xmp(&to_xmp(&empty)), empty);
+    }
+
+    #[test]
+    fn jpeg_round_trip_replaces_the_segment() {
+        let jpeg = encode_jpeg(&RgbaImage::new(4, 4), 80).unwrap();
+        let first = jpeg_with_xmp(&jpeg, &to_xmp(&ScreenshotMetadata::default())).unwrap();
+        let second = jpeg_with_xmp(&first, &to_xmp(&sample())).unwrap();
+
+        assert_eq!(jpeg_xmp(&second).map(|xmp| from_xmp(&xmp)), Some(sample()));
+        let segments = second.windows(XMP_NAMESPACE.len()).filter(|w| *w == XMP_NAMESPACE).count();
+        assert_eq!(segments, 1);
+        // JFIF stays first and the pixels still decode
+        assert_eq!(&second[2..4], &[0xFF, 0xE0]);
+        assert_eq!(decode_bytes(&second).unwrap().width, 4);
+    }
+
+    #[test]
+    fn png_round_trip_replaces_the_chunk() {
+        let png = encode_png(&RgbaImage::new(3, 2)).unwrap();
+        let first = png_with_xmp(&png, &to_xmp(&ScreenshotMetadata::default())).unwrap();
+        let second = png_with_xmp(&first, &to_xmp(&sample())).unwrap();
+
+        assert_eq!(png_xmp(&second).map(|xmp| from_xmp(&xmp)), Some(sample()));
+        let chunks: Vec<[u8; 4]> = png_chunks(&second).map(|(kind, ..)| kind).collect();
+        assert_eq!(chunks.iter().filter(|kind| *kind == b"iTXt").count(), 1);
+        assert_eq!(chunks[1], *b"iTXt");
+        assert_eq!(decode_bytes(&second).unwrap().height, 2);
+    }
+
+    #[test]
+    fn files_round_trip_and_other_formats_are_left_alone() {
+        let dir = std::env::temp_dir().join(format!("screenshotapp-metadata-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let jpeg = dir.join("a.jpg");
+        std::fs::write(&jpeg, encode_jpeg(&RgbaImage::new(2, 2), 80).unwrap()).unwrap();
+        let text = dir.join("a.txt");
+        std::fs::write(&text, "not an image").unwrap();
+
+        write_metadata(&jpeg, &sample()).unwrap();
+        write_metadata(&text, &sample()).unwrap();
+
+        assert_eq!(read_metadata(&jpeg).unwrap(), Some(sample()));
+        assert_eq!(stitch_source_size(&jpeg), Some((800, 600)));
+        assert_eq!(std::fs::read_to_string(&text).unwrap(), "not an image");
+        assert_eq!(read_metadata(&text).unwrap(), None);
+    }
+
+    #[test]
+    fn truncated_itxt_chunks_read_as_missing() {
+        let png = encode_png(&RgbaImage::new(1, 1)).unwrap();
+        let ihdr_end = PNG_SIGNATURE.len() + 8 + 13 + 4;
+        let mut full = PNG_XMP_KEYWORD.to_vec();
+        full.extend_from_slice(&[0, 0, 0, 0, 0]);
+        full.extend_from_slice(b"<x/>");
+
+        // Cut after the keyword terminator, the compression flag, the method and the language tag
+        for cut in PNG_XMP_KEYWORD.len() + 1..PNG_XMP_KEYWORD.len() + 5 {
+            let mut bytes = png[..ihdr_end].to_vec();
+            bytes.extend_from_slice(&itxt_chunk(&full[..cut]));
+            bytes.extend_from_slice(&png[ihdr_end..]);
+            assert_eq!(png_xmp(&bytes), None, "cut at {}", cut);
+        }
+    }
+
+    #[test]
+    fn truncated_jpeg_headers_do_not_panic() {
+        let jpeg = jpeg_with_xmp(&encode_jpeg(&RgbaImage::new(2, 2), 80).unwrap(), &to_xmp(&sample())).unwrap();
+        for cut in 0..200.min(jpeg.len()) {
+            let _ = jpeg_xmp(&jpeg[..cut]);
+            let _ = jpeg_with_xmp(&jpeg[..cut], "<x/>");
+        }
+        // A segment length below 2 must not stall the scan
+        assert_eq!(jpeg_xmp(&[0xFF, 0xD8, 0xFF, 0xE1, 0x00, 0x01, 0xFF, 0xD9]), None);
+    }
+}
@@ -0,0 +1,391 @@
+use std::path::Path;
+
+use ab_glyph::{point, Font, FontRef, GlyphId, PxScale, PxScaleFont, ScaleFont};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::image_pipeline::{self, RgbaImage};
+
+// Burns a note into a white bar above or below the screenshot.
+// Rendering uses a bundled font so the result is identical on every machine
+// and does not depend on the webview.
+
+const FONT_DATA: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+
+// Narrow screenshots are widened so the note stays readable.
+const MIN_NOTE_WIDTH: u32 = 400;
+const MAX_NOTE_CHARS: usize = 1000;
+const LINE_HEIGHT: f32 = 1.4;
+
+const MARGIN_COLOR: [u8; 4] = [0xf2, 0xf2, 0xf2, 0xff];
+const BAR_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+const TEXT_COLOR: [u8; 3] = [0x00, 0x00, 0x00];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NotePlacement {
+    Top,
+    #[default]
+    Bottom,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BurnNoteOptions {
+    // Falls back to the `note_placement` setting
+    #[serde(default)]
+    pub placement: Option<NotePlacement>,
+    // Pixels; by default scaled to the image width
+    #[serde(default)]
+    pub font_size: Option<f32>,
+}
+
+type NoteFont<'a> = PxScaleFont<&'a FontRef<'static>>;
+
+struct Layout {
+    font_size: f32,
+    padding: u32,
+}
+
+impl Layout {
+    // Same proportions the old canvas renderer used: 2% of the width, clamped to 12-20px.
+    fn for_width(width: u32, font_size: Option<f32>) -> Self {
+        let width = width as f32;
+        Self {
+            font_size: match font_size {
+                Some(size) => size.clamp(8.0, 72.0),
+                None => (width * 0.02).clamp(12.0, 20.0),
+            },
+            padding: (width * 0.015).clamp(8.0, 16.0).round() as u32,
+        }
+    }
+}
+
+fn text_width(font: &NoteFont, text: &str) -> f32 {
+    let mut width = 0.0;
+    let mut previous: Option<GlyphId> = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(prev) = previous {
+            width += font.kern(prev, id);
+        }
+        width += font.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+// Greedy word wrap. Explicit newlines start a new line; words wider than
+// the line are broken between characters.
+fn wrap_text(font: &NoteFont, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut current = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if current.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", current, word)
+            };
+            if text_width(font, &candidate) <= max_width {
+                current = candidate;
+                continue;
+            }
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            if text_width(font, word) <= max_width {
+                current = word.to_string();
+                continue;
+            }
+            for c in word.chars() {
+                let mut segment = current.clone();
+                segment.push(c);
+                if text_width(font, &segment) > max_width && !current.is_empty() {
+                    lines.push(std::mem::take(&mut current));
+                }
+                current.push(c);
+            }
+        }
+        lines.push(current);
+    }
+    while lines.len() > 1 && lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines
+}
+
+fn fill_rect(image: &mut RgbaImage, y: u32, height: u32, color: [u8; 4]) {
+    let row = image.width as usize * 4;
+    let start = y as usize * row;
+    let end = ((y + height) as usize * row).min(image.pixels.len());
+    for pixel in image.pixels[start..end].chunks_exact_mut(4) {
+        pixel.copy_from_slice(&color);
+    }
+}
+
+fn draw_line(image: &mut RgbaImage, font: &NoteFont, text: &str, x: f32, baseline: f32) {
+    let mut caret = x;
+    let mut previous: Option<GlyphId> = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(prev) = previous {
+            caret += font.kern(prev, id);
+        }
+        let glyph = id.with_scale_and_position(font.scale(), point(caret, baseline));
+        caret += font.h_advance(id);
+        previous = Some(id);
+
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + gx as i64;
+            let py = bounds.min.y as i64 + gy as i64;
+            if px < 0 || py < 0 || px >= image.width as i64 || py >= image.height as i64 {
+                return;
+            }
+            let i = (py as usize * image.width as usize + px as usize) * 4;
+            let alpha = coverage.clamp(0.0, 1.0);
+            for (channel, &target) in TEXT_COLOR.iter().enumerate() {
+                let current = image.pixels[i + channel] as f32;
+                image.pixels[i + channel] = (current + (target as f32 - current) * alpha).round() as u8;
+            }
+        });
+    }
+}
+
+// Height of a single-line label box, e.g. the stitch image captions.
+pub fn label_height(font_size: f32) -> u32 {
+    (font_size * LINE_HEIGHT).ceil() as u32
+}
+
+// Draw one line of text with its box's top-left at (x, y), shortened with an
+// ellipsis if it is wider than `max_width`.
+pub fn draw_label(image: &mut RgbaImage, text: &str, x: u32, y: u32, max_width: u32, font_size: f32) -> Result<(), String> {
+    let font = FontRef::try_from_slice(FONT_DATA).map_err(|e| format!("Failed to load note font: {}", e))?;
+    let scaled = font.as_scaled(PxScale::from(font_size));
+    let max_width = max_width as f32;
+
+    let mut label = text.to_string();
+    if text_width(&scaled, &label) > max_width {
+        while !label.is_empty() && text_width(&scaled, &format!("{}…", label)) > max_width {
+            label.pop();
+        }
+        label.push('…');
+    }
+
+    let text_height = scaled.ascent() - scaled.descent();
+    let baseline = y as f32 + (font_size * LINE_HEIGHT - text_height) / 2.0 + scaled.ascent();
+    draw_line(image, &scaled, &label, x as f32, baseline);
+    Ok(())
+}
+
+// Returns a new image: the source centred on a (possibly wider) canvas plus the note bar.
+pub fn render_note(
+    source: &RgbaImage,
+    note: &str,
+    placement: NotePlacement,
+    font_size: Option<f32>,
+) -> Result<RgbaImage, String> {
+    let font = FontRef::try_from_slice(FONT_DATA).map_err(|e| format!("Failed to load note font: {}", e))?;
+    let width = source.width.max(MIN_NOTE_WIDTH);
+    let layout = Layout::for_width(width, font_size);
+    let scaled = font.as_scaled(PxScale::from(layout.font_size));
+
+    let note: String = note.chars().take(MAX_NOTE_CHARS).collect();
+    let available = (width - layout.padding * 2) as f32;
+    let lines = wrap_text(&scaled, &note, available);
+    let line_height = layout.font_size * LINE_HEIGHT;
+    let bar_height = (lines.len() as f32 * line_height + layout.padding as f32 * 2.0).ceil() as u32;
+
+    let height = source
+        .height
+        .checked_add(bar_height)
+        .ok_or_else(|| "Image is too tall to add a note".to_string())?;
+    let mut output = RgbaImage::new(width, height);
+    let (image_y, bar_y) = match placement {
+        NotePlacement::Top => (bar_height, 0),
+        NotePlacement::Bottom => (0, source.height),
+    };
+
+    fill_rect(&mut output, image_y, source.height, MARGIN_COLOR);
+    fill_rect(&mut output, bar_y, bar_height, BAR_COLOR);
+
+    let x_offset = ((width - source.width) / 2) as usize;
+    let src_row = source.width as usize * 4;
+    let dst_row = width as usize * 4;
+    for y in 0..source.height as usize {
+        let src = &source.pixels[y * src_row..(y + 1) * src_row];
+        let dst_start = (image_y as usize + y) * dst_row + x_offset * 4;
+        output.pixels[dst_start..dst_start + src_row].copy_from_slice(src);
+    }
+
+    // Centre the glyphs vertically within each line box
+    let text_height = scaled.ascent() - scaled.descent();
+    let leading = (line_height - text_height) / 2.0;
+    for (index, line) in lines.iter().enumerate() {
+        let top = bar_y as f32 + layout.padding as f32 + index as f32 * line_height;
+        let baseline = top + leading + scaled.ascent();
+        draw_line(&mut output, &scaled, line, layout.padding as f32, baseline);
+    }
+
+    Ok(output)
+}
+
+// Read `source`, burn the note in and write the result to `dest` in dest's format.
+pub fn burn_note_file(
+    source: &Path,
+    dest: &Path,
+    note: &str,
+    placement: NotePlacement,
+    font_size: Option<f32>,
+    quality: u32,
+) -> Result<(), AppError> {
+    let image = image_pipeline::decode_file(source).map_err(|e| AppError::image("Failed to read image", e))?;
+    let rendered = render_note(&image, note, placement, font_size).map_err(AppError::Failed)?;
+
+    let is_png = dest
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("png"));
+    let bytes = if is_png {
+        image_pipeline::encode_png(&rendered)
+    } else {
+        image_pipeline::encode_jpeg(&rendered, quality)
+    }
+    .map_err(|e| AppError::image("Failed to encode note image", e))?;
+
+    // Write beside the destination first so a failed write never leaves a truncated screenshot
+    let tmp = dest.with_extension("note.tmp");
+    std::fs::write(&tmp, bytes).map_err(|e| AppError::io("Failed to write note image", e))?;
+    std::fs::rename(&tmp, dest).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        AppError::io("Failed to write note image", e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_font<T>(f: impl FnOnce(&NoteFont) -> T) -> T {
+        let font = FontRef::try_from_slice(FONT_DATA).unwrap();
+        f(&font.as_scaled(PxScale::from(16.0)))
+    }
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> RgbaImage {
+        RgbaImage {
+            width,
+            height,
+            pixels: rgba.repeat(width as usize * height as usize),
+        }
+    }
+
+    fn pixel(image: &RgbaImage, x: u32, y: u32) -> [u8; 4] {
+        let i = (y * image.width + x) as usize * 4;
+        image.pixels[i..i + 4].try_into().unwrap()
+    }
+
+    // Source rows copied into `output` starting at (x, y)
+    fn assert_copied(output: &RgbaImage, source: &RgbaImage, x: u32, y: u32) {
+        for row in 0..source.height {
+            for col in 0..source.width {
+                assert_eq!(pixel(output, x + col, y + row), pixel(source, col, row), "at ({}, {})", col, row);
+            }
+        }
+    }
+
+    #[test]
+    fn explicit_newlines_start_new_lines() {
+        let lines = with_font(|font| wrap_text(font, "first\n\nthird", 1000.0));
+
+        assert_eq!(lines, ["first", "", "third"]);
+    }
+
+    #[test]
+    fn trailing_empty_lines_are_dropped() {
+        with_font(|font| {
+            assert_eq!(wrap_text(font, "note\n\n\n", 1000.0), ["note"]);
+            assert_eq!(wrap_text(font, "", 1000.0), [""]);
+        });
+    }
+
+    #[test]
+    fn words_wrap_at_the_line_width() {
+        let lines = with_font(|font| {
+            let max_width = text_width(font, "alpha beta");
+            wrap_text(font, "alpha beta gamma", max_width)
+        });
+
+        assert_eq!(lines, ["alpha beta", "gamma"]);
+    }
+
+    #[test]
+    fn over_long_words_are_broken_between_characters() {
+        with_font(|font| {
+            let word = "abcdefghijklmnopqrstuvwxyz";
+            let max_width = text_width(font, "abcdef");
+            let lines = wrap_text(font, word, max_width);
+
+            assert!(lines.len() > 1);
+            assert!(lines.iter().all(|line| text_width(font, line) <= max_width), "{:?}", lines);
+            assert_eq!(lines.concat(), word);
+        });
+    }
+
+    #[test]
+    fn bottom_note_goes_below_the_unchanged_source() {
+        let mut source = solid(500, 10, [200, 30, 30, 255]);
+        source.pixels[0..4].copy_from_slice(&[1, 2, 3, 4]);
+
+        let output = render_note(&source, "hello", NotePlacement::Bottom, None).unwrap();
+
+        assert_eq!(output.width, 500);
+        assert!(output.height > source.height);
+        assert_copied(&output, &source, 0, 0);
+        assert_eq!(pixel(&output, 0, source.height), BAR_COLOR);
+        assert_eq!(pixel(&output, 0, output.height - 1), BAR_COLOR);
+    }
+
+    #[test]
+    fn top_note_goes_above_the_unchanged_source() {
+        let source = solid(500, 10, [200, 30, 30, 255]);
+
+        let top = render_note(&source, "hello", NotePlacement::Top, None).unwrap();
+        let bottom = render_note(&source, "hello", NotePlacement::Bottom, None).unwrap();
+
+        // Same bar either way
+        assert_eq!(top.height, bottom.height);
+        let bar_height = top.height - source.height;
+        assert_eq!(pixel(&top, 0, 0), BAR_COLOR);
+        assert_copied(&top, &source, 0, bar_height);
+    }
+
+    #[test]
+    fn narrow_source_is_centred_on_a_wider_canvas() {
+        let source = solid(100, 10, [200, 30, 30, 255]);
+
+        let output = render_note(&source, "hello", NotePlacement::Bottom, None).unwrap();
+
+        assert_eq!(output.width, MIN_NOTE_WIDTH);
+        assert_copied(&output, &source, (MIN_NOTE_WIDTH - 100) / 2, 0);
+        assert_eq!(pixel(&output, 0, 0), MARGIN_COLOR);
+        assert_eq!(pixel(&output, MIN_NOTE_WIDTH - 1, 9), MARGIN_COLOR);
+    }
+
+    #[test]
+    fn longer_notes_make_a_taller_bar() {
+        let source = solid(500, 10, [200, 30, 30, 255]);
+
+        let one = render_note(&source, "one line", NotePlacement::Bottom, Some(16.0)).unwrap();
+        let three = render_note(&source, "one\ntwo\nthree", NotePlacement::Bottom, Some(16.0)).unwrap();
+
+        let line_height = 16.0 * LINE_HEIGHT;
+        assert_eq!(three.height - one.height, (2.0 * line_height).round() as u32);
+    }
+}
@@ -0,0 +1,316 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+
+// Where finished screenshots go. Directories may contain tokens that expand
+// into subfolders, e.g. "~/Screenshots/{date}" -> ~/Screenshots/2025-01-20.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputDestination {
+    Area,
+    Fullscreen,
+    Stitch,
+}
+
+impl OutputDestination {
+    fn token(self) -> &'static str {
+        match self {
+            OutputDestination::Area => "area",
+            OutputDestination::Fullscreen => "fullscreen",
+            OutputDestination::Stitch => "stitch",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputSettings {
+    #[serde(default = "default_output_directory")]
+    pub directory: String,
+    #[serde(default)]
+    pub area_directory: Option<String>,
+    #[serde(default)]
+    pub fullscreen_directory: Option<String>,
+    #[serde(default)]
+    pub stitch_directory: Option<String>,
+}
+
+fn default_output_directory() -> String {
+    "~/Desktop".to_string()
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            directory: default_output_directory(),
+            area_directory: None,
+            fullscreen_directory: None,
+            stitch_directory: None,
+        }
+    }
+}
+
+impl OutputSettings {
+    fn template_for(&self, destination: OutputDestination) -> &str {
+        let override_dir = match destination {
+            OutputDestination::Area => &self.area_directory,
+            OutputDestination::Fullscreen => &self.fullscreen_directory,
+            OutputDestination::Stitch => &self.stitch_directory,
+        };
+        override_dir
+            .as_deref()
+            .map(str::trim)
+            .filter(|dir| !dir.is_empty())
+            .unwrap_or(&self.directory)
+    }
+
+    // Check every configured directory so bad tokens are rejected on save, not on capture.
+    pub fn validate(&self) -> Result<(), String> {
+        let now = Local::now();
+        for destination in [
+            OutputDestination::Area,
+            OutputDestination::Fullscreen,
+            OutputDestination::Stitch,
+        ] {
+            expand_directory(self.template_for(destination), destination, &now)?;
+        }
+        Ok(())
+    }
+}
+
+fn home_dir() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string()))
+}
+
+// Expand a leading `~` to the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    if path == "~" {
+        home_dir()
+    } else if let Some(stripped) = path.strip_prefix("~/") {
+        home_dir().join(stripped)
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+// Expand `~` and {date}, {year}, {month}, {day}, {mode} tokens.
+pub fn expand_directory(
+    template: &str,
+    destination: OutputDestination,
+    now: &DateTime<Local>,
+) -> Result<PathBuf, String> {
+    let template = template.trim();
+    if template.is_empty() {
+        return Err("Output directory is empty".to_string());
+    }
+
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .map(|i| start + i)
+            .ok_or_else(|| format!("Unclosed '{{' in output directory \"{}\"", template))?;
+        let token = &rest[start + 1..end];
+        let value = match token {
+            "date" => now.format("%Y-%m-%d").to_string(),
+            "year" => now.format("%Y").to_string(),
+            "month" => now.format("%m").to_string(),
+            "day" => now.format("%d").to_string(),
+            "mode" => destination.token().to_string(),
+            _ => {
+                return Err(format!(
+                    "Unknown token {{{}}} in output directory \"{}\" (expected date, year, month, day or mode)",
+                    token, template
+                ))
+            }
+        };
+        expanded.push_str(&value);
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+
+    let path = expand_home(&expanded);
+
+    if !path.is_absolute() {
+        return Err(format!("Output directory must be an absolute path: {}", template));
+    }
+    Ok(path)
+}
+
+// Resolve the output directory for a destination and create it if missing.
+pub fn resolve_output_dir(settings: &OutputSettings, destination: OutputDestination) -> Result<PathBuf, AppError> {
+    let dir = expand_directory(settings.template_for(destination), destination, &Local::now()).map_err(AppError::Invalid)?;
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| AppError::io(format!("Failed to create output directory {}", dir.display()), e))?;
+    Ok(dir)
+}
+
+// The app's cache directory: ~/Library/Caches/screenshotapp on macOS,
+// $XDG_CACHE_HOME/screenshotapp (or ~/.cache/screenshotapp) elsewhere.
+pub fn cache_dir() -> PathBuf {
+    if cfg!(target_os = "macos") {
+        return home_dir().join("Library").join("Caches").join("screenshotapp");
+    }
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        // The spec says relative values are invalid and must be ignored
+        .filter(|dir| dir.is_absolute())
+        .unwrap_or_else(|| home_dir().join(".cache"))
+        .join("screenshotapp")
+}
+
+// Private scratch space for in-flight captures; never the Desktop.
+pub fn temp_capture_dir() -> PathBuf {
+    cache_dir().join("captures")
+}
+
+// Rename, falling back to copy + delete when the output directory is on another volume.
+pub fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to)?;
+    std::fs::remove_file(from)
+}
+
+// Atomically claim `path` by creating an empty placeholder. Returns false if it already exists.
+pub fn try_reserve(path: &Path) -> std::io::Result<bool> {
+    match std::fs::OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// Claim every `dir/stem{suffix}.ext` or none of them.
+pub fn try_reserve_all(dir: &Path, stem: &str, extension: &str, suffixes: &[String]) -> std::io::Result<Option<Vec<PathBuf>>> {
+    let mut reserved = Vec::with_capacity(suffixes.len());
+    for suffix in suffixes {
+        let candidate = dir.join(format!("{}{}.{}", stem, suffix, extension));
+        match try_reserve(&candidate) {
+            Ok(true) => reserved.push(candidate),
+            result => {
+                for path in &reserved {
+                    let _ = std::fs::remove_file(path);
+                }
+                return result.map(|_| None);
+            }
+        }
+    }
+    Ok(Some(reserved))
+}
+
+// Claim `dir/stem.ext`, falling back to `stem_2.ext`, `stem_3.ext`...
+// Never returns a path that existed before, even if two captures race.
+pub fn reserve_unique_path(dir: &Path, stem: &str, extension: &str) -> std::io::Result<PathBuf> {
+    let mut paths = reserve_unique_paths(dir, stem, extension, &[String::new()])?;
+    Ok(paths.remove(0))
+}
+
+// Same as `reserve_unique_path` for a group of files sharing one stem (e.g. `_part1`, `_part2`).
+pub fn reserve_unique_paths(dir: &Path, stem: &str, extension: &str, suffixes: &[String]) -> std::io::Result<Vec<PathBuf>> {
+    let mut attempt = 1u32;
+    loop {
+        let candidate = if attempt == 1 {
+            stem.to_string()
+        } else {
+            format!("{}_{}", stem, attempt)
+        };
+        if let Some(paths) = try_reserve_all(dir, &candidate, extension, suffixes)? {
+            return Ok(paths);
+        }
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("screenshotapp-output-{}", std::process::id()))
+            .join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn suffixes(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn march_7th() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 3, 7, 9, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn directory_tokens_are_expanded() {
+        let dir = expand_directory("/shots/{year}/{month}/{day}/{mode}", OutputDestination::Area, &march_7th()).unwrap();
+        assert_eq!(dir, PathBuf::from("/shots/2026/03/07/area"));
+
+        let dir = expand_directory("/shots/{date}-{mode}", OutputDestination::Stitch, &march_7th()).unwrap();
+        assert_eq!(dir, PathBuf::from("/shots/2026-03-07-stitch"));
+    }
+
+    #[test]
+    fn leading_tilde_is_the_home_directory() {
+        let dir = expand_directory("~/Pictures/{mode}", OutputDestination::Fullscreen, &march_7th()).unwrap();
+        assert_eq!(dir, home_dir().join("Pictures/fullscreen"));
+    }
+
+    #[test]
+    fn bad_directory_templates_are_rejected() {
+        let unclosed = expand_directory("/shots/{year", OutputDestination::Area, &march_7th()).unwrap_err();
+        assert!(unclosed.contains("Unclosed"), "{}", unclosed);
+
+        let unknown = expand_directory("/shots/{week}", OutputDestination::Area, &march_7th()).unwrap_err();
+        assert!(unknown.contains("Unknown token {week}"), "{}", unknown);
+
+        let relative = expand_directory("shots/{mode}", OutputDestination::Area, &march_7th()).unwrap_err();
+        assert!(relative.contains("absolute"), "{}", relative);
+
+        assert!(expand_directory("  ", OutputDestination::Area, &march_7th()).is_err());
+    }
+
+    #[test]
+    fn existing_files_are_never_overwritten() {
+        let dir = scratch_dir("unique");
+        std::fs::write(dir.join("shot.jpg"), "keep").unwrap();
+
+        assert_eq!(reserve_unique_path(&dir, "shot", "jpg").unwrap(), dir.join("shot_2.jpg"));
+        assert_eq!(reserve_unique_path(&dir, "shot", "jpg").unwrap(), dir.join("shot_3.jpg"));
+        assert_eq!(std::fs::read_to_string(dir.join("shot.jpg")).unwrap(), "keep");
+    }
+
+    #[test]
+    fn parts_are_reserved_all_or_nothing() {
+        let dir = scratch_dir("parts");
+        std::fs::write(dir.join("shot_part2.jpg"), "keep").unwrap();
+        let parts = suffixes(&["_part1", "_part2"]);
+
+        assert_eq!(try_reserve_all(&dir, "shot", "jpg", &parts).unwrap(), None);
+        assert!(!dir.join("shot_part1.jpg").exists());
+
+        let reserved = reserve_unique_paths(&dir, "shot", "jpg", &parts).unwrap();
+        assert_eq!(reserved, [dir.join("shot_2_part1.jpg"), dir.join("shot_2_part2.jpg")]);
+        assert_eq!(std::fs::read_to_string(dir.join("shot_part2.jpg")).unwrap(), "keep");
+    }
+
+    #[test]
+    fn a_failed_reservation_releases_the_earlier_parts() {
+        let dir = scratch_dir("rollback");
+        // The second part would go into a folder that doesn't exist
+        let parts = suffixes(&["_part1", "/missing/part2"]);
+
+        assert!(try_reserve_all(&dir, "shot", "jpg", &parts).is_err());
+        assert!(!dir.join("shot_part1.jpg").exists());
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+
+use crate::command_runner::CommandRunner;
+use crate::error::AppError;
+use crate::AppState;
+
+// Permission diagnostics. On macOS neither missing permission is an obvious
+// error: `screencapture` without Screen Recording access still writes a file,
+// just with the wallpaper only, and `osascript` without Automation access fails
+// with -1743. The checks go through `PermissionProbe`, so the report logic runs
+// the same against a fake probe on Linux.
+
+// "Not authorized to send Apple events"
+const AUTOMATION_DENIED: &str = "-1743";
+
+// Apps we script: Finder for the stitch selection, System Events for the
+// frontmost app name used in filenames.
+const AUTOMATION_TARGETS: [&str; 2] = ["Finder", "System Events"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Permission {
+    ScreenRecording,
+    Automation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionState {
+    Granted,
+    Denied,
+    // Not asked yet, or not checked this session
+    #[default]
+    NotDetermined,
+    // The platform has no such permission
+    NotApplicable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionCheck {
+    pub permission: Permission,
+    // App being scripted, for Automation
+    pub target: Option<String>,
+    pub state: PermissionState,
+    // What the user should do, when the state needs attention
+    pub hint: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsReport {
+    pub platform: String,
+    // RFC 3339, local time
+    pub checked_at: String,
+    pub checks: Vec<PermissionCheck>,
+    // False when any check is denied
+    pub ok: bool,
+}
+
+pub trait PermissionProbe {
+    fn screen_recording(&self) -> PermissionState;
+    // Asks the OS to show its prompt where it has not been shown yet.
+    fn request_screen_recording(&self) -> PermissionState {
+        self.screen_recording()
+    }
+    // Whether the platform gates scripting other apps at all
+    fn has_automation(&self) -> bool;
+    // Sends a harmless Apple event to `target`. May show the Automation prompt.
+    fn automation(&self, target: &str) -> PermissionState;
+}
+
+pub struct SystemProbe<'a> {
+    // Only macOS has anything to script
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    runner: &'a dyn CommandRunner,
+}
+
+impl<'a> SystemProbe<'a> {
+    pub fn new(runner: &'a dyn CommandRunner) -> Self {
+        Self { runner }
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGPreflightScreenCaptureAccess() -> bool;
+    fn CGRequestScreenCaptureAccess() -> bool;
+}
+
+#[cfg(target_os = "macos")]
+impl PermissionProbe for SystemProbe<'_> {
+    // CoreGraphics can't tell "denied" from "never asked"; both read as denied.
+    fn screen_recording(&self) -> PermissionState {
+        if unsafe { CGPreflightScreenCaptureAccess() } {
+            PermissionState::Granted
+        } else {
+            PermissionState::Denied
+        }
+    }
+
+    fn request_screen_recording(&self) -> PermissionState {
+        if unsafe { CGRequestScreenCaptureAccess() } {
+            PermissionState::Granted
+        } else {
+            PermissionState::Denied
+        }
+    }
+
+    fn has_automation(&self) -> bool {
+        true
+    }
+
+    fn automation(&self, target: &str) -> PermissionState {
+        let script = format!("tell application \"{}\" to get name", target);
+        let Ok(output) = self.runner.output("osascript", &["-e", &script]) else {
+            return PermissionState::NotDetermined;
+        };
+        if output.success {
+            return PermissionState::Granted;
+        }
+        if is_automation_denied(&output.stderr) {
+            PermissionState::Denied
+        } else {
+            // e.g. the prompt is still open and the event timed out
+            PermissionState::NotDetermined
+        }
+    }
+}
+
+// X11 needs no permission; the screenshot portal asks on every capture.
+#[cfg(not(target_os = "macos"))]
+impl PermissionProbe for SystemProbe<'_> {
+    fn screen_recording(&self) -> PermissionState {
+        PermissionState::NotApplicable
+    }
+
+    fn has_automation(&self) -> bool {
+        false
+    }
+
+    fn automation(&self, _target: &str) -> PermissionState {
+        PermissionState::NotApplicable
+    }
+}
+
+pub fn is_automation_denied(stderr: &str) -> bool {
+    stderr.contains(AUTOMATION_DENIED) || stderr.contains("Not authorized to send Apple events")
+}
+
+// Error for a failed osascript run, typed as PermissionDenied for -1743.
+pub fn osascript_error(target: &str, stderr: &str) -> AppError {
+    if is_automation_denied(stderr) {
+        return AppError::PermissionDenied(automation_hint(target));
+    }
+    AppError::Failed(stderr.to_string())
+}
+
+fn screen_recording_hint() -> String {
+    "Grant Screen Recording access in System Settings → Privacy & Security → Screen Recording, then restart the app"
+        .to_string()
+}
+
+fn automation_hint(target: &str) -> String {
+    format!(
+        "Grant {} access in System Settings → Privacy & Security → Automation",
+        target
+    )
+}
+
+fn settings_url(permission: Permission) -> &'static str {
+    match permission {
+        Permission::ScreenRecording => "x-apple.systempreferences:com.apple.preference.security?Privacy_ScreenCapture",
+        Permission::Automation => "x-apple.systempreferences:com.apple.preference.security?Privacy_Automation",
+    }
+}
+
+// Last known state per permission. Automation is only probed when asked for,
+// because the probe can show the macOS prompt; in between, the states seen by
+// real osascript calls are kept here.
+#[derive(Default)]
+pub struct PermissionDiagnostics {
+    automation: Mutex<HashMap<String, PermissionState>>,
+}
+
+impl PermissionDiagnostics {
+    pub fn record_automation(&self, target: &str, state: PermissionState) {
+        self.automation.lock().unwrap().insert(target.to_string(), state);
+    }
+
+    fn observed_automation(&self, target: &str) -> PermissionState {
+        self.automation.lock().unwrap().get(target).copied().unwrap_or_default()
+    }
+
+    // With `probe_automation`, every target is sent an Apple event; otherwise
+    // the observed states are reported.
+    pub fn run(&self, probe: &dyn PermissionProbe, probe_automation: bool) -> DiagnosticsReport {
+        let screen_state = probe.screen_recording();
+        let mut checks = vec![PermissionCheck {
+            permission: Permission::ScreenRecording,
+            target: None,
+            state: screen_state,
+            hint: (screen_state == PermissionState::Denied).then(screen_recording_hint),
+        }];
+
+        let targets: &[&str] = if probe.has_automation() { &AUTOMATION_TARGETS } else { &[] };
+        for target in targets {
+            let state = if probe_automation {
+                let state = probe.automation(target);
+                if matches!(state, PermissionState::Granted | PermissionState::Denied) {
+                    self.record_automation(target, state);
+                }
+                state
+            } else {
+                self.observed_automation(target)
+            };
+            checks.push(PermissionCheck {
+                permission: Permission::Automation,
+                target: Some(target.to_string()),
+                state,
+                hint: (state == PermissionState::Denied).then(|| automation_hint(target)),
+            });
+        }
+
+        DiagnosticsReport {
+            platform: std::env::consts::OS.to_string(),
+            checked_at: Local::now().to_rfc3339(),
+            ok: checks.iter().all(|check| check.state != PermissionState::Denied),
+            checks,
+        }
+    }
+}
+
+// Called before an OS capture. Shows the macOS prompt the first time, and
+// refuses to capture rather than save a wallpaper-only image.
+pub fn require_screen_recording(probe: &dyn PermissionProbe) -> Result<(), AppError> {
+    if probe.screen_recording() != PermissionState::Denied {
+        return Ok(());
+    }
+    if probe.request_screen_recording() == PermissionState::Granted {
+        return Ok(());
+    }
+    Err(AppError::PermissionDenied(screen_recording_hint()))
+}
+
+fn log_report(report: &DiagnosticsReport) {
+    for check in &report.checks {
+        let target = check.target.as_deref().map(|t| format!(" ({})", t)).unwrap_or_default();
+        println!("[permissions] {:?}{}: {:?}", check.permission, target, check.state);
+    }
+}
+
+// Startup self-test: no Apple events, so no prompts at launch.
+pub fn self_test(diagnostics: &PermissionDiagnostics, probe: &dyn PermissionProbe) -> DiagnosticsReport {
+    let report = diagnostics.run(probe, false);
+    log_report(&report);
+    report
+}
+
+pub fn spawn_self_test(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let state = app.state::<AppState>();
+        let report = self_test(&state.permissions, &SystemProbe::new(state.runner.as_ref()));
+        let _ = app.emit("permission-diagnostics", report);
+    });
+}
+
+#[tauri::command]
+pub async fn check_permissions(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    probe_automation: Option<bool>,
+) -> Result<DiagnosticsReport, AppError> {
+    let probe = SystemProbe::new(state.runner.as_ref());
+    let report = state.permissions.run(&probe, probe_automation.unwrap_or(false));
+    log_report(&report);
+    let _ = app.emit("permission-diagnostics", report.clone());
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn open_permission_settings(state: tauri::State<AppState>, permission: Permission) -> Result<(), AppError> {
+    if !cfg!(target_os = "macos") {
+        return Err(AppError::Unsupported(
+            "Permission settings only exist on macOS".to_string(),
+        ));
+    }
+    state
+        .runner
+        .spawn("open", &[settings_url(permission)])
+        .map_err(|e| AppError::io("Failed to open System Settings", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Answers from fixed states and records which apps were sent an Apple event.
+    struct FakeProbe {
+        screen: PermissionState,
+        // What the macOS prompt would return
+        requested: PermissionState,
+        automation: HashMap<&'static str, PermissionState>,
+        asked: Mutex<Vec<String>>,
+    }
+
+    impl FakeProbe {
+        fn new(screen: PermissionState, automation: &[(&'static str, PermissionState)]) -> Self {
+            Self {
+                screen,
+                requested: screen,
+                automation: automation.iter().copied().collect(),
+                asked: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn asked(&self) -> Vec<String> {
+            self.asked.lock().unwrap().clone()
+        }
+    }
+
+    impl PermissionProbe for FakeProbe {
+        fn screen_recording(&self) -> PermissionState {
+            self.screen
+        }
+
+        fn request_screen_recording(&self) -> PermissionState {
+            self.requested
+        }
+
+        fn has_automation(&self) -> bool {
+            !self.automation.is_empty()
+        }
+
+        fn automation(&self, target: &str) -> PermissionState {
+            self.asked.lock().unwrap().push(target.to_string());
+            self.automation.get(target).copied().unwrap_or_default()
+        }
+    }
+
+    fn check<'r>(report: &'r DiagnosticsReport, target: Option<&str>) -> &'r PermissionCheck {
+        report.checks.iter().find(|check| check.target.as_deref() == target).unwrap()
+    }
+
+    fn macos_probe(screen: PermissionState) -> FakeProbe {
+        FakeProbe::new(
+            screen,
+            &[("Finder", PermissionState::Denied), ("System Events", PermissionState::Granted)],
+        )
+    }
+
+    #[test]
+    fn platform_without_automation_only_reports_screen_recording() {
+        let probe = FakeProbe::new(PermissionState::NotApplicable, &[]);
+
+        let report = PermissionDiagnostics::default().run(&probe, true);
+
+        assert_eq!(report.checks.len(), 1);
+        assert_eq!(report.checks[0].permission, Permission::ScreenRecording);
+        assert_eq!(report.checks[0].state, PermissionState::NotApplicable);
+        assert!(report.ok);
+        assert!(probe.asked().is_empty());
+    }
+
+    #[test]
+    fn denied_screen_recording_fails_the_report_with_a_hint() {
+        let probe = FakeProbe::new(PermissionState::Denied, &[]);
+
+        let report = PermissionDiagnostics::default().run(&probe, false);
+
+        assert!(!report.ok);
+        assert_eq!(check(&report, None).hint, Some(screen_recording_hint()));
+    }
+
+    #[test]
+    fn probing_automation_asks_every_target_and_remembers_the_answer() {
+        let probe = macos_probe(PermissionState::Granted);
+        let diagnostics = PermissionDiagnostics::default();
+
+        let report = diagnostics.run(&probe, true);
+
+        assert_eq!(probe.asked(), AUTOMATION_TARGETS);
+        assert!(!report.ok);
+        let finder = check(&report, Some("Finder"));
+        assert_eq!(finder.state, PermissionState::Denied);
+        assert_eq!(finder.hint, Some(automation_hint("Finder")));
+        assert_eq!(check(&report, Some("System Events")).state, PermissionState::Granted);
+        assert_eq!(diagnostics.observed_automation("Finder"), PermissionState::Denied);
+        assert_eq!(diagnostics.observed_automation("System Events"), PermissionState::Granted);
+    }
+
+    #[test]
+    fn undetermined_probe_results_are_not_remembered() {
+        let probe = FakeProbe::new(PermissionState::Granted, &[("Finder", PermissionState::NotDetermined)]);
+        let diagnostics = PermissionDiagnostics::default();
+        diagnostics.record_automation("Finder", PermissionState::Granted);
+
+        diagnostics.run(&probe, true);
+
+        assert_eq!(diagnostics.observed_automation("Finder"), PermissionState::Granted);
+    }
+
+    #[test]
+    fn self_test_sends_no_apple_events_and_reports_observed_states() {
+        let probe = macos_probe(PermissionState::Granted);
+        let diagnostics = PermissionDiagnostics::default();
+
+        let report = self_test(&diagnostics, &probe);
+        assert!(probe.asked().is_empty());
+        assert!(report.ok);
+        assert_eq!(check(&report, Some("Finder")).state, PermissionState::NotDetermined);
+        assert_eq!(report.platform, std::env::consts::OS);
+
+        // A Finder script failed with -1743 since the last check
+        diagnostics.record_automation("Finder", PermissionState::Denied);
+        let report = self_test(&diagnostics, &probe);
+        assert!(probe.asked().is_empty());
+        assert!(!report.ok);
+        assert_eq!(check(&report, Some("Finder")).state, PermissionState::Denied);
+        assert_eq!(check(&report, Some("System Events")).state, PermissionState::NotDetermined);
+    }
+
+    #[test]
+    fn screen_recording_is_requested_once_before_refusing() {
+        let mut probe = FakeProbe::new(PermissionState::Denied, &[]);
+        probe.requested = PermissionState::Granted;
+        assert!(require_screen_recording(&probe).is_ok());
+
+        probe.requested = PermissionState::Denied;
+        assert_eq!(require_screen_recording(&probe).unwrap_err().kind(), "permissionDenied");
+
+        let probe = FakeProbe::new(PermissionState::NotApplicable, &[]);
+        assert!(require_screen_recording(&probe).is_ok());
+    }
+
+    #[test]
+    fn automation_denial_is_a_permission_error() {
+        let stderr = "execution error: Not authorized to send Apple events to Finder. (-1743)";
+        assert_eq!(osascript_error("Finder", stderr).kind(), "permissionDenied");
+        assert_eq!(osascript_error("Finder", "syntax error").kind(), "failed");
+    }
+}
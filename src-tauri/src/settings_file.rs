@@ -0,0 +1,305 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::filename_template;
+use crate::shortcuts;
+use crate::Settings;
+
+// On-disk settings. The file has a `version`. Older files are upgraded by
+// running `MIGRATIONS` in order and then saved back. Saving keeps keys this
+// build doesn't know, at any depth, so an older and a newer build can share one
+// file. A file that can't be read as settings is moved aside to a new `.bak`
+// file instead of being replaced with defaults.
+
+const VERSION_KEY: &str = "version";
+
+type Migration = fn(&mut Map<String, Value>);
+
+// `MIGRATIONS[n]` upgrades a version `n` file to `n + 1`. Files without a
+// version are version 0.
+const MIGRATIONS: &[Migration] = &[shortcut_bindings, filename_pattern];
+
+pub const CURRENT_VERSION: u64 = MIGRATIONS.len() as u64;
+
+// v1: one field per shortcut became a list of bindings. The old fields are left
+// in place for older builds.
+fn shortcut_bindings(object: &mut Map<String, Value>) {
+    if object.contains_key("shortcuts") {
+        return;
+    }
+    if let Ok(bindings) = serde_json::to_value(shortcuts::migrate_legacy_fields(object)) {
+        object.insert("shortcuts".to_string(), bindings);
+    }
+}
+
+// v2: the filename block list became a pattern. The blocks are left in place
+// for older builds.
+fn filename_pattern(object: &mut Map<String, Value>) {
+    if let Some(Value::Object(template)) = object.get_mut("filenameTemplate") {
+        filename_template::migrate_legacy_blocks(template);
+    }
+}
+
+fn file_version(object: &Map<String, Value>) -> u64 {
+    object.get(VERSION_KEY).and_then(Value::as_u64).unwrap_or(0)
+}
+
+// Runs the migrations the file hasn't had yet. Returns whether any ran.
+fn migrate(object: &mut Map<String, Value>) -> bool {
+    let from = file_version(object);
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        migration(object);
+        object.insert(VERSION_KEY.to_string(), Value::from(index as u64 + 1));
+    }
+    if from < CURRENT_VERSION {
+        println!("[settings] migrated from version {} to {}", from, CURRENT_VERSION);
+        return true;
+    }
+    false
+}
+
+// `<path>.bak`, then `<path>.1.bak`, `<path>.2.bak`...
+fn backup_path(path: &Path, index: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    if index > 0 {
+        name.push(format!(".{}", index));
+    }
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+// The first backup name for `path` that isn't taken, so earlier backups are kept.
+pub fn unused_backup_path(path: &Path) -> PathBuf {
+    (0..)
+        .map(|index| backup_path(path, index))
+        .find(|backup| !backup.exists())
+        .unwrap()
+}
+
+// Keeps a broken file for the user instead of letting the next save replace it.
+fn move_aside(path: &Path, reason: &str) {
+    let backup = unused_backup_path(path);
+    match std::fs::rename(path, &backup) {
+        Ok(()) => println!("[settings] {}; moved it to {}", reason, backup.display()),
+        Err(e) => println!("[settings] {}; failed to move it aside: {}", reason, e),
+    }
+}
+
+fn read_object(path: &Path) -> Option<Map<String, Value>> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub fn load(path: &Path) -> Settings {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Settings::default(),
+        Err(e) => {
+            println!("[settings] Failed to read {}: {}", path.display(), e);
+            return Settings::default();
+        }
+    };
+    let mut object = match serde_json::from_str::<Value>(&content) {
+        Ok(Value::Object(object)) => object,
+        Ok(_) => {
+            move_aside(path, "Settings file is not a JSON object");
+            return Settings::default();
+        }
+        Err(e) => {
+            move_aside(path, &format!("Settings file is not valid JSON: {}", e));
+            return Settings::default();
+        }
+    };
+
+    let migrated = migrate(&mut object);
+    match serde_json::from_value::<Settings>(Value::Object(object)) {
+        Ok(settings) => {
+            if migrated {
+                if let Err(e) = save(path, &settings) {
+                    println!("[settings] Failed to save migrated settings: {}", e);
+                }
+            }
+            settings
+        }
+        Err(e) => {
+            move_aside(path, &format!("Failed to parse settings: {}", e));
+            Settings::default()
+        }
+    }
+}
+
+// Copies `known` over `object`. Where both have an object, they are merged
+// instead, so keys only the file has survive in nested settings too.
+fn merge(object: &mut Map<String, Value>, known: Map<String, Value>) {
+    for (key, value) in known {
+        match (object.get_mut(&key), value) {
+            (Some(Value::Object(existing)), Value::Object(value)) => merge(existing, value),
+            (_, value) => {
+                object.insert(key, value);
+            }
+        }
+    }
+}
+
+// Writes `settings` over the file. Unknown keys are kept, and the version is
+// never lowered below one a newer build wrote.
+pub fn save(path: &Path, settings: &Settings) -> Result<(), String> {
+    let known = match serde_json::to_value(settings) {
+        Ok(Value::Object(known)) => known,
+        Ok(_) => return Err("Settings did not serialize to an object".to_string()),
+        Err(e) => return Err(format!("Failed to serialize settings: {}", e)),
+    };
+    let mut object = read_object(path).unwrap_or_default();
+    let version = file_version(&object).max(CURRENT_VERSION);
+    merge(&mut object, known);
+    object.insert(VERSION_KEY.to_string(), Value::from(version));
+
+    let content = serde_json::to_string_pretty(&object)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    // Write to a sibling temp file, then rename over the old one
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, content).map_err(|e| format!("Failed to write settings: {}", e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to write settings: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shortcuts::ShortcutAction;
+
+    fn scratch_file(name: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("screenshotapp-settings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        for index in 0..3 {
+            let _ = std::fs::remove_file(backup_path(&path, index));
+        }
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn unversioned_file_gets_shortcut_bindings() {
+        let path = scratch_file(
+            "legacy.json",
+            r#"{ "quality": 55, "maxWidth": 800, "areaShortcut": "Cmd+Shift+2" }"#,
+        );
+
+        let settings = load(&path);
+
+        assert_eq!(settings.quality, 55);
+        let area = settings
+            .shortcuts
+            .iter()
+            .find(|binding| binding.action == ShortcutAction::Area)
+            .unwrap();
+        assert_eq!(area.accelerator, "Cmd+Shift+2");
+        let saved = read_object(&path).unwrap();
+        assert_eq!(file_version(&saved), CURRENT_VERSION);
+        assert!(saved.contains_key("shortcuts"));
+        assert_eq!(saved["areaShortcut"], "Cmd+Shift+2");
+    }
+
+    #[test]
+    fn unknown_keys_and_newer_versions_survive_a_save() {
+        let path = scratch_file(
+            "newer.json",
+            r#"{ "version": 99, "quality": 70, "maxWidth": 1024, "futureOption": { "enabled": true } }"#,
+        );
+
+        let mut settings = load(&path);
+        settings.quality = 40;
+        save(&path, &settings).unwrap();
+
+        let saved = read_object(&path).unwrap();
+        assert_eq!(saved["quality"], 40);
+        assert_eq!(saved["futureOption"]["enabled"], true);
+        assert_eq!(file_version(&saved), 99);
+    }
+
+    #[test]
+    fn unparseable_file_is_moved_aside() {
+        let content = r#"{ "quality": "high", "maxWidth": 1024 }"#;
+        let path = scratch_file("broken.json", content);
+
+        let settings = load(&path);
+
+        assert_eq!(settings.quality, Settings::default().quality);
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_to_string(backup_path(&path, 0)).unwrap(), content);
+    }
+
+    #[test]
+    fn invalid_json_is_moved_aside() {
+        let path = scratch_file("truncated.json", r#"{ "quality": 70, "#);
+
+        load(&path);
+
+        assert!(!path.exists());
+        assert!(backup_path(&path, 0).exists());
+    }
+
+    #[test]
+    fn earlier_backups_are_not_overwritten() {
+        let path = scratch_file("broken-twice.json", "first");
+        load(&path);
+        std::fs::write(&path, "second").unwrap();
+        load(&path);
+
+        assert_eq!(std::fs::read_to_string(backup_path(&path, 0)).unwrap(), "first");
+        assert_eq!(std::fs::read_to_string(backup_path(&path, 1)).unwrap(), "second");
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn nested_unknown_keys_survive_a_save() {
+        let path = scratch_file(
+            "nested.json",
+            r#"{
+                "version": 2, "quality": 70, "maxWidth": 1024,
+                "output": { "directory": "~/Shots", "futureSubfolders": true },
+                "stitchLayout": { "futureBorder": { "width": 2 } }
+            }"#,
+        );
+
+        let mut settings = load(&path);
+        settings.output.directory = "~/Pictures".to_string();
+        save(&path, &settings).unwrap();
+
+        let saved = read_object(&path).unwrap();
+        assert_eq!(saved["output"]["directory"], "~/Pictures");
+        assert_eq!(saved["output"]["futureSubfolders"], true);
+        assert_eq!(saved["stitchLayout"]["futureBorder"]["width"], 2);
+        assert_eq!(saved["stitchLayout"]["gap"], settings.stitch_layout.gap);
+    }
+
+    #[test]
+    fn filename_blocks_become_a_pattern_and_stay_for_older_builds() {
+        let path = scratch_file(
+            "blocks.json",
+            r#"{
+                "version": 1, "quality": 70, "maxWidth": 1024,
+                "filenameTemplate": {
+                    "blocks": [
+                        { "id": "prefix", "enabled": true, "value": "old" },
+                        { "id": "dimensions", "enabled": true, "value": null }
+                    ],
+                    "useCounter": true
+                }
+            }"#,
+        );
+
+        let settings = load(&path);
+
+        assert_eq!(settings.filename_template.pattern, "{prefix}_{w}x{h}_{counter}");
+        assert_eq!(settings.filename_template.prefix, "old");
+        let saved = read_object(&path).unwrap();
+        assert_eq!(file_version(&saved), CURRENT_VERSION);
+        let template = &saved["filenameTemplate"];
+        assert_eq!(template["pattern"], "{prefix}_{w}x{h}_{counter}");
+        assert_eq!(template["blocks"][0]["value"], "old");
+        assert_eq!(template["useCounter"], true);
+    }
+}
@@ -0,0 +1,228 @@
+use serde::Serialize;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+
+use crate::error::AppError;
+use crate::shortcuts::{accelerator_to_display, parse_accelerator, CHORD_SEPARATOR};
+
+// Conflict analysis for a shortcut before it is saved. Three kinds:
+// - `reserved_by_os`: a system binding the OS takes before any app sees it.
+//   Registration still succeeds, so these come from a per-platform table.
+// - `taken`: another app already holds it; found by test-registering it.
+// - `duplicate`: it collides with another binding of ours.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    ReservedByOs,
+    Taken,
+    Duplicate,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShortcutConflict {
+    pub kind: ConflictKind,
+    // The conflicting step, normalized
+    pub step: String,
+    pub message: String,
+}
+
+#[cfg(target_os = "macos")]
+const RESERVED: &[(&str, &str)] = &[
+    ("Cmd+Shift+3", "screenshot of the screen"),
+    ("Cmd+Shift+4", "screenshot of an area"),
+    ("Cmd+Shift+5", "screenshot toolbar"),
+    ("Cmd+Shift+6", "Touch Bar screenshot"),
+    ("Cmd+Shift+Ctrl+3", "screen to clipboard"),
+    ("Cmd+Shift+Ctrl+4", "area to clipboard"),
+    ("Cmd+Space", "Spotlight"),
+    ("Cmd+Alt+Space", "Finder search"),
+    ("Cmd+Tab", "app switcher"),
+    ("Cmd+Shift+Tab", "app switcher"),
+    ("Cmd+`", "window switcher"),
+    ("Cmd+Q", "quit app"),
+    ("Cmd+W", "close window"),
+    ("Cmd+H", "hide app"),
+    ("Cmd+Alt+H", "hide others"),
+    ("Cmd+M", "minimize"),
+    ("Cmd+Alt+Escape", "force quit"),
+    ("Cmd+Ctrl+Q", "lock screen"),
+    ("Cmd+Ctrl+Space", "character viewer"),
+    ("Ctrl+Space", "input source"),
+    ("Ctrl+Up", "Mission Control"),
+    ("Ctrl+Down", "application windows"),
+    ("Ctrl+Left", "previous space"),
+    ("Ctrl+Right", "next space"),
+];
+
+#[cfg(target_os = "linux")]
+const RESERVED: &[(&str, &str)] = &[
+    ("PrintScreen", "screenshot"),
+    ("Shift+PrintScreen", "area screenshot"),
+    ("Alt+PrintScreen", "window screenshot"),
+    ("Ctrl+PrintScreen", "screenshot to clipboard"),
+    ("Alt+Tab", "window switcher"),
+    ("Alt+Shift+Tab", "window switcher"),
+    ("Alt+F2", "run command"),
+    ("Alt+F4", "close window"),
+    ("Cmd+L", "lock screen"),
+    ("Cmd+A", "app grid"),
+    ("Cmd+Tab", "app switcher"),
+    ("Cmd+Space", "input source"),
+    ("Ctrl+Alt+T", "terminal"),
+    ("Ctrl+Alt+Delete", "log out"),
+    ("Ctrl+Alt+Left", "previous workspace"),
+    ("Ctrl+Alt+Right", "next workspace"),
+    ("Cmd+Up", "maximize"),
+    ("Cmd+Down", "restore window"),
+];
+
+#[cfg(target_os = "windows")]
+const RESERVED: &[(&str, &str)] = &[
+    ("PrintScreen", "screenshot"),
+    ("Cmd+Shift+S", "Snipping Tool"),
+    ("Cmd+PrintScreen", "save screenshot"),
+    ("Alt+PrintScreen", "window screenshot"),
+    ("Alt+Tab", "app switcher"),
+    ("Alt+F4", "close window"),
+    ("Ctrl+Alt+Delete", "security screen"),
+    ("Cmd+L", "lock screen"),
+    ("Cmd+D", "show desktop"),
+    ("Cmd+E", "File Explorer"),
+    ("Cmd+R", "Run"),
+    ("Cmd+Tab", "task view"),
+    ("Cmd+V", "clipboard history"),
+];
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+const RESERVED: &[(&str, &str)] = &[];
+
+fn reserved_by_os(shortcut: &Shortcut) -> Option<&'static str> {
+    RESERVED.iter().find_map(|(accelerator, purpose)| {
+        let (_, steps) = parse_accelerator(accelerator).ok()?;
+        (steps[0].id() == shortcut.id()).then_some(*purpose)
+    })
+}
+
+// Try to register the step and drop it again. Steps this app already holds
+// are skipped; registering them again would fail for the wrong reason.
+fn taken_by_other_app(app: &tauri::AppHandle, shortcut: Shortcut) -> Option<String> {
+    let global_shortcut = app.global_shortcut();
+    if global_shortcut.is_registered(shortcut) {
+        return None;
+    }
+    match global_shortcut.register(shortcut) {
+        Ok(()) => {
+            let _ = global_shortcut.unregister(shortcut);
+            None
+        }
+        Err(e) => Some(e.to_string()),
+    }
+}
+
+// How two parsed accelerators collide: the same steps, or one is a single
+// step that starts the other's chord. Shared with `shortcuts::compile`.
+pub fn collision(accelerator: &str, steps: &[Shortcut], other: &[Shortcut]) -> Option<String> {
+    let ids: Vec<u32> = steps.iter().map(|s| s.id()).collect();
+    let other_ids: Vec<u32> = other.iter().map(|s| s.id()).collect();
+    if ids == other_ids {
+        return Some(format!("{} is used twice", accelerator_to_display(accelerator)));
+    }
+    if ids[0] == other_ids[0] && ids.len() != other_ids.len() {
+        return Some(format!(
+            "{} is both a shortcut and the start of a chord",
+            accelerator_to_display(accelerator)
+        ));
+    }
+    None
+}
+
+fn analyze(app: &tauri::AppHandle, accelerator: &str, others: &[String]) -> Result<Vec<ShortcutConflict>, AppError> {
+    let (normalized, steps) = parse_accelerator(accelerator).map_err(AppError::Invalid)?;
+    let step_names: Vec<&str> = normalized.split(CHORD_SEPARATOR).collect();
+    let mut conflicts = Vec::new();
+
+    for other in others {
+        // Other bindings were validated when they were saved
+        let Ok((_, other_steps)) = parse_accelerator(other) else {
+            continue;
+        };
+        if let Some(message) = collision(&normalized, &steps, &other_steps) {
+            conflicts.push(ShortcutConflict {
+                kind: ConflictKind::Duplicate,
+                step: normalized.clone(),
+                message,
+            });
+        }
+    }
+
+    for (shortcut, name) in steps.iter().zip(step_names) {
+        if let Some(purpose) = reserved_by_os(shortcut) {
+            conflicts.push(ShortcutConflict {
+                kind: ConflictKind::ReservedByOs,
+                step: name.to_string(),
+                message: format!("{} is reserved by the system ({})", accelerator_to_display(name), purpose),
+            });
+        }
+        if let Some(reason) = taken_by_other_app(app, *shortcut) {
+            println!("[shortcuts] {} is taken: {}", name, reason);
+            conflicts.push(ShortcutConflict {
+                kind: ConflictKind::Taken,
+                step: name.to_string(),
+                message: format!("{} is already used by another app", accelerator_to_display(name)),
+            });
+        }
+    }
+
+    Ok(conflicts)
+}
+
+#[tauri::command]
+pub async fn check_shortcut_conflicts(
+    app: tauri::AppHandle,
+    accelerator: String,
+    others: Vec<String>,
+) -> Result<Vec<ShortcutConflict>, AppError> {
+    analyze(&app, &accelerator, &others)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(accelerator: &str) -> Vec<Shortcut> {
+        parse_accelerator(accelerator).unwrap().1
+    }
+
+    #[test]
+    fn every_reserved_shortcut_parses() {
+        for (accelerator, purpose) in RESERVED {
+            let (_, parsed) = parse_accelerator(accelerator)
+                .unwrap_or_else(|e| panic!("{} ({}) does not parse: {}", accelerator, purpose, e));
+            assert_eq!(reserved_by_os(&parsed[0]), Some(*purpose), "{}", accelerator);
+        }
+    }
+
+    #[test]
+    fn same_binding_twice_collides() {
+        let message = collision("Ctrl+Alt+S", &steps("Ctrl+Alt+S"), &steps("Alt+Ctrl+S")).unwrap();
+        assert!(message.contains("used twice"), "{}", message);
+
+        assert!(collision("Ctrl+K, A", &steps("Ctrl+K, A"), &steps("Ctrl+K, A")).is_some());
+    }
+
+    #[test]
+    fn single_step_that_starts_a_chord_collides() {
+        let message = collision("Ctrl+K", &steps("Ctrl+K"), &steps("Ctrl+K, A")).unwrap();
+        assert!(message.contains("start of a chord"), "{}", message);
+
+        assert!(collision("Ctrl+K, A", &steps("Ctrl+K, A"), &steps("Ctrl+K")).is_some());
+    }
+
+    #[test]
+    fn chords_sharing_a_prefix_do_not_collide() {
+        assert_eq!(collision("Ctrl+K, A", &steps("Ctrl+K, A"), &steps("Ctrl+K, B")), None);
+        assert_eq!(collision("Ctrl+K", &steps("Ctrl+K"), &steps("Ctrl+J")), None);
+        assert_eq!(collision("Ctrl+K, A", &steps("Ctrl+K, A"), &steps("Ctrl+J, A")), None);
+    }
+}
@@ -0,0 +1,1124 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
+
+use crate::capture_job::{spawn_capture_job, CaptureMode};
+use crate::error::AppError;
+use crate::{open_history_window, save_settings_to_file, shortcut_conflicts, stitch_session, update_tray_labels, AppState};
+
+// Global shortcuts. Settings hold a list of bindings from an accelerator to an
+// action. The first step of every accelerator is registered with the OS; a
+// chord such as `Ctrl+Alt+S, A` registers its second keys only while it waits
+// for them, and gives up after `CHORD_TIMEOUT`.
+
+const CHORD_TIMEOUT: Duration = Duration::from_millis(1500);
+pub const CHORD_SEPARATOR: &str = ", ";
+
+static CHORD_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ShortcutAction {
+    Area,
+    Fullscreen,
+    ActiveWindow,
+    RepeatRegion,
+    ClipboardImport,
+    StitchSession,
+    StitchSelection,
+    OpenHistory,
+    ShowApp,
+}
+
+impl ShortcutAction {
+    // Tray order
+    pub const ALL: [ShortcutAction; 9] = [
+        ShortcutAction::Fullscreen,
+        ShortcutAction::Area,
+        ShortcutAction::ActiveWindow,
+        ShortcutAction::RepeatRegion,
+        ShortcutAction::ClipboardImport,
+        ShortcutAction::StitchSession,
+        ShortcutAction::StitchSelection,
+        ShortcutAction::OpenHistory,
+        ShortcutAction::ShowApp,
+    ];
+
+    // Tray menu item id
+    pub fn id(self) -> &'static str {
+        match self {
+            ShortcutAction::Area => "screenshot",
+            ShortcutAction::Fullscreen => "fullscreen",
+            ShortcutAction::ActiveWindow => "active-window",
+            ShortcutAction::RepeatRegion => "repeat-region",
+            ShortcutAction::ClipboardImport => "new-from-clipboard",
+            ShortcutAction::StitchSession => "stitch-session",
+            ShortcutAction::StitchSelection => "stitch",
+            ShortcutAction::OpenHistory => "history",
+            ShortcutAction::ShowApp => "show",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            ShortcutAction::Area => "Screenshot Area",
+            ShortcutAction::Fullscreen => "Screenshot Full",
+            ShortcutAction::ActiveWindow => "Screenshot Active Window",
+            ShortcutAction::RepeatRegion => "Repeat Last Area",
+            ShortcutAction::ClipboardImport => "New from Clipboard",
+            ShortcutAction::StitchSession => "Start/Finish Stitch Session",
+            ShortcutAction::StitchSelection => "Stitch Selected Images",
+            ShortcutAction::OpenHistory => "History",
+            ShortcutAction::ShowApp => "Show App",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShortcutBinding {
+    pub action: ShortcutAction,
+    // One step (`Cmd+Shift+4`, `F13`) or a two-step chord (`Ctrl+Alt+S, A`)
+    pub accelerator: String,
+}
+
+pub fn default_bindings() -> Vec<ShortcutBinding> {
+    [
+        (ShortcutAction::Fullscreen, "Cmd+Shift+3"),
+        (ShortcutAction::Area, "Cmd+Shift+4"),
+        (ShortcutAction::StitchSession, "Cmd+Shift+2"),
+        (ShortcutAction::ClipboardImport, "Cmd+Shift+1"),
+    ]
+    .into_iter()
+    .map(|(action, accelerator)| ShortcutBinding {
+        action,
+        accelerator: accelerator.to_string(),
+    })
+    .collect()
+}
+
+// Settings files from before the bindings list have one field per action.
+// Missing fields fall back to that action's default.
+pub fn migrate_legacy_fields(value: &serde_json::Map<String, serde_json::Value>) -> Vec<ShortcutBinding> {
+    let legacy = [
+        ("fullscreenShortcut", ShortcutAction::Fullscreen),
+        ("areaShortcut", ShortcutAction::Area),
+        ("stitchShortcut", ShortcutAction::StitchSession),
+        ("clipboardShortcut", ShortcutAction::ClipboardImport),
+    ];
+    let defaults = default_bindings();
+    legacy
+        .into_iter()
+        .filter_map(|(field, action)| {
+            let accelerator = value
+                .get(field)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .or_else(|| defaults.iter().find(|b| b.action == action).map(|b| b.accelerator.clone()))?;
+            Some(ShortcutBinding { action, accelerator })
+        })
+        .collect()
+}
+
+pub fn display_for(bindings: &[ShortcutBinding], action: ShortcutAction) -> Option<String> {
+    bindings
+        .iter()
+        .find(|binding| binding.action == action)
+        .map(|binding| accelerator_to_display(&binding.accelerator))
+}
+
+#[derive(Clone, Copy)]
+struct ShortcutParts {
+    modifiers: Modifiers,
+    key: Code,
+}
+
+// Keys that are safe to register without a modifier: nothing types them.
+fn allows_bare_key(code: Code) -> bool {
+    matches!(
+        code,
+        Code::F13
+            | Code::F14
+            | Code::F15
+            | Code::F16
+            | Code::F17
+            | Code::F18
+            | Code::F19
+            | Code::F20
+            | Code::F21
+            | Code::F22
+            | Code::F23
+            | Code::F24
+            | Code::Numpad0
+            | Code::Numpad1
+            | Code::Numpad2
+            | Code::Numpad3
+            | Code::Numpad4
+            | Code::Numpad5
+            | Code::Numpad6
+            | Code::Numpad7
+            | Code::Numpad8
+            | Code::Numpad9
+            | Code::NumpadAdd
+            | Code::NumpadSubtract
+            | Code::NumpadMultiply
+            | Code::NumpadDivide
+            | Code::NumpadDecimal
+            | Code::NumpadEnter
+            | Code::NumpadEqual
+            | Code::PrintScreen
+            | Code::MediaPlayPause
+            | Code::MediaStop
+            | Code::MediaTrackNext
+            | Code::MediaTrackPrevious
+            | Code::AudioVolumeUp
+            | Code::AudioVolumeDown
+            | Code::AudioVolumeMute
+    )
+}
+
+// The second step of a chord may be a bare key: it is only registered while the chord waits.
+fn parse_shortcut_parts(shortcut_str: &str, second_step: bool) -> Result<ShortcutParts, String> {
+    let mut modifiers = Modifiers::empty();
+    let mut key_code: Option<Code> = None;
+
+    for part in shortcut_str.split('+') {
+        let token = part.trim();
+        if token.is_empty() {
+            return Err("Shortcut contains an empty token".to_string());
+        }
+
+        match token.to_lowercase().as_str() {
+            "cmd" | "command" | "super" | "meta" => modifiers |= Modifiers::SUPER,
+            "shift" => modifiers |= Modifiers::SHIFT,
+            "alt" | "option" => modifiers |= Modifiers::ALT,
+            "ctrl" | "control" => modifiers |= Modifiers::CONTROL,
+            _ => {
+                if key_code.is_some() {
+                    return Err("Shortcut must have only one main key".to_string());
+                }
+                key_code = Some(string_to_code(token)?);
+            }
+        }
+    }
+
+    let code = key_code.ok_or("No key specified in shortcut")?;
+    if modifiers.is_empty() && !second_step && !allows_bare_key(code) {
+        return Err("At least one modifier required (except for F13-F24, numpad and media keys)".to_string());
+    }
+
+    Ok(ShortcutParts { modifiers, key: code })
+}
+
+// Parse an accelerator into its normalized form and one shortcut per step.
+pub fn parse_accelerator(accelerator: &str) -> Result<(String, Vec<Shortcut>), String> {
+    let steps: Vec<&str> = accelerator.split(CHORD_SEPARATOR).collect();
+    if steps.len() > 2 {
+        return Err("Chords can have at most two steps".to_string());
+    }
+    let mut normalized = Vec::new();
+    let mut shortcuts = Vec::new();
+    for (i, step) in steps.iter().enumerate() {
+        let parts = parse_shortcut_parts(step, i > 0)?;
+        normalized.push(shortcut_parts_to_string(&parts)?);
+        shortcuts.push(Shortcut::new(Some(parts.modifiers), parts.key));
+    }
+    Ok((normalized.join(CHORD_SEPARATOR), shortcuts))
+}
+
+fn string_to_code(s: &str) -> Result<Code, String> {
+    match s.to_lowercase().as_str() {
+        "0" | "digit0" => Ok(Code::Digit0),
+        "1" | "digit1" => Ok(Code::Digit1),
+        "2" | "digit2" => Ok(Code::Digit2),
+        "3" | "digit3" => Ok(Code::Digit3),
+        "4" | "digit4" => Ok(Code::Digit4),
+        "5" | "digit5" => Ok(Code::Digit5),
+        "6" | "digit6" => Ok(Code::Digit6),
+        "7" | "digit7" => Ok(Code::Digit7),
+        "8" | "digit8" => Ok(Code::Digit8),
+        "9" | "digit9" => Ok(Code::Digit9),
+        "a" | "keya" => Ok(Code::KeyA),
+        "b" | "keyb" => Ok(Code::KeyB),
+        "c" | "keyc" => Ok(Code::KeyC),
+        "d" | "keyd" => Ok(Code::KeyD),
+        "e" | "keye" => Ok(Code::KeyE),
+        "f" | "keyf" => Ok(Code::KeyF),
+        "g" | "keyg" => Ok(Code::KeyG),
+        "h" | "keyh" => Ok(Code::KeyH),
+        "i" | "keyi" => Ok(Code::KeyI),
+        "j" | "keyj" => Ok(Code::KeyJ),
+        "k" | "keyk" => Ok(Code::KeyK),
+        "l" | "keyl" => Ok(Code::KeyL),
+        "m" | "keym" => Ok(Code::KeyM),
+        "n" | "keyn" => Ok(Code::KeyN),
+        "o" | "keyo" => Ok(Code::KeyO),
+        "p" | "keyp" => Ok(Code::KeyP),
+        "q" | "keyq" => Ok(Code::KeyQ),
+        "r" | "keyr" => Ok(Code::KeyR),
+        "s" | "keys" => Ok(Code::KeyS),
+        "t" | "keyt" => Ok(Code::KeyT),
+        "u" | "keyu" => Ok(Code::KeyU),
+        "v" | "keyv" => Ok(Code::KeyV),
+        "w" | "keyw" => Ok(Code::KeyW),
+        "x" | "keyx" => Ok(Code::KeyX),
+        "y" | "keyy" => Ok(Code::KeyY),
+        "z" | "keyz" => Ok(Code::KeyZ),
+        "f1" => Ok(Code::F1),
+        "f2" => Ok(Code::F2),
+        "f3" => Ok(Code::F3),
+        "f4" => Ok(Code::F4),
+        "f5" => Ok(Code::F5),
+        "f6" => Ok(Code::F6),
+        "f7" => Ok(Code::F7),
+        "f8" => Ok(Code::F8),
+        "f9" => Ok(Code::F9),
+        "f10" => Ok(Code::F10),
+        "f11" => Ok(Code::F11),
+        "f12" => Ok(Code::F12),
+        "space" => Ok(Code::Space),
+        "enter" => Ok(Code::Enter),
+        "tab" => Ok(Code::Tab),
+        "escape" | "esc" => Ok(Code::Escape),
+        "backspace" => Ok(Code::Backspace),
+        "minus" | "-" => Ok(Code::Minus),
+        "equal" | "equals" | "=" => Ok(Code::Equal),
+        "bracketleft" | "lbracket" | "[" => Ok(Code::BracketLeft),
+        "bracketright" | "rbracket" | "]" => Ok(Code::BracketRight),
+        "semicolon" | ";" => Ok(Code::Semicolon),
+        "quote" | "'" => Ok(Code::Quote),
+        "comma" | "," => Ok(Code::Comma),
+        "period" | "." => Ok(Code::Period),
+        "slash" | "/" => Ok(Code::Slash),
+        "backslash" | "\\" => Ok(Code::Backslash),
+        "intlbackslash" => Ok(Code::IntlBackslash),
+        "backquote" | "grave" | "`" => Ok(Code::Backquote),
+        "f13" => Ok(Code::F13),
+        "f14" => Ok(Code::F14),
+        "f15" => Ok(Code::F15),
+        "f16" => Ok(Code::F16),
+        "f17" => Ok(Code::F17),
+        "f18" => Ok(Code::F18),
+        "f19" => Ok(Code::F19),
+        "f20" => Ok(Code::F20),
+        "f21" => Ok(Code::F21),
+        "f22" => Ok(Code::F22),
+        "f23" => Ok(Code::F23),
+        "f24" => Ok(Code::F24),
+        "numpad0" | "num0" => Ok(Code::Numpad0),
+        "numpad1" | "num1" => Ok(Code::Numpad1),
+        "numpad2" | "num2" => Ok(Code::Numpad2),
+        "numpad3" | "num3" => Ok(Code::Numpad3),
+        "numpad4" | "num4" => Ok(Code::Numpad4),
+        "numpad5" | "num5" => Ok(Code::Numpad5),
+        "numpad6" | "num6" => Ok(Code::Numpad6),
+        "numpad7" | "num7" => Ok(Code::Numpad7),
+        "numpad8" | "num8" => Ok(Code::Numpad8),
+        "numpad9" | "num9" => Ok(Code::Numpad9),
+        "numpadadd" | "numadd" => Ok(Code::NumpadAdd),
+        "numpadsubtract" | "numsubtract" => Ok(Code::NumpadSubtract),
+        "numpadmultiply" | "nummultiply" => Ok(Code::NumpadMultiply),
+        "numpaddivide" | "numdivide" => Ok(Code::NumpadDivide),
+        "numpaddecimal" | "numdecimal" => Ok(Code::NumpadDecimal),
+        "numpadenter" | "numenter" => Ok(Code::NumpadEnter),
+        "numpadequal" | "numequal" => Ok(Code::NumpadEqual),
+        "arrowup" | "up" => Ok(Code::ArrowUp),
+        "arrowdown" | "down" => Ok(Code::ArrowDown),
+        "arrowleft" | "left" => Ok(Code::ArrowLeft),
+        "arrowright" | "right" => Ok(Code::ArrowRight),
+        "home" => Ok(Code::Home),
+        "end" => Ok(Code::End),
+        "pageup" => Ok(Code::PageUp),
+        "pagedown" => Ok(Code::PageDown),
+        "insert" => Ok(Code::Insert),
+        "delete" => Ok(Code::Delete),
+        "printscreen" => Ok(Code::PrintScreen),
+        "mediaplaypause" => Ok(Code::MediaPlayPause),
+        "mediastop" => Ok(Code::MediaStop),
+        "mediatracknext" | "medianext" => Ok(Code::MediaTrackNext),
+        "mediatrackprevious" | "mediaprevious" => Ok(Code::MediaTrackPrevious),
+        "audiovolumeup" | "volumeup" => Ok(Code::AudioVolumeUp),
+        "audiovolumedown" | "volumedown" => Ok(Code::AudioVolumeDown),
+        "audiovolumemute" | "volumemute" => Ok(Code::AudioVolumeMute),
+        _ => Err(format!("Unknown key: {}", s)),
+    }
+}
+
+fn code_to_string(code: Code) -> Result<String, String> {
+    match code {
+        Code::Digit0 => Ok("0".to_string()),
+        Code::Digit1 => Ok("1".to_string()),
+        Code::Digit2 => Ok("2".to_string()),
+        Code::Digit3 => Ok("3".to_string()),
+        Code::Digit4 => Ok("4".to_string()),
+        Code::Digit5 => Ok("5".to_string()),
+        Code::Digit6 => Ok("6".to_string()),
+        Code::Digit7 => Ok("7".to_string()),
+        Code::Digit8 => Ok("8".to_string()),
+        Code::Digit9 => Ok("9".to_string()),
+        Code::KeyA => Ok("A".to_string()),
+        Code::KeyB => Ok("B".to_string()),
+        Code::KeyC => Ok("C".to_string()),
+        Code::KeyD => Ok("D".to_string()),
+        Code::KeyE => Ok("E".to_string()),
+        Code::KeyF => Ok("F".to_string()),
+        Code::KeyG => Ok("G".to_string()),
+        Code::KeyH => Ok("H".to_string()),
+        Code::KeyI => Ok("I".to_string()),
+        Code::KeyJ => Ok("J".to_string()),
+        Code::KeyK => Ok("K".to_string()),
+        Code::KeyL => Ok("L".to_string()),
+        Code::KeyM => Ok("M".to_string()),
+        Code::KeyN => Ok("N".to_string()),
+        Code::KeyO => Ok("O".to_string()),
+        Code::KeyP => Ok("P".to_string()),
+        Code::KeyQ => Ok("Q".to_string()),
+        Code::KeyR => Ok("R".to_string()),
+        Code::KeyS => Ok("S".to_string()),
+        Code::KeyT => Ok("T".to_string()),
+        Code::KeyU => Ok("U".to_string()),
+        Code::KeyV => Ok("V".to_string()),
+        Code::KeyW => Ok("W".to_string()),
+        Code::KeyX => Ok("X".to_string()),
+        Code::KeyY => Ok("Y".to_string()),
+        Code::KeyZ => Ok("Z".to_string()),
+        Code::F1 => Ok("F1".to_string()),
+        Code::F2 => Ok("F2".to_string()),
+        Code::F3 => Ok("F3".to_string()),
+        Code::F4 => Ok("F4".to_string()),
+        Code::F5 => Ok("F5".to_string()),
+        Code::F6 => Ok("F6".to_string()),
+        Code::F7 => Ok("F7".to_string()),
+        Code::F8 => Ok("F8".to_string()),
+        Code::F9 => Ok("F9".to_string()),
+        Code::F10 => Ok("F10".to_string()),
+        Code::F11 => Ok("F11".to_string()),
+        Code::F12 => Ok("F12".to_string()),
+        Code::Space => Ok("Space".to_string()),
+        Code::Enter => Ok("Enter".to_string()),
+        Code::Tab => Ok("Tab".to_string()),
+        Code::Escape => Ok("Escape".to_string()),
+        Code::Backspace => Ok("Backspace".to_string()),
+        Code::Minus => Ok("-".to_string()),
+        Code::Equal => Ok("=".to_string()),
+        Code::BracketLeft => Ok("[".to_string()),
+        Code::BracketRight => Ok("]".to_string()),
+        Code::Semicolon => Ok(";".to_string()),
+        Code::Quote => Ok("'".to_string()),
+        Code::Comma => Ok(",".to_string()),
+        Code::Period => Ok(".".to_string()),
+        Code::Slash => Ok("/".to_string()),
+        Code::Backslash => Ok("\\".to_string()),
+        Code::IntlBackslash => Ok("\\".to_string()),
+        Code::Backquote => Ok("`".to_string()),
+        Code::F13 => Ok("F13".to_string()),
+        Code::F14 => Ok("F14".to_string()),
+        Code::F15 => Ok("F15".to_string()),
+        Code::F16 => Ok("F16".to_string()),
+        Code::F17 => Ok("F17".to_string()),
+        Code::F18 => Ok("F18".to_string()),
+        Code::F19 => Ok("F19".to_string()),
+        Code::F20 => Ok("F20".to_string()),
+        Code::F21 => Ok("F21".to_string()),
+        Code::F22 => Ok("F22".to_string()),
+        Code::F23 => Ok("F23".to_string()),
+        Code::F24 => Ok("F24".to_string()),
+        Code::Numpad0 => Ok("Numpad0".to_string()),
+        Code::Numpad1 => Ok("Numpad1".to_string()),
+        Code::Numpad2 => Ok("Numpad2".to_string()),
+        Code::Numpad3 => Ok("Numpad3".to_string()),
+        Code::Numpad4 => Ok("Numpad4".to_string()),
+        Code::Numpad5 => Ok("Numpad5".to_string()),
+        Code::Numpad6 => Ok("Numpad6".to_string()),
+        Code::Numpad7 => Ok("Numpad7".to_string()),
+        Code::Numpad8 => Ok("Numpad8".to_string()),
+        Code::Numpad9 => Ok("Numpad9".to_string()),
+        Code::NumpadAdd => Ok("NumpadAdd".to_string()),
+        Code::NumpadSubtract => Ok("NumpadSubtract".to_string()),
+        Code::NumpadMultiply => Ok("NumpadMultiply".to_string()),
+        Code::NumpadDivide => Ok("NumpadDivide".to_string()),
+        Code::NumpadDecimal => Ok("NumpadDecimal".to_string()),
+        Code::NumpadEnter => Ok("NumpadEnter".to_string()),
+        Code::NumpadEqual => Ok("NumpadEqual".to_string()),
+        Code::ArrowUp => Ok("Up".to_string()),
+        Code::ArrowDown => Ok("Down".to_string()),
+        Code::ArrowLeft => Ok("Left".to_string()),
+        Code::ArrowRight => Ok("Right".to_string()),
+        Code::Home => Ok("Home".to_string()),
+        Code::End => Ok("End".to_string()),
+        Code::PageUp => Ok("PageUp".to_string()),
+        Code::PageDown => Ok("PageDown".to_string()),
+        Code::Insert => Ok("Insert".to_string()),
+        Code::Delete => Ok("Delete".to_string()),
+        Code::PrintScreen => Ok("PrintScreen".to_string()),
+        Code::MediaPlayPause => Ok("MediaPlayPause".to_string()),
+        Code::MediaStop => Ok("MediaStop".to_string()),
+        Code::MediaTrackNext => Ok("MediaNext".to_string()),
+        Code::MediaTrackPrevious => Ok("MediaPrevious".to_string()),
+        Code::AudioVolumeUp => Ok("VolumeUp".to_string()),
+        Code::AudioVolumeDown => Ok("VolumeDown".to_string()),
+        Code::AudioVolumeMute => Ok("VolumeMute".to_string()),
+        _ => Err(format!("Unsupported key: {:?}", code)),
+    }
+}
+
+fn shortcut_parts_to_string(parts: &ShortcutParts) -> Result<String, String> {
+    let mut tokens = Vec::new();
+
+    if parts.modifiers.contains(Modifiers::SUPER) {
+        tokens.push("Cmd".to_string());
+    }
+    if parts.modifiers.contains(Modifiers::SHIFT) {
+        tokens.push("Shift".to_string());
+    }
+    if parts.modifiers.contains(Modifiers::ALT) {
+        tokens.push("Alt".to_string());
+    }
+    if parts.modifiers.contains(Modifiers::CONTROL) {
+        tokens.push("Ctrl".to_string());
+    }
+
+    tokens.push(code_to_string(parts.key)?);
+    Ok(tokens.join("+"))
+}
+
+pub fn accelerator_to_display(accelerator: &str) -> String {
+    let normalized = parse_accelerator(accelerator)
+        .map(|(normalized, _)| normalized)
+        .unwrap_or_else(|_| accelerator.to_string());
+    normalized
+        .split(CHORD_SEPARATOR)
+        .map(|step| {
+            step.replace("Cmd", "⌘")
+                .replace("Shift", "⇧")
+                .replace("Alt", "⌥")
+                .replace("Ctrl", "⌃")
+                .replace("+", "")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug, Clone)]
+struct CompiledBinding {
+    action: ShortcutAction,
+    accelerator: String,
+    steps: Vec<Shortcut>,
+}
+
+impl CompiledBinding {
+    fn is_chord(&self) -> bool {
+        self.steps.len() > 1
+    }
+}
+
+// Normalize and validate bindings: every accelerator parses, none is bound twice,
+// and no single-step shortcut is also the first step of a chord.
+fn compile(bindings: &[ShortcutBinding]) -> Result<Vec<CompiledBinding>, String> {
+    let mut compiled: Vec<CompiledBinding> = Vec::new();
+    for binding in bindings {
+        let (accelerator, steps) = parse_accelerator(&binding.accelerator)
+            .map_err(|e| format!("{} shortcut: {}", binding.action.title(), e))?;
+        for other in &compiled {
+            if let Some(message) = shortcut_conflicts::collision(&accelerator, &steps, &other.steps) {
+                return Err(message);
+            }
+        }
+        compiled.push(CompiledBinding {
+            action: binding.action,
+            accelerator,
+            steps,
+        });
+    }
+    Ok(compiled)
+}
+
+// Shortcuts registered with the OS: each binding's first step, once.
+fn first_steps(bindings: &[CompiledBinding]) -> Vec<(Shortcut, &CompiledBinding)> {
+    let mut steps: Vec<(Shortcut, &CompiledBinding)> = Vec::new();
+    for binding in bindings {
+        if !steps.iter().any(|(s, _)| s.id() == binding.steps[0].id()) {
+            steps.push((binding.steps[0], binding));
+        }
+    }
+    steps
+}
+
+struct PendingChord {
+    prefix: u32,
+    // Second steps registered only for this chord
+    temporary: Vec<Shortcut>,
+    generation: u64,
+}
+
+#[derive(Default)]
+struct RegistryState {
+    bindings: Vec<CompiledBinding>,
+    chord: Option<PendingChord>,
+}
+
+// What a key press resolved to.
+#[derive(Default)]
+struct Press {
+    action: Option<ShortcutAction>,
+    // Temporary chord keys to unregister
+    release: Vec<Shortcut>,
+    // Chord keys to register, with the chord's generation
+    start_chord: Option<(Vec<Shortcut>, u64)>,
+}
+
+// Chord key changes, applied in order by the chord worker.
+enum ChordWork {
+    Release(Vec<Shortcut>),
+    Start { keys: Vec<Shortcut>, generation: u64 },
+}
+
+#[derive(Default)]
+pub struct ShortcutRegistry {
+    state: Mutex<RegistryState>,
+    // Started on the first chord key change
+    worker: Mutex<Option<Sender<ChordWork>>>,
+}
+
+impl ShortcutRegistry {
+    fn bindings(&self) -> Vec<CompiledBinding> {
+        self.state.lock().unwrap().bindings.clone()
+    }
+
+    fn press(&self, id: u32) -> Press {
+        let mut state = self.state.lock().unwrap();
+        let mut press = Press::default();
+
+        if let Some(chord) = state.chord.take() {
+            press.release = chord.temporary;
+            press.action = state
+                .bindings
+                .iter()
+                .find(|b| b.is_chord() && b.steps[0].id() == chord.prefix && b.steps[1].id() == id)
+                .map(|b| b.action);
+            if press.action.is_some() {
+                return press;
+            }
+            // Any other key ends the chord and is then handled as usual
+        }
+
+        if let Some(binding) = state.bindings.iter().find(|b| !b.is_chord() && b.steps[0].id() == id) {
+            press.action = Some(binding.action);
+            return press;
+        }
+
+        let seconds: Vec<Shortcut> = state
+            .bindings
+            .iter()
+            .filter(|b| b.is_chord() && b.steps[0].id() == id)
+            .map(|b| b.steps[1])
+            .collect();
+        if seconds.is_empty() {
+            return press;
+        }
+        // Second steps that are already registered as first steps stay as they are
+        let registered = first_steps(&state.bindings);
+        let temporary: Vec<Shortcut> = seconds
+            .into_iter()
+            .filter(|s| !registered.iter().any(|(r, _)| r.id() == s.id()))
+            .collect();
+        let generation = CHORD_GENERATION.fetch_add(1, Ordering::Relaxed);
+        state.chord = Some(PendingChord {
+            prefix: id,
+            temporary: temporary.clone(),
+            generation,
+        });
+        press.start_chord = Some((temporary, generation));
+        press
+    }
+
+    fn is_waiting(&self, generation: u64) -> bool {
+        self.state.lock().unwrap().chord.as_ref().map(|c| c.generation) == Some(generation)
+    }
+
+    // Called after the timeout; returns the keys to unregister if the chord is still waiting.
+    fn expire(&self, generation: u64) -> Option<Vec<Shortcut>> {
+        let mut state = self.state.lock().unwrap();
+        if state.chord.as_ref().map(|c| c.generation) != Some(generation) {
+            return None;
+        }
+        state.chord.take().map(|chord| chord.temporary)
+    }
+
+    // Queues chord key changes for the worker thread, starting it on first use.
+    fn send(&self, app: &tauri::AppHandle, work: ChordWork) {
+        let mut worker = self.worker.lock().unwrap();
+        let sender = worker.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel();
+            let app = app.clone();
+            std::thread::spawn(move || {
+                let state = app.state::<AppState>();
+                run_chord_worker(&receiver, &state.shortcuts, &PluginKeys(&app), CHORD_TIMEOUT);
+            });
+            sender
+        });
+        let _ = sender.send(work);
+    }
+
+    fn replace(&self, bindings: Vec<CompiledBinding>) -> Vec<Shortcut> {
+        let mut state = self.state.lock().unwrap();
+        state.bindings = bindings;
+        state.chord.take().map(|chord| chord.temporary).unwrap_or_default()
+    }
+}
+
+// Swap the registered shortcuts from `old` to `new` as one step: if any of the
+// new ones fails to register, the ones already registered are dropped and the
+// old set is registered again.
+fn register_swap(app: &tauri::AppHandle, old: &[CompiledBinding], new: &[CompiledBinding]) -> Result<(), String> {
+    let global_shortcut = app.global_shortcut();
+    let old_steps = first_steps(old);
+    let new_steps = first_steps(new);
+
+    for (shortcut, _) in &old_steps {
+        let _ = global_shortcut.unregister(*shortcut);
+    }
+    for (i, (shortcut, binding)) in new_steps.iter().enumerate() {
+        if let Err(e) = global_shortcut.register(*shortcut) {
+            for (registered, _) in &new_steps[..i] {
+                let _ = global_shortcut.unregister(*registered);
+            }
+            for (previous, _) in &old_steps {
+                let _ = global_shortcut.register(*previous);
+            }
+            return Err(format!(
+                "Failed to register {} for {}: {}",
+                accelerator_to_display(&binding.accelerator),
+                binding.action.title(),
+                e
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Registers and unregisters chord keys with the OS.
+trait ChordKeys {
+    fn register(&self, key: Shortcut);
+    fn unregister(&self, key: Shortcut);
+}
+
+struct PluginKeys<'a>(&'a tauri::AppHandle);
+
+impl ChordKeys for PluginKeys<'_> {
+    fn register(&self, key: Shortcut) {
+        if let Err(e) = self.0.global_shortcut().register(key) {
+            println!("[shortcuts] Failed to register chord key: {}", e);
+        }
+    }
+
+    fn unregister(&self, key: Shortcut) {
+        let _ = self.0.global_shortcut().unregister(key);
+    }
+}
+
+// Every chord key change runs here, one at a time and in the order the presses
+// happened, so a release can't overtake the register it undoes. A chord that
+// ended before its keys got registered is skipped.
+fn run_chord_worker(work: &Receiver<ChordWork>, registry: &ShortcutRegistry, keys: &dyn ChordKeys, timeout: Duration) {
+    // Generation and deadline of the chord whose keys are registered
+    let mut waiting: Option<(u64, Instant)> = None;
+    loop {
+        let next = match waiting {
+            Some((_, deadline)) => work.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => work.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match next {
+            Ok(ChordWork::Release(released)) => {
+                for key in released {
+                    keys.unregister(key);
+                }
+            }
+            Ok(ChordWork::Start { keys: chord_keys, generation }) => {
+                if !registry.is_waiting(generation) {
+                    continue;
+                }
+                for key in chord_keys {
+                    keys.register(key);
+                }
+                waiting = Some((generation, Instant::now() + timeout));
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                let Some((generation, _)) = waiting.take() else {
+                    continue;
+                };
+                for key in registry.expire(generation).unwrap_or_default() {
+                    keys.unregister(key);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+// Register `bindings` in place of the active ones. Returns them normalized.
+// Must not run on the thread that delivers shortcut events.
+fn apply(app: &tauri::AppHandle, bindings: &[ShortcutBinding]) -> Result<Vec<ShortcutBinding>, String> {
+    let compiled = compile(bindings)?;
+    let state = app.state::<AppState>();
+    let old = state.shortcuts.bindings();
+    register_swap(app, &old, &compiled)?;
+    let normalized = compiled
+        .iter()
+        .map(|binding| ShortcutBinding {
+            action: binding.action,
+            accelerator: binding.accelerator.clone(),
+        })
+        .collect();
+    let released = state.shortcuts.replace(compiled);
+    if !released.is_empty() {
+        state.shortcuts.send(app, ChordWork::Release(released));
+    }
+    Ok(normalized)
+}
+
+// Startup: register the saved bindings, falling back to the defaults if they
+// are invalid or cannot be registered.
+pub fn register_initial(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let saved = state.settings.lock().unwrap().shortcuts.clone();
+    let normalized = match apply(app, &saved) {
+        Ok(normalized) => normalized,
+        Err(e) => {
+            println!("[shortcuts] {}; using the default shortcuts", e);
+            match apply(app, &default_bindings()) {
+                Ok(normalized) => normalized,
+                Err(e) => {
+                    println!("[shortcuts] Failed to register default shortcuts: {}", e);
+                    return;
+                }
+            }
+        }
+    };
+    if normalized != saved {
+        let snapshot = {
+            let mut settings = state.settings.lock().unwrap();
+            settings.shortcuts = normalized;
+            settings.clone()
+        };
+        let _ = save_settings_to_file(&snapshot);
+    }
+}
+
+// Global shortcut handler. The plugin holds its registry lock while this runs,
+// so (un)registering chord keys happens on the chord worker.
+pub fn handle(app: &tauri::AppHandle, shortcut: &Shortcut, state: ShortcutState) {
+    if state != ShortcutState::Pressed {
+        return;
+    }
+    if app.get_webview_window("shortcut-config").is_some() {
+        return;
+    }
+    let registry = &app.state::<AppState>().shortcuts;
+    let Press {
+        action,
+        release,
+        start_chord,
+    } = registry.press(shortcut.id());
+
+    // Release first: pressing the prefix again re-registers the same keys the
+    // previous chord is giving back
+    if !release.is_empty() {
+        registry.send(app, ChordWork::Release(release));
+    }
+    if let Some((keys, generation)) = start_chord {
+        registry.send(app, ChordWork::Start { keys, generation });
+    }
+    if let Some(action) = action {
+        run_action(app, action);
+    }
+}
+
+// Shared by shortcuts and the tray menu.
+pub fn run_action(app: &tauri::AppHandle, action: ShortcutAction) {
+    match action {
+        ShortcutAction::Area => spawn_capture_job(app, CaptureMode::Area),
+        ShortcutAction::Fullscreen => spawn_capture_job(app, CaptureMode::Fullscreen),
+        ShortcutAction::ActiveWindow => spawn_capture_job(app, CaptureMode::ActiveWindow),
+        ShortcutAction::RepeatRegion => spawn_capture_job(app, CaptureMode::LastRegion),
+        ShortcutAction::ClipboardImport => spawn_capture_job(app, CaptureMode::Clipboard),
+        ShortcutAction::StitchSession => stitch_session::toggle_session(app),
+        ShortcutAction::StitchSelection => stitch_session::stitch_from_sources(app),
+        ShortcutAction::OpenHistory => {
+            if let Err(e) = open_history_window(app) {
+                println!("[history] Failed to open history window: {}", e);
+            }
+        }
+        ShortcutAction::ShowApp => {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn update_shortcuts(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    bindings: Vec<ShortcutBinding>,
+) -> Result<Vec<ShortcutBinding>, AppError> {
+    let previous = state.settings.lock().unwrap().shortcuts.clone();
+    // Invalid, duplicate and unregistrable shortcuts all end up here
+    let normalized = apply(&app, &bindings).map_err(AppError::ShortcutConflict)?;
+
+    let settings_snapshot = {
+        let mut settings = state.settings.lock().unwrap();
+        settings.shortcuts = normalized.clone();
+        settings.clone()
+    };
+    if let Err(e) = save_settings_to_file(&settings_snapshot) {
+        state.settings.lock().unwrap().shortcuts = previous.clone();
+        if let Err(restore_error) = apply(&app, &previous) {
+            println!("[shortcuts] Failed to restore previous shortcuts: {}", restore_error);
+        }
+        return Err(AppError::Failed(e));
+    }
+
+    update_tray_labels(&app).map_err(AppError::Failed)?;
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(bindings: &[(ShortcutAction, &str)]) -> ShortcutRegistry {
+        let bindings: Vec<ShortcutBinding> = bindings
+            .iter()
+            .map(|&(action, accelerator)| ShortcutBinding {
+                action,
+                accelerator: accelerator.to_string(),
+            })
+            .collect();
+        let registry = ShortcutRegistry::default();
+        registry.replace(compile(&bindings).unwrap());
+        registry
+    }
+
+    fn id(accelerator: &str) -> u32 {
+        parse_accelerator(accelerator).unwrap().1[0].id()
+    }
+
+    fn second_step(accelerator: &str) -> u32 {
+        parse_accelerator(accelerator).unwrap().1[1].id()
+    }
+
+    fn ids(shortcuts: &[Shortcut]) -> Vec<u32> {
+        shortcuts.iter().map(|s| s.id()).collect()
+    }
+
+    fn chords() -> ShortcutRegistry {
+        registry(&[
+            (ShortcutAction::Area, "Ctrl+Alt+S, A"),
+            (ShortcutAction::Fullscreen, "Ctrl+Alt+S, B"),
+            (ShortcutAction::OpenHistory, "F13"),
+        ])
+    }
+
+    #[test]
+    fn single_step_and_bare_function_keys_fire_directly() {
+        let registry = registry(&[(ShortcutAction::Area, "Cmd+Shift+4"), (ShortcutAction::OpenHistory, "F13")]);
+
+        let press = registry.press(id("Cmd+Shift+4"));
+        assert_eq!(press.action, Some(ShortcutAction::Area));
+        assert!(press.release.is_empty() && press.start_chord.is_none());
+
+        assert_eq!(registry.press(id("F13")).action, Some(ShortcutAction::OpenHistory));
+        assert_eq!(registry.press(id("F14")).action, None);
+    }
+
+    #[test]
+    fn bare_keys_need_a_modifier_unless_they_are_function_or_second_steps() {
+        assert!(parse_accelerator("A").is_err());
+        assert!(parse_accelerator("F13").is_ok());
+        assert!(parse_accelerator("Ctrl+Alt+S, A").is_ok());
+        assert!(parse_accelerator("Ctrl+S, A, B").is_err());
+    }
+
+    #[test]
+    fn prefix_registers_second_steps_until_one_is_pressed() {
+        let registry = chords();
+
+        let press = registry.press(id("Ctrl+Alt+S"));
+        assert_eq!(press.action, None);
+        let (keys, _) = press.start_chord.unwrap();
+        assert_eq!(ids(&keys), [second_step("Ctrl+Alt+S, A"), second_step("Ctrl+Alt+S, B")]);
+
+        let second = second_step("Ctrl+Alt+S, A");
+        let press = registry.press(second);
+        assert_eq!(press.action, Some(ShortcutAction::Area));
+        assert_eq!(ids(&press.release), ids(&keys));
+        assert!(press.start_chord.is_none());
+
+        // The chord is over, so the bare key does nothing now
+        assert_eq!(registry.press(second).action, None);
+    }
+
+    #[test]
+    fn another_key_ends_the_chord_and_runs_as_usual() {
+        let registry = chords();
+        registry.press(id("Ctrl+Alt+S"));
+
+        let press = registry.press(id("F13"));
+
+        assert_eq!(press.action, Some(ShortcutAction::OpenHistory));
+        assert_eq!(press.release.len(), 2);
+    }
+
+    #[test]
+    fn timeout_only_expires_the_chord_it_started() {
+        let registry = chords();
+        let (_, first) = registry.press(id("Ctrl+Alt+S")).start_chord.unwrap();
+
+        // Pressing the prefix again gives back the keys and takes them again
+        let press = registry.press(id("Ctrl+Alt+S"));
+        let (keys, second) = press.start_chord.unwrap();
+        assert_eq!(ids(&press.release), ids(&keys));
+        assert_ne!(first, second);
+
+        assert!(registry.expire(first).is_none());
+        assert_eq!(ids(&registry.expire(second).unwrap()), ids(&keys));
+        assert!(registry.expire(second).is_none());
+        assert_eq!(registry.press(second_step("Ctrl+Alt+S, A")).action, None);
+    }
+
+    #[test]
+    fn second_steps_that_are_already_registered_stay_registered() {
+        let registry = registry(&[
+            (ShortcutAction::Area, "Ctrl+Alt+S, F13"),
+            (ShortcutAction::OpenHistory, "F13"),
+        ]);
+
+        let (keys, _) = registry.press(id("Ctrl+Alt+S")).start_chord.unwrap();
+        assert!(keys.is_empty());
+        assert_eq!(registry.press(id("F13")).action, Some(ShortcutAction::Area));
+        assert_eq!(registry.press(id("F13")).action, Some(ShortcutAction::OpenHistory));
+    }
+
+    #[test]
+    fn replacing_bindings_releases_a_waiting_chord() {
+        let registry = chords();
+        registry.press(id("Ctrl+Alt+S"));
+
+        let released = registry.replace(Vec::new());
+
+        assert_eq!(released.len(), 2);
+        assert_eq!(registry.press(id("F13")).action, None);
+    }
+
+    // Records which keys the worker left registered.
+    #[derive(Default)]
+    struct FakeKeys {
+        registered: Mutex<Vec<u32>>,
+    }
+
+    impl ChordKeys for FakeKeys {
+        fn register(&self, key: Shortcut) {
+            self.registered.lock().unwrap().push(key.id());
+        }
+
+        fn unregister(&self, key: Shortcut) {
+            self.registered.lock().unwrap().retain(|&id| id != key.id());
+        }
+    }
+
+    // Runs the worker over `work` until it is done, including any chord timeout.
+    fn run_worker(registry: &ShortcutRegistry, work: Vec<ChordWork>, timeout: Duration) -> Vec<u32> {
+        let keys = FakeKeys::default();
+        let (sender, receiver) = mpsc::channel();
+        for item in work {
+            sender.send(item).unwrap();
+        }
+        let fake = &keys;
+        std::thread::scope(|scope| {
+            scope.spawn(move || run_chord_worker(&receiver, registry, fake, timeout));
+            std::thread::sleep(timeout * 3);
+            drop(sender);
+        });
+        keys.registered.into_inner().unwrap()
+    }
+
+    fn work(press: Press) -> Vec<ChordWork> {
+        let mut work = Vec::new();
+        if !press.release.is_empty() {
+            work.push(ChordWork::Release(press.release));
+        }
+        if let Some((keys, generation)) = press.start_chord {
+            work.push(ChordWork::Start { keys, generation });
+        }
+        work
+    }
+
+    #[test]
+    fn a_press_before_the_chord_keys_are_registered_leaves_none_behind() {
+        let registry = chords();
+        // Both presses are queued before the worker gets to the first one
+        let mut queued = work(registry.press(id("Ctrl+Alt+S")));
+        queued.extend(work(registry.press(id("F13"))));
+
+        let registered = run_worker(&registry, queued, Duration::from_millis(50));
+
+        assert!(registered.is_empty());
+    }
+
+    #[test]
+    fn chord_keys_are_registered_until_the_timeout() {
+        let registry = chords();
+        let queued = work(registry.press(id("Ctrl+Alt+S")));
+        let keys = FakeKeys::default();
+        let (sender, receiver) = mpsc::channel();
+        for item in queued {
+            sender.send(item).unwrap();
+        }
+
+        let (fake, shared) = (&keys, &registry);
+        std::thread::scope(|scope| {
+            scope.spawn(move || run_chord_worker(&receiver, shared, fake, Duration::from_millis(200)));
+            std::thread::sleep(Duration::from_millis(50));
+            assert_eq!(
+                *keys.registered.lock().unwrap(),
+                [second_step("Ctrl+Alt+S, A"), second_step("Ctrl+Alt+S, B")]
+            );
+            std::thread::sleep(Duration::from_millis(400));
+            assert!(keys.registered.lock().unwrap().is_empty());
+            drop(sender);
+        });
+        assert_eq!(registry.press(second_step("Ctrl+Alt+S, A")).action, None);
+    }
+
+    #[test]
+    fn pressing_the_prefix_again_keeps_the_keys_registered() {
+        let registry = chords();
+        let mut queued = work(registry.press(id("Ctrl+Alt+S")));
+        queued.extend(work(registry.press(id("Ctrl+Alt+S"))));
+        let (sender, receiver) = mpsc::channel();
+        for item in queued {
+            sender.send(item).unwrap();
+        }
+        let keys = FakeKeys::default();
+
+        let (fake, shared) = (&keys, &registry);
+        std::thread::scope(|scope| {
+            scope.spawn(move || run_chord_worker(&receiver, shared, fake, Duration::from_secs(5)));
+            std::thread::sleep(Duration::from_millis(50));
+            assert_eq!(keys.registered.lock().unwrap().len(), 2);
+            drop(sender);
+        });
+    }
+}
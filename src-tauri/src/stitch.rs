@@ -0,0 +1,558 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::image_pipeline::{self, RgbaImage, ResizeFilter};
+use crate::image_probe::probe_dimensions;
+use crate::note_burn;
+
+// Native stitching: lay the sources out as a column, row or grid separated by
+// divider bars. The defaults match the old canvas stitcher (vertical, 30px gap
+// with a 12px #333333 bar, centred).
+//
+// Layout is planned from header dimensions only. Output is split into pages when
+// `max_page_height` would be exceeded, and each page decodes just its own images,
+// so memory is bounded by one page rather than by the number of sources.
+
+const LABEL_FONT_SIZE: f32 = 16.0;
+const LABEL_PADDING: u32 = 8;
+
+// Pages never grow past this on either side even without a configured limit;
+// JPEG caps dimensions at 65535.
+const HARD_MAX_PAGE_SIDE: u32 = 60_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StitchDirection {
+    #[default]
+    Vertical,
+    Horizontal,
+    Grid,
+}
+
+// Position of an image inside its cell along the cross axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StitchAlignment {
+    Start,
+    #[default]
+    Center,
+    End,
+}
+
+impl StitchAlignment {
+    fn offset(self, available: u32, used: u32) -> u32 {
+        let free = available.saturating_sub(used);
+        match self {
+            StitchAlignment::Start => 0,
+            StitchAlignment::Center => free / 2,
+            StitchAlignment::End => free,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StitchLabels {
+    #[default]
+    None,
+    // "1", "2", ...
+    Number,
+    Filename,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StitchLayout {
+    #[serde(default)]
+    pub direction: StitchDirection,
+    // Grid only; defaults to a roughly square grid
+    #[serde(default)]
+    pub columns: Option<u32>,
+    #[serde(default = "default_gap")]
+    pub gap: u32,
+    // Bar drawn in the middle of each gap; 0 for plain spacing
+    #[serde(default = "default_divider_thickness")]
+    pub divider_thickness: u32,
+    #[serde(default = "default_divider_color")]
+    pub divider_color: String,
+    #[serde(default = "default_background_color")]
+    pub background_color: String,
+    #[serde(default)]
+    pub alignment: StitchAlignment,
+    #[serde(default)]
+    pub labels: StitchLabels,
+    // Scale every image to the widest one
+    #[serde(default)]
+    pub normalize_width: bool,
+    // Start a new page (`_part2`, ...) rather than exceed this height in pixels
+    #[serde(default)]
+    pub max_page_height: Option<u32>,
+    // Split pages whose saved file is larger than this
+    #[serde(default)]
+    pub max_page_bytes: Option<u64>,
+}
+
+fn default_gap() -> u32 {
+    30
+}
+
+fn default_divider_thickness() -> u32 {
+    12
+}
+
+fn default_divider_color() -> String {
+    "#333333".to_string()
+}
+
+fn default_background_color() -> String {
+    "#ffffff".to_string()
+}
+
+impl Default for StitchLayout {
+    fn default() -> Self {
+        Self {
+            direction: StitchDirection::default(),
+            columns: None,
+            gap: default_gap(),
+            divider_thickness: default_divider_thickness(),
+            divider_color: default_divider_color(),
+            background_color: default_background_color(),
+            alignment: StitchAlignment::default(),
+            labels: StitchLabels::default(),
+            normalize_width: false,
+            max_page_height: None,
+            max_page_bytes: None,
+        }
+    }
+}
+
+impl StitchLayout {
+    pub fn validate(&self) -> Result<(), String> {
+        parse_color(&self.divider_color)?;
+        parse_color(&self.background_color)?;
+        if self.columns == Some(0) {
+            return Err("Stitch grid needs at least one column".to_string());
+        }
+        if self.max_page_height == Some(0) || self.max_page_bytes == Some(0) {
+            return Err("Stitch page limits must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+// "#rgb", "#rrggbb" or "#rrggbbaa".
+fn parse_color(value: &str) -> Result<[u8; 4], String> {
+    let invalid = || format!("Invalid colour \"{}\" (expected #rrggbb)", value);
+    let hex = value.trim().strip_prefix('#').ok_or_else(invalid)?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    match hex.len() {
+        3 => {
+            let short = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).map(|v| v * 17).map_err(|_| invalid());
+            Ok([short(0)?, short(1)?, short(2)?, 255])
+        }
+        6 => Ok([channel(0)?, channel(2)?, channel(4)?, 255]),
+        8 => Ok([channel(0)?, channel(2)?, channel(4)?, channel(6)?]),
+        _ => Err(invalid()),
+    }
+}
+
+// One source image: where to read it, its caption, and its size in the output.
+#[derive(Debug, Clone)]
+pub struct StitchItem {
+    pub path: PathBuf,
+    pub label: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct StitchOutput {
+    pub image: RgbaImage,
+    // Largest source image on the page, used to size the editor window
+    pub max_single_width: u32,
+    pub max_single_height: u32,
+}
+
+pub fn label_for(labels: StitchLabels, path: &Path, index: usize) -> String {
+    match labels {
+        StitchLabels::None => String::new(),
+        StitchLabels::Number => (index + 1).to_string(),
+        StitchLabels::Filename => path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+// Probe every source and work out its output size. `sources` is (path to read, label).
+pub fn prepare_items(sources: Vec<(PathBuf, String)>, layout: &StitchLayout) -> Result<Vec<StitchItem>, AppError> {
+    layout.validate().map_err(AppError::Invalid)?;
+    let mut items = Vec::with_capacity(sources.len());
+    for (index, (path, label)) in sources.into_iter().enumerate() {
+        let (width, height) = probe_dimensions(&path)
+            .map_err(|e| AppError::image(format!("Failed to read image {}", index + 1), e))?;
+        items.push(StitchItem {
+            path,
+            label,
+            width,
+            height,
+        });
+    }
+
+    if layout.normalize_width {
+        let target = items.iter().map(|item| item.width).max().unwrap_or(1);
+        for item in items.iter_mut() {
+            item.height = (item.height as u64 * target as u64 / item.width.max(1) as u64).max(1) as u32;
+            item.width = target;
+        }
+    }
+    Ok(items)
+}
+
+// Cell positions for a set of items.
+struct Grid {
+    columns: usize,
+    column_widths: Vec<u32>,
+    row_heights: Vec<u32>,
+    label_height: u32,
+    width: u32,
+    height: u32,
+}
+
+fn checked_total(values: impl Iterator<Item = u32>, gap: u32, count: usize) -> Result<u32, String> {
+    let gaps = (count.saturating_sub(1) as u64) * gap as u64;
+    let total = values.map(u64::from).sum::<u64>() + gaps;
+    u32::try_from(total).map_err(|_| "Stitched image would be too large".to_string())
+}
+
+// Whether an RGBA canvas of this size stays under the decoder's memory limit.
+fn fits_in_memory(width: u32, height: u32) -> bool {
+    width as u64 * height as u64 * 4 <= image_pipeline::MAX_DECODED_BYTES as u64
+}
+
+fn grid_for(items: &[StitchItem], layout: &StitchLayout, total_items: usize) -> Result<Grid, String> {
+    let label_height = if layout.labels == StitchLabels::None {
+        0
+    } else {
+        note_burn::label_height(LABEL_FONT_SIZE)
+    };
+    let count = items.len();
+    // Grid column count comes from the whole set so pages break on the same rows.
+    // Only a last page shorter than one row ends up with fewer columns.
+    let columns = match layout.direction {
+        StitchDirection::Vertical => 1,
+        StitchDirection::Horizontal => count,
+        StitchDirection::Grid => layout
+            .columns
+            .map(|c| c as usize)
+            .unwrap_or_else(|| (total_items as f64).sqrt().ceil() as usize),
+    }
+    .clamp(1, count.max(1));
+    let rows = count.div_ceil(columns);
+
+    let column_widths: Vec<u32> = (0..columns)
+        .map(|col| (col..count).step_by(columns).map(|i| items[i].width).max().unwrap_or(0))
+        .collect();
+    let row_heights: Vec<u32> = (0..rows)
+        .map(|row| {
+            items[row * columns..((row + 1) * columns).min(count)]
+                .iter()
+                .map(|item| item.height + label_height)
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    Ok(Grid {
+        width: checked_total(column_widths.iter().copied(), layout.gap, columns)?,
+        height: checked_total(row_heights.iter().copied(), layout.gap, rows)?,
+        columns,
+        column_widths,
+        row_heights,
+        label_height,
+    })
+}
+
+// Group items into pages no taller than the limit, no wider than the hard cap
+// (which matters for horizontal strips) and small enough to hold in memory.
+// Grid pages break on whole rows. An item larger than the limit gets a page of
+// its own.
+pub fn paginate(items: &[StitchItem], layout: &StitchLayout) -> Result<Vec<std::ops::Range<usize>>, String> {
+    let max_height = layout
+        .max_page_height
+        .unwrap_or(HARD_MAX_PAGE_SIDE)
+        .min(HARD_MAX_PAGE_SIDE);
+    let step = page_step(items, layout)?;
+
+    let mut pages = Vec::new();
+    let mut start = 0;
+    while start < items.len() {
+        let mut end = (start + step).min(items.len());
+        while end < items.len() {
+            let next = (end + step).min(items.len());
+            let grid = grid_for(&items[start..next], layout, items.len())?;
+            if grid.height > max_height || grid.width > HARD_MAX_PAGE_SIDE || !fits_in_memory(grid.width, grid.height) {
+                break;
+            }
+            end = next;
+        }
+        pages.push(start..end);
+        start = end;
+    }
+    Ok(pages)
+}
+
+// Pages start and end on multiples of this: one grid row, otherwise one image.
+fn page_step(items: &[StitchItem], layout: &StitchLayout) -> Result<usize, String> {
+    match layout.direction {
+        StitchDirection::Grid => Ok(grid_for(items, layout, items.len())?.columns),
+        _ => Ok(1),
+    }
+}
+
+// Split a page from `paginate` in two, between grid rows for a grid. None when
+// the page is a single image or a single grid row.
+pub fn split_page(
+    items: &[StitchItem],
+    layout: &StitchLayout,
+    page: std::ops::Range<usize>,
+) -> Result<Option<(std::ops::Range<usize>, std::ops::Range<usize>)>, String> {
+    let step = page_step(items, layout)?;
+    let rows = page.len().div_ceil(step);
+    if rows < 2 {
+        return Ok(None);
+    }
+    let middle = page.start + rows / 2 * step;
+    Ok(Some((page.start..middle, middle..page.end)))
+}
+
+fn fill(image: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: [u8; 4]) {
+    let x_end = (x + width).min(image.width);
+    let y_end = (y + height).min(image.height);
+    for row in y..y_end {
+        let start = (row as usize * image.width as usize + x as usize) * 4;
+        let end = (row as usize * image.width as usize + x_end as usize) * 4;
+        for pixel in image.pixels[start..end].chunks_exact_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+}
+
+// Source-over blend so transparent screenshots sit on the background colour.
+fn blit(canvas: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32) {
+    let row_len = image.width as usize * 4;
+    for row in 0..image.height as usize {
+        let src = &image.pixels[row * row_len..(row + 1) * row_len];
+        let start = ((y as usize + row) * canvas.width as usize + x as usize) * 4;
+        let dst = &mut canvas.pixels[start..start + row_len];
+        for (d, s) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+            let alpha = s[3] as u32;
+            if alpha == 255 {
+                d.copy_from_slice(s);
+                continue;
+            }
+            for c in 0..3 {
+                d[c] = ((s[c] as u32 * alpha + d[c] as u32 * (255 - alpha)) / 255) as u8;
+            }
+            d[3] = (alpha + d[3] as u32 * (255 - alpha) / 255) as u8;
+        }
+    }
+}
+
+// Decode and compose one page. `total_items` is the size of the whole stitch.
+// `on_item` runs after each image is drawn; an error from it aborts the page.
+pub fn render_page(
+    items: &[StitchItem],
+    layout: &StitchLayout,
+    filter: ResizeFilter,
+    total_items: usize,
+    on_item: &mut dyn FnMut() -> Result<(), AppError>,
+) -> Result<StitchOutput, AppError> {
+    let background = parse_color(&layout.background_color).map_err(AppError::Invalid)?;
+    let divider = parse_color(&layout.divider_color).map_err(AppError::Invalid)?;
+    let grid = grid_for(items, layout, total_items).map_err(AppError::Invalid)?;
+    let (width, height) = (grid.width, grid.height);
+    // Only reached when a single image or grid row is already too big
+    if !fits_in_memory(width, height) {
+        return Err(AppError::Invalid(format!(
+            "Stitched page would be too large ({}x{})",
+            width, height
+        )));
+    }
+
+    let mut canvas = RgbaImage::new(width, height);
+    fill(&mut canvas, 0, 0, width, height, background);
+
+    // Divider bars run across the whole canvas, centred in each gap
+    let thickness = layout.divider_thickness.min(layout.gap);
+    let bar_offset = (layout.gap - thickness) / 2;
+    if thickness > 0 {
+        let mut y = 0;
+        for row_height in &grid.row_heights[..grid.row_heights.len() - 1] {
+            y += row_height;
+            fill(&mut canvas, 0, y + bar_offset, width, thickness, divider);
+            y += layout.gap;
+        }
+        let mut x = 0;
+        for column_width in &grid.column_widths[..grid.columns - 1] {
+            x += column_width;
+            fill(&mut canvas, x + bar_offset, 0, thickness, height, divider);
+            x += layout.gap;
+        }
+    }
+
+    let mut y = 0;
+    for (row, row_height) in grid.row_heights.iter().enumerate() {
+        let mut x = 0;
+        for (col, column_width) in grid.column_widths.iter().enumerate() {
+            let index = row * grid.columns + col;
+            let Some(item) = items.get(index) else {
+                break;
+            };
+            let mut image = image_pipeline::decode_file(&item.path)
+                .map_err(|e| AppError::image(format!("Failed to read {}", item.path.display()), e))?;
+            if image.width != item.width || image.height != item.height {
+                image = image_pipeline::resize(&image, item.width, item.height, filter);
+            }
+
+            let cell_height = item.height + grid.label_height;
+            let cell_x = x + layout.alignment.offset(*column_width, item.width);
+            let cell_y = y + layout.alignment.offset(*row_height, cell_height);
+            if grid.label_height > 0 && !item.label.is_empty() {
+                let label_width = item.width.saturating_sub(LABEL_PADDING * 2).max(1);
+                note_burn::draw_label(&mut canvas, &item.label, cell_x + LABEL_PADDING, cell_y, label_width, LABEL_FONT_SIZE).map_err(AppError::Failed)?;
+            }
+            blit(&mut canvas, &image, cell_x, cell_y + grid.label_height);
+            on_item()?;
+            x += column_width + layout.gap;
+        }
+        y += row_height + layout.gap;
+    }
+
+    Ok(StitchOutput {
+        image: canvas,
+        max_single_width: items.iter().map(|item| item.width).max().unwrap_or(0),
+        max_single_height: items.iter().map(|item| item.height).max().unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(sizes: &[(u32, u32)]) -> Vec<StitchItem> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(index, &(width, height))| StitchItem {
+                path: PathBuf::from(format!("{}.png", index)),
+                label: String::new(),
+                width,
+                height,
+            })
+            .collect()
+    }
+
+    fn layout(direction: StitchDirection, columns: Option<u32>, max_page_height: Option<u32>) -> StitchLayout {
+        StitchLayout {
+            direction,
+            columns,
+            max_page_height,
+            ..StitchLayout::default()
+        }
+    }
+
+    #[test]
+    fn everything_fits_on_one_page_without_a_limit() {
+        let items = items(&[(100, 100); 5]);
+        for direction in [StitchDirection::Vertical, StitchDirection::Horizontal, StitchDirection::Grid] {
+            assert_eq!(paginate(&items, &layout(direction, None, None)).unwrap(), [0..5]);
+        }
+    }
+
+    #[test]
+    fn vertical_pages_stay_under_the_height_limit() {
+        // 100, then 100 + 30 gap + 100 = 230, then 360
+        let items = items(&[(100, 100); 5]);
+        let pages = paginate(&items, &layout(StitchDirection::Vertical, None, Some(250))).unwrap();
+        assert_eq!(pages, [0..2, 2..4, 4..5]);
+    }
+
+    #[test]
+    fn an_oversized_image_gets_its_own_page() {
+        let items = items(&[(100, 100), (100, 500), (100, 100)]);
+        let pages = paginate(&items, &layout(StitchDirection::Vertical, None, Some(250))).unwrap();
+        assert_eq!(pages, [0..1, 1..2, 2..3]);
+    }
+
+    #[test]
+    fn grid_pages_break_on_whole_rows() {
+        let items = items(&[(100, 100); 5]);
+        let pages = paginate(&items, &layout(StitchDirection::Grid, Some(2), Some(250))).unwrap();
+        assert_eq!(pages, [0..4, 4..5]);
+
+        // Three columns from the square root of five; a page holds one row
+        let pages = paginate(&items, &layout(StitchDirection::Grid, None, Some(150))).unwrap();
+        assert_eq!(pages, [0..3, 3..5]);
+    }
+
+    #[test]
+    fn horizontal_strips_respect_the_hard_width_cap() {
+        let items = items(&[(40_000, 100); 3]);
+        let pages = paginate(&items, &layout(StitchDirection::Horizontal, None, None)).unwrap();
+        assert_eq!(pages, [0..1, 1..2, 2..3]);
+    }
+
+    #[test]
+    fn pages_stay_under_the_memory_limit() {
+        // 5K-wide captures: 15 of them would be under the height cap but over 1 GiB
+        let items = items(&[(5120, 3500); 15]);
+        let vertical = layout(StitchDirection::Vertical, None, None);
+        assert_eq!(paginate(&items, &vertical).unwrap(), [0..14, 14..15]);
+
+        // 100 Retina captures in a 10x10 grid: five rows per page
+        let items = items(&[(2880, 1800); 100]);
+        let grid = layout(StitchDirection::Grid, None, None);
+        let pages = paginate(&items, &grid).unwrap();
+        assert_eq!(pages, [0..50, 50..100]);
+        for page in pages {
+            let shape = grid_for(&items[page], &grid, items.len()).unwrap();
+            assert!(fits_in_memory(shape.width, shape.height));
+        }
+    }
+
+    #[test]
+    fn split_halves_by_image_or_by_grid_row() {
+        let items = items(&[(100, 100); 7]);
+
+        let vertical = layout(StitchDirection::Vertical, None, None);
+        assert_eq!(split_page(&items, &vertical, 0..4).unwrap(), Some((0..2, 2..4)));
+        assert_eq!(split_page(&items, &vertical, 2..5).unwrap(), Some((2..3, 3..5)));
+        assert_eq!(split_page(&items, &vertical, 3..4).unwrap(), None);
+
+        let grid = layout(StitchDirection::Grid, Some(3), None);
+        assert_eq!(split_page(&items, &grid, 0..7).unwrap(), Some((0..3, 3..7)));
+        assert_eq!(split_page(&items, &grid, 3..7).unwrap(), Some((3..6, 6..7)));
+        assert_eq!(split_page(&items, &grid, 0..3).unwrap(), None);
+        assert_eq!(split_page(&items, &grid, 6..7).unwrap(), None);
+    }
+
+    #[test]
+    fn unreadable_sources_keep_their_error_kind() {
+        let dir = std::env::temp_dir().join(format!("screenshotapp-stitch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let text = dir.join("notes.png");
+        std::fs::write(&text, "not an image").unwrap();
+        let missing = dir.join("missing.png");
+        let layout = StitchLayout::default();
+
+        let error = prepare_items(vec![(missing, String::new())], &layout).unwrap_err();
+        assert_eq!(error.kind(), "notFound");
+        let error = prepare_items(vec![(text, String::new())], &layout).unwrap_err();
+        assert_eq!(error.kind(), "unsupported");
+    }
+}
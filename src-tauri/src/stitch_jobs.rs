@@ -0,0 +1,323 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+use crate::error::AppError;
+use crate::stitch_session;
+use crate::{open_rename_popup, reveal_in_file_manager, show_alert, AppState};
+
+// Registry of stitch jobs. One job runs at a time; the shortcut, tray and
+// commands all ask the registry before starting one. Every status or progress
+// change is emitted as a `stitch-job-updated` event carrying the job; the stitch
+// HUD shows it and cancels through `cancel_stitch_job`.
+
+// Finished jobs kept for `list_stitch_jobs`
+const FINISHED_JOBS_KEPT: usize = 20;
+
+static STITCH_JOB_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    fn is_active(self) -> bool {
+        matches!(self, JobStatus::Queued | JobStatus::Running)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StitchJob {
+    pub id: u64,
+    pub status: JobStatus,
+    // Images composed so far, out of `total`
+    pub completed: usize,
+    pub total: usize,
+    pub paths: Vec<String>,
+    pub error: Option<JobError>,
+}
+
+// A failed job's error, kept after the `AppError` is gone. Same shape as a
+// serialised `AppError`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobError {
+    pub kind: &'static str,
+    pub message: String,
+    pub details: Option<String>,
+}
+
+impl From<&AppError> for JobError {
+    fn from(error: &AppError) -> Self {
+        Self {
+            kind: error.kind(),
+            message: error.message(),
+            details: error.details(),
+        }
+    }
+}
+
+struct JobEntry {
+    job: StitchJob,
+    cancel: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<Vec<JobEntry>>,
+}
+
+impl JobRegistry {
+    // Queue a job unless one is already queued or running.
+    fn enqueue(&self, total: usize) -> Result<(StitchJob, Arc<AtomicBool>), AppError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.iter().any(|entry| entry.job.status.is_active()) {
+            return Err(AppError::Busy("A stitch is already running".to_string()));
+        }
+        let finished = jobs.len().saturating_sub(FINISHED_JOBS_KEPT);
+        jobs.drain(..finished);
+
+        let job = StitchJob {
+            id: STITCH_JOB_COUNTER.fetch_add(1, Ordering::Relaxed),
+            status: JobStatus::Queued,
+            completed: 0,
+            total,
+            paths: Vec::new(),
+            error: None,
+        };
+        let cancel = Arc::new(AtomicBool::new(false));
+        jobs.push(JobEntry {
+            job: job.clone(),
+            cancel: cancel.clone(),
+        });
+        Ok((job, cancel))
+    }
+
+    fn update(&self, id: u64, change: impl FnOnce(&mut StitchJob)) -> Option<StitchJob> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let entry = jobs.iter_mut().find(|entry| entry.job.id == id)?;
+        change(&mut entry.job);
+        Some(entry.job.clone())
+    }
+
+    pub fn has_active(&self) -> bool {
+        self.jobs.lock().unwrap().iter().any(|entry| entry.job.status.is_active())
+    }
+
+    pub fn list(&self) -> Vec<StitchJob> {
+        self.jobs.lock().unwrap().iter().rev().map(|entry| entry.job.clone()).collect()
+    }
+
+    pub fn cancel(&self, id: u64) -> Result<(), AppError> {
+        let jobs = self.jobs.lock().unwrap();
+        let entry = jobs
+            .iter()
+            .find(|entry| entry.job.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("Unknown stitch job {}", id)))?;
+        if !entry.job.status.is_active() {
+            return Err(AppError::Invalid(format!("Stitch job {} has already finished", id)));
+        }
+        entry.cancel.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// The job's final status once `run` has returned.
+fn apply_result(job: &mut StitchJob, result: &Result<Vec<String>, AppError>) {
+    match result {
+        Ok(paths) => {
+            job.status = JobStatus::Done;
+            job.completed = job.total;
+            job.paths = paths.clone();
+        }
+        Err(e) if e.is_cancelled() => job.status = JobStatus::Cancelled,
+        Err(e) => {
+            job.status = JobStatus::Failed;
+            job.error = Some(JobError::from(e));
+        }
+    }
+}
+
+// Handed to the running job for progress reports and cancellation checks.
+pub struct JobHandle {
+    app: tauri::AppHandle,
+    id: u64,
+    cancel: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    fn update(&self, change: impl FnOnce(&mut StitchJob)) {
+        let updated = self.app.state::<AppState>().stitch_jobs.update(self.id, change);
+        if let Some(job) = updated {
+            let _ = self.app.emit("stitch-job-updated", job);
+        }
+    }
+
+    pub fn check_cancelled(&self) -> Result<(), AppError> {
+        if self.cancel.load(Ordering::Relaxed) {
+            Err(AppError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Called after each image is composed. Errors once the job has been cancelled.
+    pub fn image_done(&self) -> Result<(), AppError> {
+        self.update(|job| job.completed = (job.completed + 1).min(job.total));
+        self.check_cancelled()
+    }
+
+    pub fn set_total(&self, total: usize) {
+        self.update(|job| job.total = total);
+    }
+
+    // Pages over the byte budget are re-rendered, so progress can step back.
+    pub fn set_completed(&self, completed: usize) {
+        self.update(|job| job.completed = completed.min(job.total));
+    }
+}
+
+// Register a job and run it off the main thread. With `open_result` the single
+// result opens the rename popup, several pages are revealed, and errors are alerted.
+pub fn spawn_job(
+    app: &tauri::AppHandle,
+    total: usize,
+    open_result: bool,
+    run: impl FnOnce(&AppState, &JobHandle) -> Result<Vec<String>, AppError> + Send + 'static,
+) -> Result<u64, AppError> {
+    let (job, cancel) = app.state::<AppState>().stitch_jobs.enqueue(total)?;
+    let _ = app.emit("stitch-job-updated", job.clone());
+    // Callers may hold the session lock, so open directly rather than refresh
+    stitch_session::open_hud(app);
+    let handle = JobHandle {
+        app: app.clone(),
+        id: job.id,
+        cancel,
+    };
+
+    std::thread::spawn(move || {
+        handle.update(|job| job.status = JobStatus::Running);
+        let state = handle.app.state::<AppState>();
+        let result = run(state.inner(), &handle);
+        handle.update(|job| apply_result(job, &result));
+        println!("[stitch] job {} finished: {:?}", handle.id, result);
+        stitch_session::refresh_hud(&handle.app);
+        if !open_result {
+            return;
+        }
+        match result {
+            Ok(paths) if paths.len() == 1 => {
+                let _ = open_rename_popup(handle.app.clone(), paths[0].clone());
+            }
+            // Parts share one name; renaming a single part would break the set
+            Ok(paths) => {
+                if let Some(first) = paths.first() {
+                    let runner = handle.app.state::<AppState>().runner.clone();
+                    let _ = reveal_in_file_manager(runner.as_ref(), first);
+                }
+            }
+            Err(e) if e.is_cancelled() => {}
+            Err(e) => {
+                let _ = show_alert("Stitch Error".to_string(), e.to_string());
+            }
+        }
+    });
+    Ok(job.id)
+}
+
+#[tauri::command]
+pub fn list_stitch_jobs(state: tauri::State<AppState>) -> Vec<StitchJob> {
+    state.stitch_jobs.list()
+}
+
+#[tauri::command]
+pub fn cancel_stitch_job(state: tauri::State<AppState>, id: u64) -> Result<(), AppError> {
+    state.stitch_jobs.cancel(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finish(registry: &JobRegistry, id: u64, result: Result<Vec<String>, AppError>) -> StitchJob {
+        registry.update(id, |job| apply_result(job, &result)).unwrap()
+    }
+
+    #[test]
+    fn only_one_job_runs_at_a_time() {
+        let registry = JobRegistry::default();
+        let (job, _) = registry.enqueue(3).unwrap();
+        assert!(registry.has_active());
+
+        assert_eq!(registry.enqueue(2).unwrap_err().kind(), "busy");
+        registry.update(job.id, |job| job.status = JobStatus::Running);
+        assert_eq!(registry.enqueue(2).unwrap_err().kind(), "busy");
+    }
+
+    #[test]
+    fn a_new_job_can_start_after_one_fails() {
+        let registry = JobRegistry::default();
+        let (job, _) = registry.enqueue(3).unwrap();
+
+        let failed = finish(&registry, job.id, Err(AppError::Failed("Out of disk space".to_string())));
+
+        assert_eq!(failed.status, JobStatus::Failed);
+        let error = failed.error.unwrap();
+        assert_eq!((error.kind, error.message.as_str()), ("failed", "Out of disk space"));
+        assert!(!registry.has_active());
+        assert!(registry.enqueue(3).is_ok());
+    }
+
+    #[test]
+    fn cancel_sets_the_job_flag() {
+        let registry = JobRegistry::default();
+        let (job, cancel) = registry.enqueue(3).unwrap();
+
+        registry.cancel(job.id).unwrap();
+        assert!(cancel.load(Ordering::Relaxed));
+        assert_eq!(registry.cancel(job.id + 1000).unwrap_err().kind(), "notFound");
+
+        let cancelled = finish(&registry, job.id, Err(AppError::Cancelled));
+        assert_eq!(cancelled.status, JobStatus::Cancelled);
+        assert!(cancelled.error.is_none());
+        // Too late once it has finished
+        assert_eq!(registry.cancel(job.id).unwrap_err().kind(), "invalid");
+    }
+
+    #[test]
+    fn done_job_keeps_its_paths_and_completes_progress() {
+        let registry = JobRegistry::default();
+        let (job, _) = registry.enqueue(3).unwrap();
+        registry.update(job.id, |job| job.completed = 1);
+
+        let done = finish(&registry, job.id, Ok(vec!["/shots/a_part1.jpg".to_string(), "/shots/a_part2.jpg".to_string()]));
+
+        assert_eq!(done.status, JobStatus::Done);
+        assert_eq!(done.completed, 3);
+        assert_eq!(done.paths, ["/shots/a_part1.jpg", "/shots/a_part2.jpg"]);
+        assert_eq!(registry.list()[0].status, JobStatus::Done);
+    }
+
+    #[test]
+    fn only_recent_finished_jobs_are_kept() {
+        let registry = JobRegistry::default();
+        for _ in 0..FINISHED_JOBS_KEPT + 5 {
+            let (job, _) = registry.enqueue(1).unwrap();
+            finish(&registry, job.id, Ok(Vec::new()));
+        }
+        let (latest, _) = registry.enqueue(1).unwrap();
+
+        let jobs = registry.list();
+        assert_eq!(jobs.len(), FINISHED_JOBS_KEPT + 1);
+        assert_eq!(jobs[0].id, latest.id);
+    }
+}
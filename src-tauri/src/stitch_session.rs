@@ -0,0 +1,302 @@
+use serde::Serialize;
+use tauri::{webview::WebviewWindowBuilder, Emitter, Manager};
+
+use crate::error::AppError;
+use crate::stitch_jobs;
+use crate::{collect_sources, run_stitch, AppState};
+
+// Stitch session: the stitch shortcut starts collecting, every capture made
+// meanwhile is appended instead of opening the rename popup, and pressing the
+// shortcut again (or Enter in the HUD) stitches them and opens the rename popup once.
+// The stitch itself runs as a job in `stitch_jobs`. The HUD stays open while a
+// session is collecting or a job is running, and shows the job's progress.
+
+#[derive(Debug, Clone, Default)]
+pub enum StitchSession {
+    #[default]
+    Idle,
+    Collecting {
+        paths: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StitchSessionEvent {
+    pub active: bool,
+    pub count: usize,
+}
+
+impl StitchSession {
+    // Returns false if a session is already collecting.
+    fn start(&mut self) -> bool {
+        if self.is_collecting() {
+            return false;
+        }
+        *self = StitchSession::Collecting { paths: Vec::new() };
+        true
+    }
+
+    // Returns the new capture count, or None when no session is collecting.
+    fn append(&mut self, path: &str) -> Option<usize> {
+        let StitchSession::Collecting { paths } = self else {
+            return None;
+        };
+        paths.push(path.to_string());
+        Some(paths.len())
+    }
+
+    // End the session, handing its captures to `spawn` when there are enough to
+    // stitch. If `spawn` fails (e.g. a previous stitch is still running) the
+    // session keeps collecting. Returns how many captures are being stitched.
+    fn finish(&mut self, spawn: impl FnOnce(Vec<String>) -> Result<(), AppError>) -> Result<usize, AppError> {
+        let StitchSession::Collecting { paths } = self else {
+            return Err(AppError::NotFound("No stitch session is running".to_string()));
+        };
+        let paths = paths.clone();
+        let count = paths.len();
+        if count >= 2 {
+            spawn(paths)?;
+        }
+        *self = StitchSession::Idle;
+        if count < 2 {
+            return Err(AppError::Invalid("Need at least 2 captures to stitch".to_string()));
+        }
+        Ok(count)
+    }
+
+    // Returns false if no session was collecting.
+    fn cancel(&mut self) -> bool {
+        std::mem::take(self).is_collecting()
+    }
+
+    fn is_collecting(&self) -> bool {
+        matches!(self, StitchSession::Collecting { .. })
+    }
+
+    fn event(&self) -> StitchSessionEvent {
+        match self {
+            StitchSession::Collecting { paths } => StitchSessionEvent {
+                active: true,
+                count: paths.len(),
+            },
+            StitchSession::Idle => StitchSessionEvent { active: false, count: 0 },
+        }
+    }
+}
+
+const HUD_LABEL: &str = "stitch-hud";
+
+fn emit_session(app: &tauri::AppHandle, active: bool, count: usize) {
+    let _ = app.emit("stitch-session-updated", StitchSessionEvent { active, count });
+}
+
+pub fn open_hud(app: &tauri::AppHandle) {
+    if app.get_webview_window(HUD_LABEL).is_some() {
+        return;
+    }
+    let result = WebviewWindowBuilder::new(app, HUD_LABEL, tauri::WebviewUrl::App("/stitch-hud.html".into()))
+        .title("Stitch Session")
+        .inner_size(300.0, 64.0)
+        .resizable(false)
+        .always_on_top(true)
+        .decorations(false)
+        .transparent(true)
+        .focused(false)
+        .build();
+    if let Err(e) = result {
+        println!("[stitch] Failed to open session HUD: {}", e);
+    }
+}
+
+fn close_hud(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window(HUD_LABEL) {
+        let _ = window.close();
+    }
+}
+
+// Open or close the HUD to match the session and job state.
+pub fn refresh_hud(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let collecting = state.stitch_session.lock().unwrap().is_collecting();
+    if collecting || state.stitch_jobs.has_active() {
+        open_hud(app);
+    } else {
+        close_hud(app);
+    }
+}
+
+// Stitch shortcut: start a session, or finish the running one.
+pub fn toggle_session(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let started = state.stitch_session.lock().unwrap().start();
+    if started {
+        println!("[stitch] session started");
+        open_hud(app);
+        emit_session(app, true, 0);
+    } else if let Err(e) = finish_session(app) {
+        println!("[stitch] {}", e);
+    }
+}
+
+// Capture pipeline hook. Returns true when the capture was taken by a session.
+pub fn append_capture(app: &tauri::AppHandle, path: &str) -> bool {
+    let state = app.state::<AppState>();
+    let Some(count) = state.stitch_session.lock().unwrap().append(path) else {
+        return false;
+    };
+    println!("[stitch] session capture {} added: {}", count, path);
+    emit_session(app, true, count);
+    true
+}
+
+pub fn finish_session(app: &tauri::AppHandle) -> Result<(), AppError> {
+    let state = app.state::<AppState>();
+    let mut session = state.stitch_session.lock().unwrap();
+    let result = session.finish(|paths| {
+        let total = paths.len();
+        stitch_jobs::spawn_job(app, total, true, move |state, job| run_stitch(state, job, paths, None)).map(|_| ())
+    });
+    // Nothing to end, or the job couldn't start and the session keeps collecting
+    if matches!(result, Err(AppError::NotFound(_))) || session.is_collecting() {
+        return result.map(|_| ());
+    }
+    drop(session);
+    refresh_hud(app);
+    emit_session(app, false, 0);
+    match &result {
+        Ok(count) => println!("[stitch] session finished, stitching {} captures", count),
+        Err(e) => println!("[stitch] session ended: {}", e),
+    }
+    result.map(|_| ())
+}
+
+pub fn cancel_session(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let cancelled = state.stitch_session.lock().unwrap().cancel();
+    if cancelled {
+        println!("[stitch] session cancelled");
+    }
+    refresh_hud(app);
+    emit_session(app, false, 0);
+}
+
+// Tray item: stitch the configured source (Finder selection by default) right away.
+pub fn stitch_from_sources(app: &tauri::AppHandle) {
+    let result = stitch_jobs::spawn_job(app, 0, true, |state, job| {
+        let paths = collect_sources(state, None)?;
+        if paths.len() < 2 {
+            return Err(AppError::Invalid("Select at least 2 images to stitch".to_string()));
+        }
+        job.set_total(paths.len());
+        run_stitch(state, job, paths, None)
+    });
+    if let Err(e) = result {
+        println!("[stitch] tray stitch ignored: {}", e);
+    }
+}
+
+#[tauri::command]
+pub fn get_stitch_session(state: tauri::State<AppState>) -> StitchSessionEvent {
+    state.stitch_session.lock().unwrap().event()
+}
+
+#[tauri::command]
+pub fn finish_stitch_session(app: tauri::AppHandle) -> Result<(), AppError> {
+    finish_session(&app)
+}
+
+#[tauri::command]
+pub fn cancel_stitch_session(app: tauri::AppHandle) {
+    cancel_session(&app)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collecting(paths: &[&str]) -> StitchSession {
+        let mut session = StitchSession::default();
+        assert!(session.start());
+        for path in paths {
+            session.append(path);
+        }
+        session
+    }
+
+    #[test]
+    fn captures_are_only_taken_while_collecting() {
+        let mut session = StitchSession::default();
+        assert_eq!(session.append("/a.png"), None);
+
+        assert!(session.start());
+        assert!(!session.start());
+        assert_eq!(session.append("/a.png"), Some(1));
+        assert_eq!(session.append("/b.png"), Some(2));
+
+        let event = session.event();
+        assert!(event.active);
+        assert_eq!(event.count, 2);
+    }
+
+    #[test]
+    fn finish_hands_the_captures_to_the_stitch() {
+        let mut session = collecting(&["/a.png", "/b.png"]);
+        let mut spawned = Vec::new();
+
+        let count = session
+            .finish(|paths| {
+                spawned = paths;
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(spawned, ["/a.png", "/b.png"]);
+        assert!(!session.is_collecting());
+    }
+
+    #[test]
+    fn finish_with_too_few_captures_ends_without_stitching() {
+        let mut session = collecting(&["/a.png"]);
+
+        let error = session.finish(|_| panic!("nothing to stitch")).unwrap_err();
+
+        assert_eq!(error.kind(), "invalid");
+        assert!(!session.is_collecting());
+    }
+
+    #[test]
+    fn session_is_kept_if_the_stitch_cannot_start() {
+        let mut session = collecting(&["/a.png", "/b.png"]);
+
+        let error = session
+            .finish(|_| Err(AppError::Busy("A stitch is already running".to_string())))
+            .unwrap_err();
+
+        assert_eq!(error.kind(), "busy");
+        assert_eq!(session.event().count, 2);
+        // Finishing again once the other stitch is done still has every capture
+        assert_eq!(session.append("/c.png"), Some(3));
+        assert_eq!(session.finish(|_| Ok(())).unwrap(), 3);
+    }
+
+    #[test]
+    fn finish_or_cancel_without_a_session() {
+        let mut session = StitchSession::default();
+
+        assert_eq!(session.finish(|_| Ok(())).unwrap_err().kind(), "notFound");
+        assert!(!session.cancel());
+    }
+
+    #[test]
+    fn cancel_drops_the_captures() {
+        let mut session = collecting(&["/a.png", "/b.png"]);
+
+        assert!(session.cancel());
+
+        assert!(!session.is_collecting());
+        assert_eq!(session.event().count, 0);
+        assert_eq!(session.append("/c.png"), None);
+    }
+}
@@ -0,0 +1,311 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::history::{self, HistoryQuery};
+use crate::image_pipeline::RgbaImage;
+use crate::image_probe::{self, ImageFormat};
+use crate::output;
+
+// Where the images for a stitch come from. Several sources can be combined in
+// one request; their paths are concatenated in order.
+
+// Extensions we look at when listing a folder or reading pasted paths. The
+// header is checked too, since the stitcher can only decode PNG and JPEG.
+const STITCH_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "jpe", "jfif"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum StitchSource {
+    // Current Finder selection (needs Automation permission)
+    #[default]
+    Finder,
+    // The newest `count` captures still on disk, oldest first
+    History {
+        count: usize,
+        #[serde(default)]
+        mode: Option<String>,
+    },
+    // Image data and/or file paths currently on the clipboard
+    Clipboard,
+    Paths { paths: Vec<String> },
+    // Images in `path` whose filename matches `pattern` (`*` and `?`, case-insensitive), by name
+    Directory {
+        path: String,
+        #[serde(default)]
+        pattern: Option<String>,
+    },
+}
+
+impl StitchSource {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            StitchSource::History { count: 0, .. } => Err("History stitch needs a count of at least 1".to_string()),
+            StitchSource::Directory { path, .. } if path.trim().is_empty() => {
+                Err("Stitch folder is empty".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn has_stitch_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| STITCH_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+fn has_decodable_header(path: &Path) -> bool {
+    let mut header = [0u8; 8];
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+    let len = file.read(&mut header).unwrap_or(0);
+    matches!(image_probe::detect_format(&header[..len]), Some(ImageFormat::Png | ImageFormat::Jpeg))
+}
+
+// Shared filter for every source: an existing PNG/JPEG file whose dimensions can be read.
+pub fn is_stitchable(path: &Path) -> bool {
+    if !path.is_file() || !has_stitch_extension(path) {
+        return false;
+    }
+    if !has_decodable_header(path) {
+        println!("[stitch] skipping unsupported image: {}", path.display());
+        return false;
+    }
+    match image_probe::probe_dimensions(path) {
+        Ok(_) => true,
+        Err(e) => {
+            println!("[stitch] skipping unreadable image {}: {}", path.display(), e);
+            false
+        }
+    }
+}
+
+pub fn filter_stitchable<I, S>(paths: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    paths
+        .into_iter()
+        .map(|p| p.as_ref().trim().to_string())
+        .filter(|p| !p.is_empty())
+        .filter(|p| is_stitchable(Path::new(p)))
+        .collect()
+}
+
+pub fn from_history(history: &Path, count: usize, mode: Option<String>) -> Result<Vec<String>, AppError> {
+    let query = HistoryQuery {
+        mode,
+        // Over-fetch so deleted files don't shrink the result
+        limit: Some(count.saturating_mul(4).max(50)),
+        ..HistoryQuery::default()
+    };
+    let mut paths: Vec<String> = filter_stitchable(
+        history::search(history, &query)?
+            .into_iter()
+            .filter(|item| item.exists)
+            .map(|item| item.entry.path),
+    );
+    paths.truncate(count);
+    paths.reverse();
+    Ok(paths)
+}
+
+// `*` matches any run of characters, `?` exactly one. Iterative: on a mismatch,
+// retry from the most recent `*` with it covering one more character, which
+// keeps the worst case at O(pattern * name).
+fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*`, and where in `name` that star's match ends
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after_star, matched)) => {
+                    p = after_star;
+                    n = matched + 1;
+                    star = Some((after_star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+pub fn from_directory(dir: &str, pattern: Option<&str>) -> Result<Vec<String>, AppError> {
+    let dir = output::expand_home(dir.trim());
+    let entries = std::fs::read_dir(&dir)
+        .map_err(|e| AppError::io(format!("Failed to read folder {}", dir.display()), e))?;
+    let pattern: Vec<char> = pattern
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .unwrap_or("*")
+        .to_lowercase()
+        .chars()
+        .collect();
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| !name.starts_with('.'))
+        .filter(|name| {
+            let lowered: Vec<char> = name.to_lowercase().chars().collect();
+            glob_matches(&pattern, &lowered)
+        })
+        .collect();
+    names.sort();
+    Ok(filter_stitchable(
+        names.iter().map(|name| dir.join(name).to_string_lossy().to_string()),
+    ))
+}
+
+// Clipboard text holding paths, one per line, as plain paths or file:// URLs.
+pub fn paths_from_text(text: &str) -> Vec<String> {
+    filter_stitchable(text.lines().map(|line| {
+        let line = line.trim();
+        match line.strip_prefix("file://") {
+            Some(rest) => urlencoding::decode(rest)
+                .map(|decoded| decoded.into_owned())
+                .unwrap_or_else(|_| rest.to_string()),
+            None => line.to_string(),
+        }
+    }))
+}
+
+pub struct ClipboardContents {
+    pub paths: Vec<String>,
+    pub image: Option<RgbaImage>,
+}
+
+pub fn read_clipboard() -> Result<ClipboardContents, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| format!("Failed to open clipboard: {}", e))?;
+    let paths = clipboard
+        .get_text()
+        .map(|text| paths_from_text(&text))
+        .unwrap_or_default();
+    let image = clipboard.get_image().ok().map(|data| RgbaImage {
+        width: data.width as u32,
+        height: data.height as u32,
+        pixels: data.bytes.into_owned(),
+    });
+    Ok(ClipboardContents { paths, image })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_pipeline::encode_png;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        glob_matches(&pattern, &name)
+    }
+
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("screenshotapp-sources-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_png(path: &Path) {
+        std::fs::write(path, encode_png(&RgbaImage::new(2, 3)).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn glob_stars_and_question_marks() {
+        assert!(matches("*", ""));
+        assert!(matches("*", "shot.png"));
+        assert!(matches("shot-*.png", "shot-01.png"));
+        assert!(matches("shot-*.png", "shot-.png"));
+        assert!(matches("s?ot*", "shot.jpg"));
+        assert!(matches("*.*", "a.b"));
+        assert!(matches("a**b", "ab"));
+        assert!(matches("*a*b", "xaxxab"));
+        assert!(!matches("shot-*.png", "shot-01.jpg"));
+        assert!(!matches("?", ""));
+        assert!(!matches("a?", "a"));
+        assert!(!matches("abc", "ab"));
+        assert!(!matches("", "a"));
+        assert!(matches("é*", "été"));
+    }
+
+    #[test]
+    fn glob_does_not_backtrack_exponentially() {
+        let name = "a".repeat(200);
+        let start = std::time::Instant::now();
+        assert!(!matches("*a*a*a*a*a*a*a*a*a*b", &name));
+        assert!(matches("*a*a*a*a*a*a*a*a*a*", &name));
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn directory_lists_matching_images_by_name() {
+        let dir = scratch_dir("dir");
+        for name in ["b.png", "a.PNG", "other.png", "c.jpg.txt", ".hidden.png"] {
+            write_png(&dir.join(name));
+        }
+        // Right extension but not an image
+        std::fs::write(dir.join("fake.png"), "not a png").unwrap();
+
+        let all = from_directory(dir.to_str().unwrap(), None).unwrap();
+        let names: Vec<String> = all
+            .iter()
+            .map(|p| Path::new(p).file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, ["a.PNG", "b.png", "other.png"]);
+
+        let filtered = from_directory(dir.to_str().unwrap(), Some(" A* ")).unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert!(filtered[0].ends_with("a.PNG"));
+
+        assert!(from_directory(dir.join("missing").to_str().unwrap(), None).is_err());
+    }
+
+    #[test]
+    fn text_paths_accept_plain_paths_and_file_urls() {
+        let dir = scratch_dir("text");
+        let plain = dir.join("plain.png");
+        let spaced = dir.join("with space.png");
+        write_png(&plain);
+        write_png(&spaced);
+        let url = format!("file://{}", spaced.to_string_lossy().replace(' ', "%20"));
+        let text = format!(
+            "  {}  \n\n{}\n{}\nnot a path\n",
+            plain.display(),
+            url,
+            dir.join("missing.png").display()
+        );
+
+        let paths = paths_from_text(&text);
+
+        assert_eq!(paths, [plain.to_string_lossy().to_string(), spaced.to_string_lossy().to_string()]);
+    }
+
+    #[test]
+    fn history_and_directory_sources_validate() {
+        assert!(StitchSource::History { count: 0, mode: None }.validate().is_err());
+        assert!(StitchSource::History { count: 2, mode: None }.validate().is_ok());
+        let empty = StitchSource::Directory {
+            path: "  ".to_string(),
+            pattern: None,
+        };
+        assert!(empty.validate().is_err());
+    }
+}
@@ -3,45 +3,143 @@ import { invoke } from "@tauri-apps/api/core";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import { listen } from "@tauri-apps/api/event";
 import "./App.css";
-import FilenameTemplateEditor from "./FilenameTemplate";
+import FilenameTemplateEditor, { FilenameTemplate } from "./FilenameTemplate";
 import { formatShortcutForDisplay } from "./shortcutFormat";
-import { MAX_STITCH_IMAGES } from "./constants";
+import { errorMessage, isCancelled, type AppError } from "./appError";
 
 
-interface FilenameBlock {
-  id: string;
-  enabled: boolean;
-  value?: string;
+type ResizeFilter = "lanczos" | "bilinear";
+type CaptureBackendKind = "auto" | "screencapture" | "x11" | "waylandPortal";
+
+interface OutputSettings {
+  directory: string;
+  areaDirectory?: string | null;
+  fullscreenDirectory?: string | null;
+  stitchDirectory?: string | null;
+}
+
+type NotePlacement = "top" | "bottom";
+
+type StitchDirection = "vertical" | "horizontal" | "grid";
+
+interface StitchLayout {
+  direction: StitchDirection;
+  columns?: number | null;
+  gap: number;
+  dividerThickness: number;
+  dividerColor: string;
+  backgroundColor: string;
+  alignment: "start" | "center" | "end";
+  labels: "none" | "number" | "filename";
+  normalizeWidth: boolean;
+  maxPageHeight?: number | null;
+  maxPageBytes?: number | null;
+}
+
+type StitchSource =
+  | { kind: "finder" }
+  | { kind: "history"; count: number; mode?: string | null }
+  | { kind: "clipboard" }
+  | { kind: "paths"; paths: string[] }
+  | { kind: "directory"; path: string; pattern?: string | null };
+
+const DEFAULT_HISTORY_STITCH_COUNT = 4;
+
+const DEFAULT_STITCH_LAYOUT: StitchLayout = {
+  direction: "vertical",
+  gap: 30,
+  dividerThickness: 12,
+  dividerColor: "#333333",
+  backgroundColor: "#ffffff",
+  alignment: "center",
+  labels: "none",
+  normalizeWidth: false,
+};
+
+type ShortcutAction =
+  | "area"
+  | "fullscreen"
+  | "activeWindow"
+  | "repeatRegion"
+  | "clipboardImport"
+  | "stitchSession"
+  | "stitchSelection"
+  | "openHistory"
+  | "showApp";
+
+interface ShortcutBinding {
+  action: ShortcutAction;
+  accelerator: string;
 }
 
-interface FilenameTemplate {
-  blocks: FilenameBlock[];
-  useCounter: boolean;
+const SHORTCUT_ACTIONS: { value: ShortcutAction; label: string }[] = [
+  { value: "area", label: "area" },
+  { value: "fullscreen", label: "fullscreen" },
+  { value: "activeWindow", label: "window" },
+  { value: "repeatRegion", label: "repeat area" },
+  { value: "clipboardImport", label: "clipboard" },
+  { value: "stitchSession", label: "stitch" },
+  { value: "stitchSelection", label: "stitch selection" },
+  { value: "openHistory", label: "history" },
+  { value: "showApp", label: "settings" },
+];
+
+function shortcutActionLabel(action: ShortcutAction): string {
+  return SHORTCUT_ACTIONS.find((option) => option.value === action)?.label ?? action;
 }
 
 interface Settings {
   quality: number;
   maxWidth: number;
+  resizeFilter: ResizeFilter;
+  captureBackend: CaptureBackendKind;
+  output: OutputSettings;
   notePrefixEnabled: boolean;
   notePrefix: string;
+  notePlacement: NotePlacement;
   filenameTemplate: FilenameTemplate;
-  fullscreenShortcut: string;
-  areaShortcut: string;
-  stitchShortcut: string;
+  stitchLayout: StitchLayout;
+  stitchSource: StitchSource;
+  shortcuts: ShortcutBinding[];
 }
 
 const DEFAULT_FILENAME_TEMPLATE: FilenameTemplate = {
-  blocks: [
-    { id: "prefix", enabled: true, value: "llm-scr" },
-    { id: "date", enabled: true },
-    { id: "time", enabled: true },
-    { id: "quality", enabled: true },
-    { id: "dimensions", enabled: true },
-    { id: "counter", enabled: false },
-  ],
-  useCounter: false,
+  pattern: "{prefix}_{date:%m-%d}_{time:%H-%M-%S}_{quality}%_{w}x{h}",
+  prefix: "llm-scr",
+  counterStart: 1,
+  counterPadding: 3,
+  counterReset: "global",
 };
 
+interface CacheStats {
+  files: number;
+  bytes: number;
+  maxBytes: number;
+}
+
+type Permission = "screenRecording" | "automation";
+
+interface PermissionCheck {
+  permission: Permission;
+  target: string | null;
+  state: "granted" | "denied" | "notDetermined" | "notApplicable";
+  hint: string | null;
+}
+
+interface DiagnosticsReport {
+  platform: string;
+  checkedAt: string;
+  checks: PermissionCheck[];
+  ok: boolean;
+}
+
+function formatBytes(bytes: number): string {
+  if (bytes < 1024 * 1024) {
+    return `${Math.round(bytes / 1024)} KB`;
+  }
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+}
+
 const SIZE_OPTIONS = [
   { label: "Original", value: 0 },
   { label: "1920 x 1080", value: 1920 },
@@ -60,19 +158,32 @@ function App() {
   const [settings, setSettings] = useState<Settings>({
     quality: 70,
     maxWidth: 1024,
+    resizeFilter: "lanczos",
+    captureBackend: "auto",
+    output: { directory: "~/Desktop" },
     notePrefixEnabled: false,
     notePrefix: "",
+    notePlacement: "bottom",
     filenameTemplate: DEFAULT_FILENAME_TEMPLATE,
-    fullscreenShortcut: "Cmd+Shift+3",
-    areaShortcut: "Cmd+Shift+4",
-    stitchShortcut: "Cmd+Shift+2",
+    stitchLayout: DEFAULT_STITCH_LAYOUT,
+    stitchSource: { kind: "finder" },
+    shortcuts: [
+      { action: "fullscreen", accelerator: "Cmd+Shift+3" },
+      { action: "area", accelerator: "Cmd+Shift+4" },
+      { action: "stitchSession", accelerator: "Cmd+Shift+2" },
+      { action: "clipboardImport", accelerator: "Cmd+Shift+1" },
+    ],
   });
   const [lastSavedSettings, setLastSavedSettings] = useState<Settings | null>(null);
   const [saveStatus, setSaveStatus] = useState<"idle" | "dirty" | "saving" | "saved" | "error">("idle");
   const saveStatusTimeoutRef = useRef<number | null>(null);
   const [showFilenameTemplate, setShowFilenameTemplate] = useState(false);
   const [shortcutError, setShortcutError] = useState<string | null>(null);
+  const [captureError, setCaptureError] = useState<string | null>(null);
+  const [newShortcutAction, setNewShortcutAction] = useState<ShortcutAction>("activeWindow");
   const settingsPanelRef = useRef<HTMLDivElement | null>(null);
+  const [cacheStats, setCacheStats] = useState<CacheStats | null>(null);
+  const [permissionReport, setPermissionReport] = useState<DiagnosticsReport | null>(null);
 
   // Load settings on mount
   useEffect(() => {
@@ -82,6 +193,38 @@ function App() {
     }).catch(console.error);
   }, []);
 
+  // Load clipboard cache usage on mount
+  useEffect(() => {
+    invoke<CacheStats>("get_cache_stats").then(setCacheStats).catch(console.error);
+  }, []);
+
+  // Permission report from the startup self-test; re-checked on demand
+  useEffect(() => {
+    invoke<DiagnosticsReport>("check_permissions").then(setPermissionReport).catch(console.error);
+    const unlisten = listen<DiagnosticsReport>("permission-diagnostics", (event) => {
+      setPermissionReport(event.payload);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  const checkPermissions = async () => {
+    try {
+      setPermissionReport(await invoke<DiagnosticsReport>("check_permissions", { probeAutomation: true }));
+    } catch (error) {
+      console.error("Failed to check permissions:", error);
+    }
+  };
+
+  const clearCache = async () => {
+    try {
+      setCacheStats(await invoke<CacheStats>("clear_cache"));
+    } catch (error) {
+      console.error("Failed to clear clipboard cache:", error);
+    }
+  };
+
   // Save settings when they change
   const updateSettings = (newSettings: Settings) => {
     setSettings(newSettings);
@@ -120,21 +263,29 @@ function App() {
     }
   }
 
+  function reportCaptureError(e: unknown) {
+    if (isCancelled(e)) return;
+    console.error(e);
+    setCaptureError(errorMessage(e, "Screenshot failed"));
+  }
+
   async function takeScreenshot() {
     try {
       const filepath = await invoke<string>("take_screenshot");
+      setCaptureError(null);
       handleScreenshotTaken(filepath);
     } catch (e) {
-      console.error(e);
+      reportCaptureError(e);
     }
   }
 
   async function takeFullscreenScreenshot() {
     try {
       const filepath = await invoke<string>("take_fullscreen_screenshot");
+      setCaptureError(null);
       handleScreenshotTaken(filepath);
     } catch (e) {
-      console.error(e);
+      reportCaptureError(e);
     }
   }
 
@@ -148,78 +299,61 @@ function App() {
       takeFullscreenScreenshot();
     });
 
-    const unlistenStitch = listen("stitch-images", async () => {
-      console.log("[stitch] event received");
-      try {
-        const paths = await invoke<string[]>("get_finder_selection");
-        console.log("[stitch] finder selection count:", paths.length);
-        if (paths.length < 2) {
-          console.warn("[stitch] need at least 2 images");
-          await invoke("clear_stitch_lock");
-          return;
-        }
-        if (paths.length > MAX_STITCH_IMAGES) {
-          throw new Error(`Too many images (max ${MAX_STITCH_IMAGES})`);
-        }
-        
-        const { stitchImages } = await import("./stitch");
-        const stitch = stitchImages;
-        const result = await stitch(paths);
-        console.log("[stitch] stitch result", { width: result.width, height: result.height });
-        
-        const tempPath = await invoke<string>("save_stitch_temp", { 
-          base64Data: result.base64Data,
-          maxSingleImageHeight: result.maxSingleImageHeight,
-          maxSingleImageWidth: result.maxSingleImageWidth
-        });
-        console.log("[stitch] temp saved at", tempPath);
-        
-        await invoke("open_rename_popup", { filepath: tempPath });
-      } catch (e) {
-        console.error("Stitch failed:", e);
-        const errorMessage = typeof e === "string" ? e : e instanceof Error ? e.message : "Stitch failed";
-        if (errorMessage.includes("Too many images")) {
-          await invoke("show_alert", { title: "Stitch Error", message: errorMessage });
-        }
-        await invoke("clear_stitch_lock");
-      }
+    // Shortcut and tray captures run in Rust and report failures here
+    const unlistenError = listen<{ jobId: number; error: AppError }>("capture-error", (event) => {
+      reportCaptureError(event.payload.error);
     });
 
     return () => {
       unlistenArea.then((fn) => fn());
       unlistenFull.then((fn) => fn());
-      unlistenStitch.then((fn) => fn());
+      unlistenError.then((fn) => fn());
     };
   }, []);
 
+  async function applyShortcuts(bindings: ShortcutBinding[]) {
+    try {
+      const applied = await invoke<ShortcutBinding[]>("update_shortcuts", { bindings });
+      setSettings((current) => ({ ...current, shortcuts: applied }));
+      setShortcutError(null);
+    } catch (e) {
+      console.error("Failed to update shortcuts:", e);
+      setShortcutError(errorMessage(e, "Failed to update shortcuts"));
+    }
+  }
+
+  // target is the index of the binding being changed, or "new:<action>" to add one
+  function configureShortcut(target: string, currentShortcut: string) {
+    setShortcutError(null);
+    const index = target.startsWith("new:") ? -1 : Number(target);
+    invoke("open_shortcut_config", {
+      target,
+      currentShortcut,
+      otherShortcut: settings.shortcuts
+        .filter((_, i) => i !== index)
+        .map((binding) => binding.accelerator)
+        .join("|"),
+    });
+  }
+
   useEffect(() => {
     const unlistenShortcut = listen<{ target: string; shortcut: string }>(
       "shortcut-configured",
       async (event) => {
         const { target, shortcut } = event.payload;
-        const newSettings = { ...settings };
-
-        if (target === "fullscreen") {
-          newSettings.fullscreenShortcut = shortcut;
-        } else if (target === "area") {
-          newSettings.areaShortcut = shortcut;
-        } else if (target === "stitch") {
-          newSettings.stitchShortcut = shortcut;
-        }
+        let bindings: ShortcutBinding[];
 
-        try {
-          await invoke("update_shortcuts", {
-            fullscreenShortcut: newSettings.fullscreenShortcut,
-            areaShortcut: newSettings.areaShortcut,
-            stitchShortcut: newSettings.stitchShortcut,
-          });
-          setSettings(newSettings);
-          setShortcutError(null);
-        } catch (e) {
-          console.error("Failed to update shortcuts:", e);
-          const message = typeof e === "string" ? e : e instanceof Error ? e.message : "Failed to update shortcuts";
-          setShortcutError(message);
+        if (target.startsWith("new:")) {
+          const action = target.slice("new:".length) as ShortcutAction;
+          bindings = [...settings.shortcuts, { action, accelerator: shortcut }];
+        } else {
+          const index = Number(target);
+          bindings = settings.shortcuts.map((binding, i) =>
+            i === index ? { ...binding, accelerator: shortcut } : binding
+          );
         }
+
+        await applyShortcuts(bindings);
       }
     );
 
@@ -332,6 +466,41 @@ function App() {
             ))}
           </select>
         </div>
+        <div className="settings-row">
+          <label>Resize:</label>
+          <select
+            value={settings.resizeFilter}
+            onChange={(e) => updateSettings({ ...settings, resizeFilter: e.target.value as ResizeFilter })}
+            className="size-select"
+          >
+            <option value="lanczos">Lanczos (sharp)</option>
+            <option value="bilinear">Bilinear (fast)</option>
+          </select>
+        </div>
+        <div className="settings-row">
+          <label>Capture:</label>
+          <select
+            value={settings.captureBackend}
+            onChange={(e) => updateSettings({ ...settings, captureBackend: e.target.value as CaptureBackendKind })}
+            className="size-select"
+          >
+            <option value="auto">Auto</option>
+            <option value="screencapture">screencapture (macOS)</option>
+            <option value="x11">X11</option>
+            <option value="waylandPortal">Wayland portal</option>
+          </select>
+        </div>
+        <div className="settings-row">
+          <label>Save to:</label>
+          <input
+            type="text"
+            value={settings.output.directory}
+            onChange={(e) => updateSettings({ ...settings, output: { ...settings.output, directory: e.target.value } })}
+            placeholder="~/Desktop/{date}"
+            title="Tokens: {date} {year} {month} {day} {mode}"
+            className="prefix-input"
+          />
+        </div>
         <div className="settings-row prefix-row">
           <div className="prefix-toggle">
             <input
@@ -353,90 +522,157 @@ function App() {
           />
         </div>
         <div className="settings-row">
-          <button onClick={() => setShowFilenameTemplate(true)} className="template-btn">
-            Change Filename Template
-          </button>
+          <label>Stitch:</label>
+          <select
+            value={settings.stitchLayout.direction}
+            onChange={(e) =>
+              updateSettings({
+                ...settings,
+                stitchLayout: { ...settings.stitchLayout, direction: e.target.value as StitchDirection },
+              })
+            }
+            className="size-select"
+          >
+            <option value="vertical">Vertical</option>
+            <option value="horizontal">Horizontal</option>
+            <option value="grid">Grid</option>
+          </select>
         </div>
-        <div className="shortcuts-hint">
-          <span
-            className="shortcut-link"
-            role="button"
-            tabIndex={0}
-            onClick={() => {
-              setShortcutError(null);
-              invoke("open_shortcut_config", {
-                target: "fullscreen",
-                currentShortcut: settings.fullscreenShortcut,
-                otherShortcut: settings.areaShortcut + "," + settings.stitchShortcut,
-              });
-            }}
-            onKeyDown={(event) => {
-              if (event.key === "Enter" || event.key === " ") {
-                setShortcutError(null);
-                invoke("open_shortcut_config", {
-                  target: "fullscreen",
-                  currentShortcut: settings.fullscreenShortcut,
-                  otherShortcut: settings.areaShortcut + "," + settings.stitchShortcut,
-                });
-              }
+        <div className="settings-row">
+          <label>Stitch from:</label>
+          <select
+            value={settings.stitchSource.kind}
+            onChange={(e) => {
+              const kind = e.target.value;
+              const stitchSource: StitchSource =
+                kind === "history"
+                  ? { kind: "history", count: DEFAULT_HISTORY_STITCH_COUNT }
+                  : kind === "clipboard"
+                    ? { kind: "clipboard" }
+                    : { kind: "finder" };
+              updateSettings({ ...settings, stitchSource });
             }}
+            className="size-select"
           >
-            <kbd>{formatShortcutForDisplay(settings.fullscreenShortcut)}</kbd> fullscreen
-          </span>
-          {" · "}
-          <span
-            className="shortcut-link"
-            role="button"
-            tabIndex={0}
-            onClick={() => {
-              setShortcutError(null);
-              invoke("open_shortcut_config", {
-                target: "area",
-                currentShortcut: settings.areaShortcut,
-                otherShortcut: settings.fullscreenShortcut + "," + settings.stitchShortcut,
-              });
-            }}
-            onKeyDown={(event) => {
-              if (event.key === "Enter" || event.key === " ") {
-                setShortcutError(null);
-                invoke("open_shortcut_config", {
-                  target: "area",
-                  currentShortcut: settings.areaShortcut,
-                  otherShortcut: settings.fullscreenShortcut + "," + settings.stitchShortcut,
-                });
+            <option value="finder">Finder selection</option>
+            <option value="history">Latest captures</option>
+            <option value="clipboard">Clipboard</option>
+          </select>
+          {settings.stitchSource.kind === "history" && (
+            <input
+              type="number"
+              min={2}
+              max={100}
+              value={settings.stitchSource.count}
+              onChange={(e) =>
+                updateSettings({
+                  ...settings,
+                  stitchSource: { kind: "history", count: Math.max(2, Number(e.target.value) || 2) },
+                })
               }
-            }}
+              className="prefix-input"
+            />
+          )}
+        </div>
+        <div className="settings-row">
+          <label>Note:</label>
+          <select
+            value={settings.notePlacement}
+            onChange={(e) => updateSettings({ ...settings, notePlacement: e.target.value as NotePlacement })}
+            className="size-select"
           >
-            <kbd>{formatShortcutForDisplay(settings.areaShortcut)}</kbd> area
-          </span>
-          {" · "}
-          <span
-            className="shortcut-link"
-            role="button"
-            tabIndex={0}
-            onClick={() => {
-              setShortcutError(null);
-              invoke("open_shortcut_config", {
-                target: "stitch",
-                currentShortcut: settings.stitchShortcut,
-                otherShortcut: settings.fullscreenShortcut + "," + settings.areaShortcut,
-              });
-            }}
-            onKeyDown={(event) => {
-              if (event.key === "Enter" || event.key === " ") {
-                setShortcutError(null);
-                invoke("open_shortcut_config", {
-                  target: "stitch",
-                  currentShortcut: settings.stitchShortcut,
-                  otherShortcut: settings.fullscreenShortcut + "," + settings.areaShortcut,
-                });
-              }
-            }}
+            <option value="bottom">Below image</option>
+            <option value="top">Above image</option>
+          </select>
+        </div>
+        <div className="settings-row">
+          <label>Clipboard cache:</label>
+          <span>{cacheStats ? `${formatBytes(cacheStats.bytes)} (${cacheStats.files} files)` : "…"}</span>
+          <button onClick={clearCache} className="template-btn" disabled={!cacheStats || cacheStats.files === 0}>
+            Clear
+          </button>
+        </div>
+        {permissionReport && permissionReport.checks.some((check) => check.state !== "notApplicable") && (
+          <div className="settings-row">
+            <label>Permissions:</label>
+            <span>{permissionReport.ok ? "OK" : "Missing access"}</span>
+            <button onClick={checkPermissions} className="template-btn">
+              Check
+            </button>
+          </div>
+        )}
+        {permissionReport?.checks
+          .filter((check) => check.hint)
+          .map((check) => (
+            <div key={`${check.permission}-${check.target ?? ""}`} className="shortcut-error">
+              {check.hint}{" "}
+              <span
+                className="shortcut-link"
+                role="button"
+                tabIndex={0}
+                onClick={() => invoke("open_permission_settings", { permission: check.permission }).catch(console.error)}
+              >
+                Open Settings
+              </span>
+            </div>
+          ))}
+        <div className="settings-row">
+          <button onClick={() => setShowFilenameTemplate(true)} className="template-btn">
+            Change Filename Template
+          </button>
+        </div>
+        <div className="shortcuts-hint">
+          {settings.shortcuts.map((binding, index) => (
+            <span key={`${binding.action}-${index}`}>
+              {index > 0 && " · "}
+              <span
+                className="shortcut-link"
+                role="button"
+                tabIndex={0}
+                onClick={() => configureShortcut(String(index), binding.accelerator)}
+                onKeyDown={(event) => {
+                  if (event.key === "Enter" || event.key === " ") {
+                    configureShortcut(String(index), binding.accelerator);
+                  }
+                }}
+              >
+                <kbd>{formatShortcutForDisplay(binding.accelerator)}</kbd> {shortcutActionLabel(binding.action)}
+              </span>
+              <span
+                className="shortcut-link shortcut-remove"
+                role="button"
+                tabIndex={0}
+                title="Remove shortcut"
+                onClick={() => applyShortcuts(settings.shortcuts.filter((_, i) => i !== index))}
+                onKeyDown={(event) => {
+                  if (event.key === "Enter" || event.key === " ") {
+                    applyShortcuts(settings.shortcuts.filter((_, i) => i !== index));
+                  }
+                }}
+              >
+                ×
+              </span>
+            </span>
+          ))}
+        </div>
+        <div className="settings-row">
+          <label>Add shortcut:</label>
+          <select
+            value={newShortcutAction}
+            onChange={(e) => setNewShortcutAction(e.target.value as ShortcutAction)}
           >
-            <kbd>{formatShortcutForDisplay(settings.stitchShortcut)}</kbd> stitch
-          </span>
+            {SHORTCUT_ACTIONS.map((option) => (
+              <option key={option.value} value={option.value}>
+                {option.label}
+              </option>
+            ))}
+          </select>
+          <button onClick={() => configureShortcut(`new:${newShortcutAction}`, "")} className="template-btn">
+            Record
+          </button>
         </div>
         {shortcutError && <div className="shortcut-error">{shortcutError}</div>}
+        {captureError && <div className="shortcut-error">{captureError}</div>}
         <div className="button-row">
           <button onClick={saveSettings} className="save-btn">
             Save
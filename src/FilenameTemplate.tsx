@@ -1,161 +1,123 @@
 import { useState, useEffect } from "react";
-
-interface FilenameBlock {
-  id: string;
-  enabled: boolean;
-  value?: string;
+import { invoke } from "@tauri-apps/api/core";
+import { errorMessage } from "./appError";
+
+export interface FilenameTemplate {
+  pattern: string;
+  prefix: string;
+  counterStart: number;
+  counterPadding: number;
+  counterReset: CounterReset;
 }
 
-interface FilenameTemplate {
-  blocks: FilenameBlock[];
-  useCounter: boolean;
-}
+export type CounterReset = "global" | "daily";
 
 interface Props {
   template: FilenameTemplate;
   onTemplateChange: (template: FilenameTemplate) => void;
 }
 
-const BLOCK_LABELS: Record<string, string> = {
-  prefix: "Prefix",
-  date: "Date (MM-DD)",
-  time: "Time (HH-MM-SS)",
-  quality: "Quality (%)",
-  dimensions: "Dimensions (WxH)",
-  counter: "Counter (#001)",
-};
-
-const PLACEHOLDER_VALUES: Record<string, string> = {
-  prefix: "",
-  date: "01-20",
-  time: "14-35-42",
-  quality: "85",
-  dimensions: "1920x1080",
-  counter: "001",
-};
-
-const LOCKED_BLOCKS = ["time", "counter"];
+const TOKENS: { token: string; label: string }[] = [
+  { token: "{prefix}", label: "Prefix" },
+  { token: "{date:%m-%d}", label: "Date" },
+  { token: "{time:%H-%M-%S}", label: "Time" },
+  { token: "{quality}", label: "Quality" },
+  { token: "{w}x{h}", label: "Dimensions" },
+  { token: "{counter}", label: "Counter" },
+  { token: "{mode}", label: "Mode" },
+  { token: "{app}", label: "App" },
+  { token: "{title}", label: "Window title" },
+];
 
 function FilenameTemplate({ template, onTemplateChange }: Props) {
-  const [blocks, setBlocks] = useState<FilenameBlock[]>(template.blocks);
+  const [preview, setPreview] = useState<string>("");
+  const [error, setError] = useState<string | null>(null);
 
+  // Parsing and validation live in Rust so the preview matches real captures
   useEffect(() => {
-    setBlocks(template.blocks);
-  }, [template.blocks]);
-
-  const generatePreview = (): string => {
-    const parts: string[] = [];
-    for (const block of blocks) {
-      if (!block.enabled) continue;
-      if (block.id === "prefix") {
-        if (block.value) parts.push(block.value);
-      } else {
-        parts.push(PLACEHOLDER_VALUES[block.id]);
-      }
-    }
-    return parts.length > 0 ? parts.join("_") + ".jpg" : "screenshot.jpg";
-  };
-
-  const isTimeEnabled = blocks.find((b) => b.id === "time")?.enabled ?? false;
-  const isCounterEnabled = blocks.find((b) => b.id === "counter")?.enabled ?? false;
-  const showWarning = !isTimeEnabled && !isCounterEnabled;
-
-  const updateBlocks = (newBlocks: FilenameBlock[]) => {
-    setBlocks(newBlocks);
-    onTemplateChange({ ...template, blocks: newBlocks });
-  };
-
-  const handleToggle = (id: string) => {
-    const newBlocks = blocks.map((b) => {
-      if (b.id !== id) return b;
-      const newEnabled = !b.enabled;
-      if (!newEnabled && (id === "time" || id === "counter")) {
-        const otherId = id === "time" ? "counter" : "time";
-        const otherBlock = blocks.find((x) => x.id === otherId);
-        if (!otherBlock?.enabled) {
-          return b;
-        }
-      }
-      return { ...b, enabled: newEnabled };
-    });
-
-    if (id === "time" || id === "counter") {
-      const timeBlock = newBlocks.find((b) => b.id === "time");
-      const counterBlock = newBlocks.find((b) => b.id === "counter");
-      if (!timeBlock?.enabled && !counterBlock?.enabled) {
-        const otherIdx = newBlocks.findIndex((b) => b.id === (id === "time" ? "counter" : "time"));
-        if (otherIdx !== -1) {
-          newBlocks[otherIdx] = { ...newBlocks[otherIdx], enabled: true };
-        }
-      }
-    }
-
-    updateBlocks(newBlocks);
+    invoke<string>("preview_filename_template", { template })
+      .then((name) => {
+        setPreview(name);
+        setError(null);
+      })
+      .catch((e) => {
+        setError(errorMessage(e, "Invalid template"));
+      });
+  }, [template]);
+
+  const update = (changes: Partial<FilenameTemplate>) => {
+    onTemplateChange({ ...template, ...changes });
   };
 
-  const handlePrefixChange = (value: string) => {
-    const newBlocks = blocks.map((b) => (b.id === "prefix" ? { ...b, value } : b));
-    updateBlocks(newBlocks);
-  };
-
-  const moveBlock = (index: number, direction: "up" | "down") => {
-    const newIndex = direction === "up" ? index - 1 : index + 1;
-    if (newIndex < 0 || newIndex >= blocks.length) return;
-    const newBlocks = [...blocks];
-    [newBlocks[index], newBlocks[newIndex]] = [newBlocks[newIndex], newBlocks[index]];
-    updateBlocks(newBlocks);
+  const insertToken = (token: string) => {
+    update({ pattern: template.pattern + token });
   };
 
   return (
     <div className="filename-template">
-      <div className="preview-filename">{generatePreview()}</div>
+      <div className="preview-filename">{error ? "—" : preview}</div>
 
       <div className="template-header">
-        {showWarning && (
-          <div className="warning-message">Time OR Counter required</div>
-        )}
+        {error && <div className="warning-message">{error}</div>}
       </div>
 
+      <input
+        type="text"
+        value={template.pattern}
+        onChange={(e) => update({ pattern: e.target.value })}
+        spellCheck={false}
+        className="prefix-input"
+      />
+
       <div className="blocks-list">
-        {blocks.map((block, index) => (
-          <div key={block.id} className="block-row">
-            <div className="block-controls">
-              <button
-                onClick={() => moveBlock(index, "up")}
-                disabled={index === 0}
-                className="move-btn"
-              >
-                ▲
-              </button>
-              <button
-                onClick={() => moveBlock(index, "down")}
-                disabled={index === blocks.length - 1}
-                className="move-btn"
-              >
-                ▼
-              </button>
-            </div>
-            <label className="block-checkbox">
-              <input
-                type="checkbox"
-                checked={block.enabled}
-                onChange={() => handleToggle(block.id)}
-              />
-              {BLOCK_LABELS[block.id]}
-              {LOCKED_BLOCKS.includes(block.id) && <span className="lock-icon" title="Required for unique filenames. Either Time or Counter must be enabled.">🔒</span>}
-            </label>
-            {block.id === "prefix" && (
-              <input
-                type="text"
-                value={block.value || ""}
-                onChange={(e) => handlePrefixChange(e.target.value)}
-                placeholder="screenshot"
-                className="prefix-input"
-              />
-            )}
-          </div>
+        {TOKENS.map(({ token, label }) => (
+          <button key={token} onClick={() => insertToken(token)} className="move-btn" title={token}>
+            {label}
+          </button>
         ))}
       </div>
+
+      <div className="block-row">
+        <label className="block-checkbox">Prefix</label>
+        <input
+          type="text"
+          value={template.prefix}
+          onChange={(e) => update({ prefix: e.target.value })}
+          placeholder="screenshot"
+          className="prefix-input"
+        />
+      </div>
+      <div className="block-row">
+        <label className="block-checkbox">Counter start</label>
+        <input
+          type="number"
+          min={0}
+          value={template.counterStart}
+          onChange={(e) => update({ counterStart: Math.max(0, parseInt(e.target.value) || 0) })}
+          className="prefix-input"
+        />
+      </div>
+      <div className="block-row">
+        <label className="block-checkbox">Counter digits</label>
+        <input
+          type="number"
+          min={0}
+          max={10}
+          value={template.counterPadding}
+          onChange={(e) => update({ counterPadding: Math.min(10, Math.max(0, parseInt(e.target.value) || 0)) })}
+          className="prefix-input"
+        />
+      </div>
+      <div className="block-row">
+        <label className="block-checkbox">Counter resets</label>
+        <select
+          value={template.counterReset}
+          onChange={(e) => update({ counterReset: e.target.value as CounterReset })}
+        >
+          <option value="global">Never</option>
+          <option value="daily">Daily</option>
+        </select>
+      </div>
     </div>
   );
 }
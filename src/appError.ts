@@ -0,0 +1,34 @@
+// Mirrors `AppError` in src-tauri/src/error.rs: every command rejects with this shape.
+export type AppErrorKind =
+  | "cancelled"
+  | "permissionDenied"
+  | "notFound"
+  | "io"
+  | "decode"
+  | "invalid"
+  | "unsupported"
+  | "shortcutConflict"
+  | "windowBusy"
+  | "busy"
+  | "failed";
+
+export interface AppError {
+  kind: AppErrorKind;
+  message: string;
+  details: string | null;
+}
+
+export function isAppError(e: unknown): e is AppError {
+  return typeof e === "object" && e !== null && "kind" in e && "message" in e;
+}
+
+export function isCancelled(e: unknown): boolean {
+  return isAppError(e) && e.kind === "cancelled";
+}
+
+export function errorMessage(e: unknown, fallback: string): string {
+  if (isAppError(e)) return e.message;
+  if (typeof e === "string") return e;
+  if (e instanceof Error) return e.message;
+  return fallback;
+}
@@ -1 +0,0 @@
-export const MAX_STITCH_IMAGES = 8;
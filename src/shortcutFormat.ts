@@ -1,3 +1,6 @@
+// Second step of a chord follows the first after this separator.
+export const CHORD_SEPARATOR = ", ";
+
 const MODIFIER_ORDER = ["Cmd", "Shift", "Alt", "Ctrl"] as const;
 const MODIFIER_ALIASES: Record<string, (typeof MODIFIER_ORDER)[number]> = {
   cmd: "Cmd",
@@ -64,9 +67,12 @@ function normalizeKeyToken(token: string): string | null {
   if (/^[a-z]$/.test(lower)) {
     return lower.toUpperCase();
   }
-  if (/^f([1-9]|1[0-2])$/.test(lower)) {
+  if (/^f([1-9]|1[0-9]|2[0-4])$/.test(lower)) {
     return lower.toUpperCase();
   }
+  if (/^(numpad|num)[0-9]$/.test(lower)) {
+    return `Numpad${lower.slice(-1)}`;
+  }
 
   switch (lower) {
     case "space":
@@ -80,20 +86,93 @@ function normalizeKeyToken(token: string): string | null {
       return "Escape";
     case "backspace":
       return "Backspace";
+    case "numpadadd":
+    case "numadd":
+      return "NumpadAdd";
+    case "numpadsubtract":
+    case "numsubtract":
+      return "NumpadSubtract";
+    case "numpadmultiply":
+    case "nummultiply":
+      return "NumpadMultiply";
+    case "numpaddivide":
+    case "numdivide":
+      return "NumpadDivide";
+    case "numpaddecimal":
+    case "numdecimal":
+      return "NumpadDecimal";
+    case "numpadenter":
+    case "numenter":
+      return "NumpadEnter";
+    case "numpadequal":
+    case "numequal":
+      return "NumpadEqual";
+    case "arrowup":
+    case "up":
+      return "Up";
+    case "arrowdown":
+    case "down":
+      return "Down";
+    case "arrowleft":
+    case "left":
+      return "Left";
+    case "arrowright":
+    case "right":
+      return "Right";
+    case "home":
+      return "Home";
+    case "end":
+      return "End";
+    case "pageup":
+      return "PageUp";
+    case "pagedown":
+      return "PageDown";
+    case "insert":
+      return "Insert";
+    case "delete":
+      return "Delete";
+    case "printscreen":
+      return "PrintScreen";
+    case "mediaplaypause":
+      return "MediaPlayPause";
+    case "mediastop":
+      return "MediaStop";
+    case "mediatracknext":
+    case "medianext":
+      return "MediaNext";
+    case "mediatrackprevious":
+    case "mediaprevious":
+      return "MediaPrevious";
+    case "audiovolumeup":
+    case "volumeup":
+      return "VolumeUp";
+    case "audiovolumedown":
+    case "volumedown":
+      return "VolumeDown";
+    case "audiovolumemute":
+    case "volumemute":
+      return "VolumeMute";
     default:
       return null;
   }
 }
 
-export function normalizeShortcutString(shortcut: string): string | null {
-  if (!shortcut) return null;
+// Keys that work as a shortcut without any modifier.
+export function allowsBareKey(key: string): boolean {
+  return /^F(1[3-9]|2[0-4])$/.test(key)
+    || key.startsWith("Numpad")
+    || key === "PrintScreen"
+    || key.startsWith("Media")
+    || key.startsWith("Volume");
+}
 
-  const parts = shortcut
+function normalizeStep(step: string, secondStep: boolean): string | null {
+  const parts = step
     .split("+")
     .map((part) => part.trim())
     .filter(Boolean);
 
-  if (parts.length < 2) return null;
+  if (parts.length === 0) return null;
 
   const modifiers = new Set<(typeof MODIFIER_ORDER)[number]>();
   let key: string | null = null;
@@ -116,18 +195,37 @@ export function normalizeShortcutString(shortcut: string): string | null {
     key = normalizedKey;
   }
 
-  if (!key || modifiers.size === 0) return null;
+  if (!key) return null;
+  if (modifiers.size === 0 && !secondStep && !allowsBareKey(key)) return null;
 
   const orderedModifiers = MODIFIER_ORDER.filter((modifier) => modifiers.has(modifier));
   return [...orderedModifiers, key].join("+");
 }
 
+export function normalizeShortcutString(shortcut: string): string | null {
+  if (!shortcut) return null;
+
+  const steps = shortcut.split(CHORD_SEPARATOR);
+  if (steps.length > 2) return null;
+
+  const normalized: string[] = [];
+  for (const [index, step] of steps.entries()) {
+    const next = normalizeStep(step, index > 0);
+    if (!next) return null;
+    normalized.push(next);
+  }
+  return normalized.join(CHORD_SEPARATOR);
+}
+
 export function formatShortcutForDisplay(shortcut: string): string {
   const normalized = normalizeShortcutString(shortcut) ?? shortcut;
   return normalized
-    .replace(/Cmd/g, "⌘")
-    .replace(/Shift/g, "⇧")
-    .replace(/Alt/g, "⌥")
-    .replace(/Ctrl/g, "⌃")
-    .replace(/\+/g, "");
+    .split(CHORD_SEPARATOR)
+    .map((step) => step
+      .replace(/Cmd/g, "⌘")
+      .replace(/Shift/g, "⇧")
+      .replace(/Alt/g, "⌥")
+      .replace(/Ctrl/g, "⌃")
+      .replace(/\+/g, ""))
+    .join(" ");
 }
@@ -32,6 +32,8 @@ export default defineConfig(async () => ({
         note: resolve(__dirname, "note.html"),
         editor: resolve(__dirname, "editor.html"),
         shortcutConfig: resolve(__dirname, "shortcut-config.html"),
+        stitchHud: resolve(__dirname, "stitch-hud.html"),
+        history: resolve(__dirname, "history.html"),
       },
     },
   },